@@ -246,6 +246,7 @@ fn stitch(path: &str, out_folder: &str) -> (Mesh, Mesh)
     let fire_file_name = "fire.json";
     let out_model_file_name = "result.obj";
     let out_fire_model_name = "fire.obj";
+    let out_patches_file_name = "result.stl";
 
     println!("Started preprocessing of case in {}", path);
     println!("Output is found at: {}", out_folder);
@@ -259,17 +260,26 @@ fn stitch(path: &str, out_folder: &str) -> (Mesh, Mesh)
     );
 
     let mut in_mesh = MeshBuilder::new().with_obj(source).build().unwrap();
-    in_mesh.merge_overlapping_primitives().unwrap();
-    //in_mesh.collapse_small_faces(0.01);
-    //in_mesh.remove_lonely_primitives();
-    //::in_mesh::test_utility::test_is_valid(&in_mesh).unwrap();
+    let report = validation::validate(&in_mesh);
+    if !report.is_valid()
+    {
+        println!("The model has {} defects: {:?}", report.no_defects(), report);
+    }
 
     // Fix in_mesh
-    //in_mesh.smooth_vertices(0.1);
-    //in_mesh.flip_edges(0.9);
-    //in_mesh.collapse_small_faces(0.01);
-    //in_mesh.remove_lonely_primitives();
-    //::in_mesh::test_utility::test_is_valid(&in_mesh).unwrap();
+    let repair_report = repair::repair(&mut in_mesh, &repair::RepairOptions::default()).unwrap_or_else(
+        |err| {
+                eprintln!("Cannot repair the model: {:?}", err);
+                std::process::exit(2);
+            }
+        );
+    if !repair_report.is_unchanged()
+    {
+        println!("Repaired the model: {:?}", repair_report);
+    }
+
+    let feature_edges = features::feature_edges(&in_mesh, features::DEFAULT_ANGLE_THRESHOLD);
+    println!("The model has {} feature edges in {} polylines", feature_edges.no_edges(), feature_edges.polylines().len());
 
     exporter::save(&in_mesh, &format!("{}in_mesh.obj", out_folder)).unwrap();
 
@@ -282,10 +292,19 @@ fn stitch(path: &str, out_folder: &str) -> (Mesh, Mesh)
     );
     println!("Fire: {:?}", fire);
 
+    // Snap fire onto the surface of the model, penetrating the surface by half its radius
+    let placement = stitching::place_source(&in_mesh, &fire.position, &fire.normal, fire.radius, 0.5 * fire.radius).unwrap_or_else(
+        |err| {
+                eprintln!("Cannot place the fire on the model: {:?}", err);
+                std::process::exit(2);
+            }
+        );
+    println!("Fire center: {:?} on face {:?} with normal {:?}", placement.center, placement.face_id, placement.normal);
+
     // Create fire model
     let mut fire_mesh = tri_mesh::MeshBuilder::new().icosahedron().build().unwrap();
     fire_mesh.scale(fire.radius);
-    fire_mesh.translate(fire.position);
+    fire_mesh.translate(placement.center);
 
     exporter::save(&fire_mesh, &format!("{}{}", out_folder, out_fire_model_name)).unwrap_or_else(
     |err| {
@@ -299,43 +318,17 @@ fn stitch(path: &str, out_folder: &str) -> (Mesh, Mesh)
     println!("Stitching in_mesh: Vertices: {:?} and Faces: {:?}", in_mesh.no_vertices(), in_mesh.no_faces());
     println!("with fire_mesh: Vertices: {:?} and Faces: {:?}", fire_mesh.no_vertices(), fire_mesh.no_faces());
 
-    let mut out_mesh = fire_mesh.clone();
-    let (mut out_part_meshes, mut in_part_meshes) = out_mesh.split_at_intersection(&mut in_mesh);
-
-    print_and_save(&out_part_meshes, &format!("out_mesh"), out_folder);
-    print_and_save(&in_part_meshes, &format!("in_mesh"), out_folder);
-
-    let mut meshes_to_merge = Vec::new();
-
-    for submesh in out_part_meshes.drain(..) {
-        if mesh_is_inside_other(&submesh, &in_mesh, &fire.position)
-        {
-            meshes_to_merge.push(submesh);
-        }
-    }
-
-    for submesh in in_part_meshes.drain(..) {
-        if mesh_is_inside_other(&submesh, &out_mesh, &fire.position)
-        {
-            meshes_to_merge.push(submesh);
-        }
-    }
-
-    println!("Meshes to merge: {}", meshes_to_merge.len());
-    print_and_save(&meshes_to_merge, &format!("mesh_to_merge"), out_folder);
-
-    let mut iter = meshes_to_merge.drain(..);
-    let mut result_mesh = iter.next().unwrap();
-    for mesh in iter {
-        result_mesh.merge_with(&mesh).unwrap_or_else(
-            |err| {
-                    println!("Error in merging: {:?}", err);
-                    panic!("Error in merging: {:?}", err);
-                }
-            );
-    }
+    let stitched = stitching::stitch(&in_mesh, &fire_mesh, &placement.center).unwrap_or_else(
+        |err| {
+                eprintln!("Error in stitching: {:?}", err);
+                std::process::exit(2);
+            }
+        );
+    let result_mesh = stitched.mesh.clone();
 
     println!("Result in_mesh: Vertices: {:?} and Faces: {:?}", result_mesh.no_vertices(), result_mesh.no_faces());
+    println!("Faces from fire: {} and from model: {}", stitched.faces_from(stitching::Origin::Source(0)).len(),
+             stitched.faces_from(stitching::Origin::Model).len());
 
     // Save in_mesh
     exporter::save(&result_mesh, &format!("{}{}", out_folder, out_model_file_name)).unwrap_or_else(
@@ -344,45 +337,32 @@ fn stitch(path: &str, out_folder: &str) -> (Mesh, Mesh)
             std::process::exit(2);
         }
     );
-    (fire_mesh, result_mesh)
-}
-
-fn print_and_save(meshes: &Vec<tri_mesh::mesh::Mesh>, name: &str, folder: &str)
-{
-    let mut i = 0;
-    for mesh in meshes {
-        println!("Mesh {} {}: Vertices: {:?} and Faces: {:?}", name, i, mesh.no_vertices(), mesh.no_faces());
-        exporter::save(mesh, &format!("{}{}{}.obj", folder, name, i)).unwrap();
-        i += 1;
-    }
-}
 
-fn mesh_is_inside_other(mesh: &tri_mesh::mesh::Mesh, other: &tri_mesh::mesh::Mesh, point: &Vec3) -> bool
-{
-    for face_id in mesh.face_iter() {
-        let face_center = mesh.face_center(face_id);
-        if !mesh_blocks_view(other, &face_center, point)
-        {
-            return true;
+    // Save in_mesh with the origin of each face and the curvature at each vertex
+    let mut attributes = stitched.attributes();
+    curvature::add_curvature_layers(&result_mesh, &mut attributes);
+    exporter::save_with_attributes(&result_mesh, &attributes, &format!("{}result.vtk", out_folder)).unwrap_or_else(
+    |err| {
+            eprintln!("Cannot save result.vtk in {}: {:#?}", out_folder, err);
+            std::process::exit(2);
         }
-    }
-    false
-}
+    );
 
-fn mesh_blocks_view(mesh: &tri_mesh::mesh::Mesh, point0: &Vec3, point1: &Vec3) -> bool
-{
-    for face_id in mesh.face_iter() {
-        if mesh.face_line_piece_intersection(face_id, point0, point1).is_some()
-        {
-            return true;
+    // Save in_mesh with a patch for the fire and one for the model
+    let stl = exporter::parse_as_stl(&result_mesh, &|face_id| stitched.origin(face_id).name());
+    std::fs::write(&format!("{}{}", out_folder, out_patches_file_name), stl).unwrap_or_else(
+    |err| {
+            eprintln!("Cannot save {} in {}: {:#?}", out_patches_file_name, out_folder, err);
+            std::process::exit(2);
         }
-    }
-    false
+    );
+    (fire_mesh, result_mesh)
 }
 
 #[derive(Debug)]
 struct Fire {
     position: Vec3,
+    normal: Vec3,
     radius: f64
 }
 
@@ -395,7 +375,7 @@ fn load_fire(filename: &str) -> Result<Fire, serde_json::Error>
     let radius = obj["radius"].as_f64().unwrap();
     let n = &obj["normal"];
     let normal = vec3(n["x"].as_f64().unwrap(), n["y"].as_f64().unwrap(), n["z"].as_f64().unwrap());
-    Ok(Fire { position: position + normal * 0.5 * radius, radius })
+    Ok(Fire { position, normal, radius })
 }
 
 fn load_json(filename: &str) -> String
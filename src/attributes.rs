@@ -0,0 +1,885 @@
+//! Attribute layers which attach data, for example scalars, vectors, integer tags or colours, to the vertices and faces of a mesh.
+//!
+//! The layers are stored in an [Attributes](struct.Attributes.html) container next to the mesh. The mesh operations in this module
+//! ([merge_with](fn.merge_with.html), [split_edge](fn.split_edge.html), [split_face](fn.split_face.html) and [collapse_edge](fn.collapse_edge.html))
+//! update both the mesh and the attributes, so the attributes stay consistent with the mesh.
+//! For other operations, [transfer](fn.transfer.html) moves the attributes to a mesh with new IDs based on the positions.
+
+use tri_mesh::prelude::*;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+///
+/// A colour with red, green, blue and alpha components in the range `[0, 1]`.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64
+}
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64, a: f64) -> Self
+    {
+        Color {r, g, b, a}
+    }
+}
+
+///
+/// A layer of values of one type, keyed by [VertexID](../prelude/struct.VertexID.html) or [FaceID](../prelude/struct.FaceID.html).
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layer<K: Eq + Hash> {
+    Scalar(HashMap<K, f64>),
+    Vector(HashMap<K, Vec3>),
+    Tag(HashMap<K, i32>),
+    Color(HashMap<K, Color>)
+}
+
+impl<K: Eq + Hash + Copy> Layer<K> {
+    ///
+    /// The number of components of each value, ie. 1 for scalars and tags, 3 for vectors and 4 for colours.
+    ///
+    pub fn no_components(&self) -> usize
+    {
+        match self {
+            Layer::Scalar(_) | Layer::Tag(_) => 1,
+            Layer::Vector(_) => 3,
+            Layer::Color(_) => 4
+        }
+    }
+
+    ///
+    /// Returns the components of the value for the given key, see [no_components](#method.no_components).
+    ///
+    pub fn components(&self, key: &K) -> Option<Vec<f64>>
+    {
+        match self {
+            Layer::Scalar(values) => values.get(key).map(|v| vec![*v]),
+            Layer::Vector(values) => values.get(key).map(|v| vec![v.x, v.y, v.z]),
+            Layer::Tag(values) => values.get(key).map(|v| vec![*v as f64]),
+            Layer::Color(values) => values.get(key).map(|v| vec![v.r, v.g, v.b, v.a])
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool
+    {
+        match self {
+            Layer::Scalar(values) => values.contains_key(key),
+            Layer::Vector(values) => values.contains_key(key),
+            Layer::Tag(values) => values.contains_key(key),
+            Layer::Color(values) => values.contains_key(key)
+        }
+    }
+
+    pub fn remove(&mut self, key: &K)
+    {
+        match self {
+            Layer::Scalar(values) => { values.remove(key); },
+            Layer::Vector(values) => { values.remove(key); },
+            Layer::Tag(values) => { values.remove(key); },
+            Layer::Color(values) => { values.remove(key); }
+        }
+    }
+
+    fn empty(&self) -> Self
+    {
+        match self {
+            Layer::Scalar(_) => Layer::Scalar(HashMap::new()),
+            Layer::Vector(_) => Layer::Vector(HashMap::new()),
+            Layer::Tag(_) => Layer::Tag(HashMap::new()),
+            Layer::Color(_) => Layer::Color(HashMap::new())
+        }
+    }
+
+    fn subset(&self, keys: &[K]) -> Self
+    {
+        let mut layer = self.empty();
+        for key in keys {
+            layer.copy_from(*key, self, key);
+        }
+        layer
+    }
+
+    ///
+    /// Copies the value for the key `from` in the other layer to the key `to` in this layer.
+    /// Nothing is copied if the other layer has no value for the key or if the layers have different types.
+    ///
+    fn copy_from(&mut self, to: K, other: &Layer<K>, from: &K)
+    {
+        match (self, other) {
+            (Layer::Scalar(values), Layer::Scalar(others)) => { others.get(from).map(|v| values.insert(to, *v)); },
+            (Layer::Vector(values), Layer::Vector(others)) => { others.get(from).map(|v| values.insert(to, *v)); },
+            (Layer::Tag(values), Layer::Tag(others)) => { others.get(from).map(|v| values.insert(to, *v)); },
+            (Layer::Color(values), Layer::Color(others)) => { others.get(from).map(|v| values.insert(to, *v)); },
+            _ => {}
+        }
+    }
+
+    ///
+    /// Sets the value for the key `to` to the weighted average of the values in the other layer for the given keys.
+    /// Keys without a value are ignored. Tags are not averaged, instead the tag with the largest weight is used.
+    ///
+    fn interpolate_from(&mut self, to: K, other: &Layer<K>, from: &[(K, f64)])
+    {
+        fn average<K: Eq + Hash, T>(others: &HashMap<K, T>, from: &[(K, f64)]) -> Option<T>
+            where T: Copy + std::ops::Mul<f64, Output = T> + std::ops::Add<T, Output = T>
+        {
+            let present: Vec<(T, f64)> = from.iter().filter_map(|(key, weight)| others.get(key).map(|v| (*v, *weight))).collect();
+            let total: f64 = present.iter().map(|(_, weight)| weight).sum();
+            if present.len() == 0 || total <= 0.0 { return present.first().map(|(v, _)| *v); }
+            present.iter().map(|(v, weight)| *v * (*weight / total)).fold(None, |sum, v| Some(match sum { Some(s) => s + v, None => v }))
+        }
+
+        match (self, other) {
+            (Layer::Scalar(values), Layer::Scalar(others)) => { average(others, from).map(|v| values.insert(to, v)); },
+            (Layer::Vector(values), Layer::Vector(others)) => { average(others, from).map(|v| values.insert(to, v)); },
+            (Layer::Color(values), Layer::Color(others)) => {
+                let present: Vec<(Color, f64)> = from.iter().filter_map(|(key, weight)| others.get(key).map(|v| (*v, *weight))).collect();
+                let total: f64 = present.iter().map(|(_, weight)| weight).sum();
+                if present.len() > 0 && total > 0.0 {
+                    let mut color = Color::new(0.0, 0.0, 0.0, 0.0);
+                    for (c, weight) in present {
+                        color.r += c.r * weight / total;
+                        color.g += c.g * weight / total;
+                        color.b += c.b * weight / total;
+                        color.a += c.a * weight / total;
+                    }
+                    values.insert(to, color);
+                }
+                else if let Some((c, _)) = present.first() { values.insert(to, *c); }
+            },
+            (Layer::Tag(values), Layer::Tag(others)) => {
+                from.iter().filter_map(|(key, weight)| others.get(key).map(|v| (*v, *weight)))
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map(|(v, _)| values.insert(to, v));
+            },
+            _ => {}
+        }
+    }
+}
+
+///
+/// A type which can be stored in a [Layer](enum.Layer.html), ie. `f64` (scalars), `Vec3` (vectors), `i32` (tags) and [Color](struct.Color.html).
+///
+pub trait AttributeValue: Clone {
+    fn values<K: Eq + Hash>(layer: &Layer<K>) -> Option<&HashMap<K, Self>>;
+    fn values_mut<K: Eq + Hash>(layer: &mut Layer<K>) -> Option<&mut HashMap<K, Self>>;
+    fn new_layer<K: Eq + Hash>() -> Layer<K>;
+}
+
+macro_rules! impl_attribute_value {
+    ($type:ty, $variant:ident) => {
+        impl AttributeValue for $type {
+            fn values<K: Eq + Hash>(layer: &Layer<K>) -> Option<&HashMap<K, Self>>
+            {
+                if let Layer::$variant(values) = layer { Some(values) } else { None }
+            }
+
+            fn values_mut<K: Eq + Hash>(layer: &mut Layer<K>) -> Option<&mut HashMap<K, Self>>
+            {
+                if let Layer::$variant(values) = layer { Some(values) } else { None }
+            }
+
+            fn new_layer<K: Eq + Hash>() -> Layer<K>
+            {
+                Layer::$variant(HashMap::new())
+            }
+        }
+    }
+}
+
+impl_attribute_value!(f64, Scalar);
+impl_attribute_value!(Vec3, Vector);
+impl_attribute_value!(i32, Tag);
+impl_attribute_value!(Color, Color);
+
+///
+/// Named attribute layers on the vertices and faces of a mesh.
+///
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    vertex_layers: HashMap<String, Layer<VertexID>>,
+    face_layers: HashMap<String, Layer<FaceID>>
+}
+
+impl Attributes {
+    pub fn new() -> Self
+    {
+        Attributes {vertex_layers: HashMap::new(), face_layers: HashMap::new()}
+    }
+
+    ///
+    /// Returns the vertex layer with the given name or `None` if there is no vertex layer with that name and type.
+    ///
+    pub fn vertex_layer<T: AttributeValue>(&self, name: &str) -> Option<&HashMap<VertexID, T>>
+    {
+        self.vertex_layers.get(name).and_then(|layer| T::values(layer))
+    }
+
+    ///
+    /// Returns the vertex layer with the given name. The layer is created if it does not exist.
+    ///
+    /// # Panics
+    ///
+    /// If a vertex layer with the given name but another type exists.
+    ///
+    pub fn vertex_layer_mut<T: AttributeValue>(&mut self, name: &str) -> &mut HashMap<VertexID, T>
+    {
+        T::values_mut(self.vertex_layers.entry(name.to_string()).or_insert_with(|| T::new_layer()))
+            .unwrap_or_else(|| panic!("The vertex layer {} has another type", name))
+    }
+
+    ///
+    /// Returns the face layer with the given name or `None` if there is no face layer with that name and type.
+    ///
+    pub fn face_layer<T: AttributeValue>(&self, name: &str) -> Option<&HashMap<FaceID, T>>
+    {
+        self.face_layers.get(name).and_then(|layer| T::values(layer))
+    }
+
+    ///
+    /// Returns the face layer with the given name. The layer is created if it does not exist.
+    ///
+    /// # Panics
+    ///
+    /// If a face layer with the given name but another type exists.
+    ///
+    pub fn face_layer_mut<T: AttributeValue>(&mut self, name: &str) -> &mut HashMap<FaceID, T>
+    {
+        T::values_mut(self.face_layers.entry(name.to_string()).or_insert_with(|| T::new_layer()))
+            .unwrap_or_else(|| panic!("The face layer {} has another type", name))
+    }
+
+    pub fn remove_vertex_layer(&mut self, name: &str) -> Option<Layer<VertexID>>
+    {
+        self.vertex_layers.remove(name)
+    }
+
+    pub fn remove_face_layer(&mut self, name: &str) -> Option<Layer<FaceID>>
+    {
+        self.face_layers.remove(name)
+    }
+
+    ///
+    /// Returns the names and layers of all vertex layers sorted by name.
+    ///
+    pub fn vertex_layers(&self) -> Vec<(&str, &Layer<VertexID>)>
+    {
+        let mut layers: Vec<(&str, &Layer<VertexID>)> = self.vertex_layers.iter().map(|(name, layer)| (name.as_str(), layer)).collect();
+        layers.sort_by(|a, b| a.0.cmp(b.0));
+        layers
+    }
+
+    ///
+    /// Returns the names and layers of all face layers sorted by name.
+    ///
+    pub fn face_layers(&self) -> Vec<(&str, &Layer<FaceID>)>
+    {
+        let mut layers: Vec<(&str, &Layer<FaceID>)> = self.face_layers.iter().map(|(name, layer)| (name.as_str(), layer)).collect();
+        layers.sort_by(|a, b| a.0.cmp(b.0));
+        layers
+    }
+
+    ///
+    /// Removes the values of the vertices and faces which are no longer in the mesh.
+    ///
+    pub fn remove_unused(&mut self, mesh: &Mesh)
+    {
+        let vertices: std::collections::HashSet<VertexID> = mesh.vertex_iter().collect();
+        let faces: std::collections::HashSet<FaceID> = mesh.face_iter().collect();
+        for layer in self.vertex_layers.values_mut() {
+            match layer {
+                Layer::Scalar(values) => values.retain(|key, _| vertices.contains(key)),
+                Layer::Vector(values) => values.retain(|key, _| vertices.contains(key)),
+                Layer::Tag(values) => values.retain(|key, _| vertices.contains(key)),
+                Layer::Color(values) => values.retain(|key, _| vertices.contains(key))
+            }
+        }
+        for layer in self.face_layers.values_mut() {
+            match layer {
+                Layer::Scalar(values) => values.retain(|key, _| faces.contains(key)),
+                Layer::Vector(values) => values.retain(|key, _| faces.contains(key)),
+                Layer::Tag(values) => values.retain(|key, _| faces.contains(key)),
+                Layer::Color(values) => values.retain(|key, _| faces.contains(key))
+            }
+        }
+    }
+
+    fn subset(&self, vertices: &[VertexID], faces: &[FaceID]) -> Self
+    {
+        Attributes {
+            vertex_layers: self.vertex_layers.iter().map(|(name, layer)| (name.clone(), layer.subset(vertices))).collect(),
+            face_layers: self.face_layers.iter().map(|(name, layer)| (name.clone(), layer.subset(faces))).collect()
+        }
+    }
+
+    ///
+    /// Returns the layers as attributes which can be saved in the GPM format, see [parse_as_gpm](../exporter/fn.parse_as_gpm.html).
+    /// Vertices and faces without a value in a layer get zeros.
+    ///
+    pub fn gpm_attributes(&self, mesh: &Mesh) -> Vec<crate::exporter::GpmAttribute>
+    {
+        use crate::exporter::{GpmAttribute, GpmAttributeLocation};
+        let mut attributes = Vec::new();
+        for (name, layer) in self.vertex_layers() {
+            attributes.push(GpmAttribute {name: name.to_string(), location: GpmAttributeLocation::Vertex, kind: gpm_kind(layer),
+                no_components: layer.no_components(), values: layer_values(layer, mesh.vertex_iter())});
+        }
+        for (name, layer) in self.face_layers() {
+            attributes.push(GpmAttribute {name: name.to_string(), location: GpmAttributeLocation::Face, kind: gpm_kind(layer),
+                no_components: layer.no_components(), values: layer_values(layer, mesh.face_iter())});
+        }
+        attributes
+    }
+
+    ///
+    /// Creates attribute layers from attributes loaded from a GPM file, see [parse_gpm](../importer/fn.parse_gpm.html).
+    /// Attributes which cannot be represented as a layer, for example real values with two components, are ignored.
+    ///
+    pub fn from_gpm_attributes(mesh: &Mesh, gpm_attributes: &[crate::exporter::GpmAttribute]) -> Self
+    {
+        use crate::exporter::GpmAttributeLocation;
+        let mut attributes = Attributes::new();
+        for attribute in gpm_attributes {
+            match attribute.location {
+                GpmAttributeLocation::Vertex => {
+                    let keys: Vec<VertexID> = mesh.vertex_iter().collect();
+                    if let Some(layer) = gpm_layer(&keys, attribute.kind, attribute.no_components, &attribute.values) {
+                        attributes.vertex_layers.insert(attribute.name.clone(), layer);
+                    }
+                },
+                GpmAttributeLocation::Face => {
+                    let keys: Vec<FaceID> = mesh.face_iter().collect();
+                    if let Some(layer) = gpm_layer(&keys, attribute.kind, attribute.no_components, &attribute.values) {
+                        attributes.face_layers.insert(attribute.name.clone(), layer);
+                    }
+                }
+            }
+        }
+        attributes
+    }
+}
+
+fn gpm_kind<K: Eq + Hash + Copy>(layer: &Layer<K>) -> crate::exporter::GpmAttributeKind
+{
+    use crate::exporter::GpmAttributeKind;
+    match layer {
+        Layer::Scalar(_) | Layer::Vector(_) => GpmAttributeKind::Real,
+        Layer::Tag(_) => GpmAttributeKind::Integer,
+        Layer::Color(_) => GpmAttributeKind::Color
+    }
+}
+
+fn layer_values<K: Eq + Hash + Copy>(layer: &Layer<K>, keys: impl Iterator<Item = K>) -> Vec<f64>
+{
+    let no_components = layer.no_components();
+    keys.flat_map(|key| layer.components(&key).unwrap_or(vec![0.0; no_components])).collect()
+}
+
+fn gpm_layer<K: Eq + Hash + Copy>(keys: &[K], kind: crate::exporter::GpmAttributeKind, no_components: usize, values: &[f64]) -> Option<Layer<K>>
+{
+    use crate::exporter::GpmAttributeKind;
+    let chunks = keys.iter().zip(values.chunks(no_components.max(1)));
+    match (kind, no_components) {
+        (GpmAttributeKind::Real, 1) => Some(Layer::Scalar(chunks.map(|(k, v)| (*k, v[0])).collect())),
+        (GpmAttributeKind::Real, 3) => Some(Layer::Vector(chunks.map(|(k, v)| (*k, vec3(v[0], v[1], v[2]))).collect())),
+        (GpmAttributeKind::Integer, 1) => Some(Layer::Tag(chunks.map(|(k, v)| (*k, v[0] as i32)).collect())),
+        (GpmAttributeKind::Color, 4) => Some(Layer::Color(chunks.map(|(k, v)| (*k, Color::new(v[0], v[1], v[2], v[3]))).collect())),
+        _ => None
+    }
+}
+
+///
+/// Returns the attributes of the `from` meshes transferred to the `to` mesh, which must consist of the same vertices and faces
+/// geometrically, but may have new IDs. For example, the result of merging or cloning meshes.
+/// Each vertex and face gets the values of the vertex and face at the nearest position (face center for faces) in the `from` meshes.
+///
+pub fn transfer(from: &[(&Mesh, &Attributes)], to: &Mesh) -> Attributes
+{
+    let mut result = Attributes::new();
+    for (_, attributes) in from.iter() {
+        for (name, layer) in attributes.vertex_layers.iter() {
+            result.vertex_layers.entry(name.clone()).or_insert_with(|| layer.empty());
+        }
+        for (name, layer) in attributes.face_layers.iter() {
+            result.face_layers.entry(name.clone()).or_insert_with(|| layer.empty());
+        }
+    }
+
+    let vertex_locator = PointLocator::new(from.iter().enumerate().flat_map(|(i, (mesh, _))| {
+        mesh.vertex_iter().map(move |vertex_id| (mesh.vertex_position(vertex_id), (i, vertex_id)))
+    }).collect());
+    let face_locator = PointLocator::new(from.iter().enumerate().flat_map(|(i, (mesh, _))| {
+        mesh.face_iter().map(move |face_id| (mesh.face_center(face_id), (i, face_id)))
+    }).collect());
+
+    if !vertex_locator.is_empty() {
+        for vertex_id in to.vertex_iter() {
+            let (i, from_id) = vertex_locator.nearest(&to.vertex_position(vertex_id));
+            for (name, layer) in result.vertex_layers.iter_mut() {
+                if let Some(from_layer) = from[i].1.vertex_layers.get(name) {
+                    layer.copy_from(vertex_id, from_layer, &from_id);
+                }
+            }
+        }
+    }
+    if !face_locator.is_empty() {
+        for face_id in to.face_iter() {
+            let (i, from_id) = face_locator.nearest(&to.face_center(face_id));
+            for (name, layer) in result.face_layers.iter_mut() {
+                if let Some(from_layer) = from[i].1.face_layers.get(name) {
+                    layer.copy_from(face_id, from_layer, &from_id);
+                }
+            }
+        }
+    }
+    result
+}
+
+///
+/// Merges the other mesh into the mesh (see `Mesh::merge_with`) and the attributes of the other mesh into the attributes.
+/// Where vertices are merged, the values of the mesh are used.
+///
+pub fn merge_with(mesh: &mut Mesh, attributes: &mut Attributes, other: &Mesh, other_attributes: &Attributes) -> Result<(), tri_mesh::mesh::Error>
+{
+    let before = mesh.clone();
+    mesh.merge_with(other)?;
+    *attributes = transfer(&[(&before, attributes), (other, other_attributes)], mesh);
+    Ok(())
+}
+
+///
+/// Splits the edge at the given position (see `Mesh::split_edge`). The values of the new vertex are interpolated linearly
+/// between the values of the end points of the edge, and the new faces get the values of the face they are a part of.
+///
+pub fn split_edge(mesh: &mut Mesh, attributes: &mut Attributes, halfedge_id: HalfEdgeID, position: Vec3) -> VertexID
+{
+    let mut walker = mesh.walker_from_halfedge(halfedge_id);
+    let vertex_id1 = walker.vertex_id().unwrap();
+    let face_id0 = walker.face_id();
+    let vertex_id0 = walker.as_twin().vertex_id().unwrap();
+    let face_id1 = walker.face_id();
+
+    let (p0, p1) = (mesh.vertex_position(vertex_id0), mesh.vertex_position(vertex_id1));
+    let length = p0.distance(p1);
+    let t = if length > 0.0 { (p0.distance(position) / length).min(1.0) } else { 0.5 };
+
+    let old_faces: Vec<FaceID> = face_id0.iter().chain(face_id1.iter()).cloned().collect();
+    let old_triangles: Vec<(FaceID, (Vec3, Vec3, Vec3))> = old_faces.iter().map(|f| (*f, mesh.face_positions(*f))).collect();
+    let old = attributes.subset(&[vertex_id0, vertex_id1], &old_faces);
+
+    let vertex_id = mesh.split_edge(halfedge_id, position);
+
+    for (name, layer) in attributes.vertex_layers.iter_mut() {
+        layer.interpolate_from(vertex_id, &old.vertex_layers[name], &[(vertex_id0, 1.0 - t), (vertex_id1, t)]);
+    }
+    assign_faces_around(mesh, attributes, &old, vertex_id, &old_triangles);
+    vertex_id
+}
+
+///
+/// Splits the face at the given position (see `Mesh::split_face`). The values of the new vertex are interpolated
+/// between the values of the corners of the face using barycentric coordinates, and the new faces get the values of the face.
+///
+pub fn split_face(mesh: &mut Mesh, attributes: &mut Attributes, face_id: FaceID, position: Vec3) -> VertexID
+{
+    let (v0, v1, v2) = mesh.face_vertices(face_id);
+    let triangle = mesh.face_positions(face_id);
+    let (w0, w1, w2) = barycentric_coordinates(&position, &triangle);
+    let old = attributes.subset(&[v0, v1, v2], &[face_id]);
+
+    let vertex_id = mesh.split_face(face_id, position);
+
+    for (name, layer) in attributes.vertex_layers.iter_mut() {
+        layer.interpolate_from(vertex_id, &old.vertex_layers[name], &[(v0, w0), (v1, w1), (v2, w2)]);
+    }
+    assign_faces_around(mesh, attributes, &old, vertex_id, &[(face_id, triangle)]);
+    vertex_id
+}
+
+///
+/// Collapses the edge (see `Mesh::collapse_edge`). The values of the remaining vertex are the average of the values
+/// of the end points of the edge and the values of the removed vertex and faces are removed.
+///
+pub fn collapse_edge(mesh: &mut Mesh, attributes: &mut Attributes, halfedge_id: HalfEdgeID) -> VertexID
+{
+    let mut walker = mesh.walker_from_halfedge(halfedge_id);
+    let vertex_id1 = walker.vertex_id().unwrap();
+    let face_id0 = walker.face_id();
+    let vertex_id0 = walker.as_twin().vertex_id().unwrap();
+    let face_id1 = walker.face_id();
+    let old = attributes.subset(&[vertex_id0, vertex_id1], &[]);
+
+    let vertex_id = mesh.collapse_edge(halfedge_id);
+
+    let removed_vertex = if vertex_id == vertex_id0 { vertex_id1 } else { vertex_id0 };
+    for (name, layer) in attributes.vertex_layers.iter_mut() {
+        layer.remove(&removed_vertex);
+        layer.interpolate_from(vertex_id, &old.vertex_layers[name], &[(vertex_id0, 0.5), (vertex_id1, 0.5)]);
+    }
+    for layer in attributes.face_layers.values_mut() {
+        for face_id in face_id0.iter().chain(face_id1.iter()) {
+            layer.remove(face_id);
+        }
+    }
+    vertex_id
+}
+
+/// Assigns each face around the vertex the values of the old face which is nearest to the face center.
+fn assign_faces_around(mesh: &Mesh, attributes: &mut Attributes, old: &Attributes, vertex_id: VertexID, old_triangles: &[(FaceID, (Vec3, Vec3, Vec3))])
+{
+    if old_triangles.len() == 0 { return; }
+    for halfedge_id in mesh.vertex_halfedge_iter(vertex_id) {
+        if let Some(face_id) = mesh.walker_from_halfedge(halfedge_id).face_id() {
+            let center = mesh.face_center(face_id);
+            let (old_face_id, _) = old_triangles.iter().min_by(|a, b| point_triangle_distance(&center, &a.1)
+                .partial_cmp(&point_triangle_distance(&center, &b.1)).unwrap()).unwrap();
+            for (name, layer) in attributes.face_layers.iter_mut() {
+                layer.remove(&face_id);
+                layer.copy_from(face_id, &old.face_layers[name], old_face_id);
+            }
+        }
+    }
+}
+
+fn barycentric_coordinates(point: &Vec3, triangle: &(Vec3, Vec3, Vec3)) -> (f64, f64, f64)
+{
+    let (a, b, c) = triangle;
+    let (v0, v1, v2) = (b - a, c - a, point - a);
+    let (d00, d01, d11) = (v0.dot(v0), v0.dot(v1), v1.dot(v1));
+    let (d20, d21) = (v2.dot(v0), v2.dot(v1));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() < std::f64::EPSILON { return (1.0/3.0, 1.0/3.0, 1.0/3.0); }
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    (1.0 - v - w, v, w)
+}
+
+fn point_triangle_distance(point: &Vec3, triangle: &(Vec3, Vec3, Vec3)) -> f64
+{
+    let (u, v, w) = barycentric_coordinates(point, triangle);
+    let (u, v, w) = (u.max(0.0), v.max(0.0), w.max(0.0));
+    let sum = u + v + w;
+    let closest = (triangle.0 * u + triangle.1 * v + triangle.2 * w) / sum;
+    point.distance(closest)
+}
+
+///
+/// Finds the value stored at the point nearest to a query point using a uniform grid.
+///
+pub(crate) struct PointLocator<T> {
+    cell_size: f64,
+    cells: HashMap<(i64, i64, i64), Vec<(Vec3, T)>>,
+    min_cell: (i64, i64, i64),
+    max_cell: (i64, i64, i64)
+}
+
+impl<T: Clone> PointLocator<T> {
+    ///
+    /// Creates a locator for the given points and values. The cell size is scaled by the diagonal of the bounding box of the points,
+    /// so there are few points in each cell independently of the size of the mesh.
+    ///
+    pub fn new(points: Vec<(Vec3, T)>) -> Self
+    {
+        let diagonal = crate::ray_casting::diagonal(points.iter().map(|(point, _)| *point));
+        let cell_size = (diagonal / (points.len() as f64).cbrt()).max(std::f64::MIN_POSITIVE);
+        Self::with_cell_size(points, cell_size)
+    }
+
+    fn with_cell_size(points: Vec<(Vec3, T)>, cell_size: f64) -> Self
+    {
+        let mut locator = PointLocator {cell_size, cells: HashMap::new(), min_cell: (i64::MAX, i64::MAX, i64::MAX), max_cell: (i64::MIN, i64::MIN, i64::MIN)};
+        for (point, value) in points {
+            let cell = locator.cell(&point);
+            locator.min_cell = (locator.min_cell.0.min(cell.0), locator.min_cell.1.min(cell.1), locator.min_cell.2.min(cell.2));
+            locator.max_cell = (locator.max_cell.0.max(cell.0), locator.max_cell.1.max(cell.1), locator.max_cell.2.max(cell.2));
+            locator.cells.entry(cell).or_insert(Vec::new()).push((point, value));
+        }
+        locator
+    }
+
+    fn cell(&self, point: &Vec3) -> (i64, i64, i64)
+    {
+        ((point.x / self.cell_size).floor() as i64, (point.y / self.cell_size).floor() as i64, (point.z / self.cell_size).floor() as i64)
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.cells.is_empty()
+    }
+
+    ///
+    /// Returns the value at the nearest point. The cells are searched in rings of increasing size around the cell of the query point,
+    /// until the nearest point found is closer than any point in the cells outside the searched rings.
+    ///
+    /// # Panics
+    ///
+    /// If no values are inserted.
+    ///
+    pub fn nearest(&self, point: &Vec3) -> T
+    {
+        let (x, y, z) = self.cell(point);
+        let (min, max) = (self.min_cell, self.max_cell);
+        let distance_to = |c: i64, min: i64, max: i64| (min - c).max(c - max).max(0);
+        let extent_from = |c: i64, min: i64, max: i64| (c - min).abs().max((max - c).abs());
+        let first_ring = distance_to(x, min.0, max.0).max(distance_to(y, min.1, max.1)).max(distance_to(z, min.2, max.2));
+        let last_ring = extent_from(x, min.0, max.0).max(extent_from(y, min.1, max.1)).max(extent_from(z, min.2, max.2));
+
+        let mut nearest: Option<(f64, &T)> = None;
+        for ring in first_ring..last_ring + 1 {
+            for i in (x - ring).max(min.0)..(x + ring).min(max.0) + 1 {
+                for j in (y - ring).max(min.1)..(y + ring).min(max.1) + 1 {
+                    // Only the cells on the boundary of the ring are new
+                    let ks: Vec<i64> = if (i - x).abs() == ring || (j - y).abs() == ring { ((z - ring).max(min.2)..(z + ring).min(max.2) + 1).collect() }
+                        else { vec![z - ring, z + ring] };
+                    for k in ks {
+                        for (p, value) in self.cells.get(&(i, j, k)).iter().flat_map(|cell| cell.iter()) {
+                            let distance2 = p.distance2(*point);
+                            if nearest.map(|(nearest_distance2, _)| distance2 < nearest_distance2).unwrap_or(true) {
+                                nearest = Some((distance2, value));
+                            }
+                        }
+                    }
+                }
+            }
+            // The points outside the searched rings are at least the width of the rings away
+            let searched_distance = ring as f64 * self.cell_size;
+            if nearest.map(|(distance2, _)| distance2 <= searched_distance * searched_distance).unwrap_or(false) {
+                break;
+            }
+        }
+        nearest.expect("No values are inserted in the point locator").1.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Returns the mesh scaled by the given factor with the vertices and faces in reverse order.
+    fn scaled_and_reversed(mesh: &Mesh, factor: f64) -> Mesh
+    {
+        let no_vertices = mesh.no_vertices() as u32;
+        let positions = mesh.positions_buffer().chunks(3).rev().flat_map(|p| vec![p[0] * factor, p[1] * factor, p[2] * factor]).collect();
+        let indices = mesh.indices_buffer().chunks(3).rev().flat_map(|t| vec![no_vertices - 1 - t[0], no_vertices - 1 - t[1], no_vertices - 1 - t[2]]).collect();
+        crate::test_utils::mesh(positions, indices)
+    }
+
+    fn linear_value(position: &Vec3, offset: f64) -> f64
+    {
+        position.x + 2.0 * position.y + 3.0 * position.z + offset
+    }
+
+    /// Returns vertex layers which are linear in the position, so interpolated values are known exactly,
+    /// and face layers which are different for each face.
+    fn test_attributes(mesh: &Mesh, offset: f64) -> Attributes
+    {
+        let mut attributes = Attributes::new();
+        for vertex_id in mesh.vertex_iter() {
+            let p = mesh.vertex_position(vertex_id);
+            attributes.vertex_layer_mut::<f64>("scalar").insert(vertex_id, linear_value(&p, offset));
+            attributes.vertex_layer_mut::<Vec3>("vector").insert(vertex_id, p + vec3(offset, 0.0, 0.0));
+            attributes.vertex_layer_mut::<Color>("color").insert(vertex_id, Color::new(p.x, p.y, p.z, offset));
+        }
+        for (i, face_id) in mesh.face_iter().enumerate() {
+            attributes.face_layer_mut::<i32>("tag").insert(face_id, i as i32 + offset as i32);
+            attributes.face_layer_mut::<Color>("face_color").insert(face_id, Color::new(i as f64, 0.0, 0.0, offset));
+        }
+        attributes
+    }
+
+    /// Checks that the layers have values for exactly the vertices and faces of the mesh and that the vertex values are linear in the position.
+    fn check_attributes(mesh: &Mesh, attributes: &Attributes, offset: &dyn Fn(&Vec3) -> f64)
+    {
+        let vertices: std::collections::HashSet<VertexID> = mesh.vertex_iter().collect();
+        let faces: std::collections::HashSet<FaceID> = mesh.face_iter().collect();
+        for (_, layer) in attributes.vertex_layers() {
+            assert!(vertices.iter().all(|vertex_id| layer.contains_key(vertex_id)));
+        }
+        for (_, layer) in attributes.face_layers() {
+            assert!(faces.iter().all(|face_id| layer.contains_key(face_id)));
+        }
+        assert_eq!(attributes.vertex_layer::<f64>("scalar").unwrap().len(), vertices.len());
+        assert_eq!(attributes.vertex_layer::<Vec3>("vector").unwrap().len(), vertices.len());
+        assert_eq!(attributes.vertex_layer::<Color>("color").unwrap().len(), vertices.len());
+        assert_eq!(attributes.face_layer::<i32>("tag").unwrap().len(), faces.len());
+        assert_eq!(attributes.face_layer::<Color>("face_color").unwrap().len(), faces.len());
+
+        for vertex_id in mesh.vertex_iter() {
+            let p = mesh.vertex_position(vertex_id);
+            let offset = offset(&p);
+            assert!((attributes.vertex_layer::<f64>("scalar").unwrap()[&vertex_id] - linear_value(&p, offset)).abs() < 1.0e-12);
+            assert!(attributes.vertex_layer::<Vec3>("vector").unwrap()[&vertex_id].distance(p + vec3(offset, 0.0, 0.0)) < 1.0e-12);
+            let color = attributes.vertex_layer::<Color>("color").unwrap()[&vertex_id];
+            assert!(vec3(color.r, color.g, color.b).distance(p) < 1.0e-12 && (color.a - offset).abs() < 1.0e-12);
+        }
+        for face_id in mesh.face_iter() {
+            let color = attributes.face_layer::<Color>("face_color").unwrap()[&face_id];
+            assert_eq!(color.r as i32 + color.a as i32, attributes.face_layer::<i32>("tag").unwrap()[&face_id]);
+        }
+    }
+
+    /// Returns an edge of the sphere away from the poles.
+    fn equator_edge(mesh: &Mesh) -> HalfEdgeID
+    {
+        mesh.halfedge_iter().find(|halfedge_id| {
+            let (p0, p1) = mesh.edge_positions(*halfedge_id);
+            p0.z.abs() < 0.5 && p1.z.abs() < 0.5 && !mesh.is_edge_on_boundary(*halfedge_id)
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_merge_with()
+    {
+        let mut mesh = cube(1);
+        let mut attributes = test_attributes(&mesh, 0.0);
+        let other = translated(&cube(1), vec3(2.0, 0.0, 0.0));
+        let other_attributes = test_attributes(&other, 100.0);
+
+        merge_with(&mut mesh, &mut attributes, &other, &other_attributes).unwrap();
+
+        assert_eq!(mesh.no_vertices(), 16);
+        assert_eq!(mesh.no_faces(), 24);
+        check_attributes(&mesh, &attributes, &|p| if p.x > 1.5 { 100.0 } else { 0.0 });
+        let tags = attributes.face_layer::<i32>("tag").unwrap();
+        for face_id in mesh.face_iter() {
+            assert_eq!(tags[&face_id] >= 100, mesh.face_center(face_id).x > 1.5);
+        }
+    }
+
+    #[test]
+    fn test_split_edge()
+    {
+        let mut mesh = sphere(1.0, 6);
+        let mut attributes = test_attributes(&mesh, 0.0);
+        let halfedge_id = equator_edge(&mesh);
+        let (p0, p1) = mesh.edge_positions(halfedge_id);
+        let mut walker = mesh.walker_from_halfedge(halfedge_id);
+        let face_id0 = walker.face_id().unwrap();
+        let face_id1 = walker.as_twin().face_id().unwrap();
+        let tags = attributes.face_layer::<i32>("tag").unwrap().clone();
+
+        let vertex_id = split_edge(&mut mesh, &mut attributes, halfedge_id, p0 + 0.25 * (p1 - p0));
+
+        check_attributes(&mesh, &attributes, &|_| 0.0);
+        let mut new_tags: Vec<i32> = mesh.vertex_halfedge_iter(vertex_id).filter_map(|halfedge_id| mesh.walker_from_halfedge(halfedge_id).face_id())
+            .map(|face_id| attributes.face_layer::<i32>("tag").unwrap()[&face_id]).collect();
+        new_tags.sort();
+        let mut expected = vec![tags[&face_id0], tags[&face_id0], tags[&face_id1], tags[&face_id1]];
+        expected.sort();
+        assert_eq!(new_tags, expected);
+    }
+
+    #[test]
+    fn test_split_face()
+    {
+        let mut mesh = sphere(1.0, 6);
+        let mut attributes = test_attributes(&mesh, 0.0);
+        let face_id = mesh.face_iter().nth(20).unwrap();
+        let (p0, p1, p2) = mesh.face_positions(face_id);
+        let tag = attributes.face_layer::<i32>("tag").unwrap()[&face_id];
+        let no_faces = mesh.no_faces();
+
+        let vertex_id = split_face(&mut mesh, &mut attributes, face_id, 0.2 * p0 + 0.3 * p1 + 0.5 * p2);
+
+        assert_eq!(mesh.no_faces(), no_faces + 2);
+        check_attributes(&mesh, &attributes, &|_| 0.0);
+        let new_tags: Vec<i32> = mesh.vertex_halfedge_iter(vertex_id).filter_map(|halfedge_id| mesh.walker_from_halfedge(halfedge_id).face_id())
+            .map(|face_id| attributes.face_layer::<i32>("tag").unwrap()[&face_id]).collect();
+        assert_eq!(new_tags, vec![tag; 3]);
+    }
+
+    #[test]
+    fn test_collapse_edge()
+    {
+        let mut mesh = sphere(1.0, 6);
+        let mut attributes = test_attributes(&mesh, 0.0);
+        let halfedge_id = equator_edge(&mesh);
+        let tags = attributes.face_layer::<i32>("tag").unwrap().clone();
+        let no_vertices = mesh.no_vertices();
+        let no_faces = mesh.no_faces();
+
+        collapse_edge(&mut mesh, &mut attributes, halfedge_id);
+
+        assert_eq!(mesh.no_vertices(), no_vertices - 1);
+        assert_eq!(mesh.no_faces(), no_faces - 2);
+        check_attributes(&mesh, &attributes, &|_| 0.0);
+        assert_eq!(attributes.vertex_layer::<f64>("scalar").unwrap().len(), no_vertices - 1);
+        assert_eq!(attributes.face_layer::<i32>("tag").unwrap().len(), no_faces - 2);
+        assert!(mesh.face_iter().all(|face_id| attributes.face_layer::<i32>("tag").unwrap()[&face_id] == tags[&face_id]));
+    }
+
+    #[test]
+    fn test_layers()
+    {
+        let cube = cube(1);
+        let vertex_id = cube.vertex_iter().next().unwrap();
+        let face_id = cube.face_iter().next().unwrap();
+        let mut attributes = Attributes::new();
+        attributes.vertex_layer_mut::<f64>("height").insert(vertex_id, 2.0);
+        attributes.vertex_layer_mut::<Color>("color").insert(vertex_id, Color::new(1.0, 0.0, 0.0, 1.0));
+        attributes.face_layer_mut::<i32>("marker").insert(face_id, 7);
+        attributes.face_layer_mut::<Vec3>("normal").insert(face_id, vec3(0.0, 0.0, 1.0));
+
+        assert_eq!(attributes.vertex_layer::<f64>("height").unwrap()[&vertex_id], 2.0);
+        assert!(attributes.vertex_layer::<i32>("height").is_none());
+        assert!(attributes.face_layer::<f64>("height").is_none());
+        assert_eq!(attributes.face_layer::<i32>("marker").unwrap()[&face_id], 7);
+        let names: Vec<&str> = attributes.vertex_layers().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["color", "height"]);
+        let (_, normals) = attributes.face_layers()[1];
+        assert_eq!(normals.no_components(), 3);
+        assert_eq!(normals.components(&face_id), Some(vec![0.0, 0.0, 1.0]));
+
+        assert!(attributes.remove_face_layer("marker").is_some());
+        assert!(attributes.face_layer::<i32>("marker").is_none());
+    }
+
+    #[test]
+    fn test_transfer_independent_of_scale()
+    {
+        for factor in [1.0e-6, 1.0, 1.0e4].iter() {
+            let from = scaled_and_reversed(&sphere(1.0, 6), *factor);
+            let to = scaled_and_reversed(&from, 1.0);
+            let mut attributes = Attributes::new();
+            for vertex_id in from.vertex_iter() {
+                attributes.vertex_layer_mut::<Vec3>("position").insert(vertex_id, from.vertex_position(vertex_id));
+            }
+            for face_id in from.face_iter() {
+                attributes.face_layer_mut::<Vec3>("center").insert(face_id, from.face_center(face_id));
+            }
+
+            let transferred = transfer(&[(&from, &attributes)], &to);
+            let positions = transferred.vertex_layer::<Vec3>("position").unwrap();
+            assert!(to.vertex_iter().all(|vertex_id| positions[&vertex_id] == to.vertex_position(vertex_id)));
+            let centers = transferred.face_layer::<Vec3>("center").unwrap();
+            assert!(to.face_iter().all(|face_id| centers[&face_id].distance(to.face_center(face_id)) <= 1.0e-12 * factor));
+        }
+    }
+
+    #[test]
+    fn test_point_locator()
+    {
+        let points: Vec<(Vec3, usize)> = (0..1000).map(|i| (vec3((i % 10) as f64, (i / 10 % 10) as f64, (i / 100) as f64) * 1.0e-5, i)).collect();
+        let locator = PointLocator::new(points.clone());
+        for (point, value) in points.iter() {
+            assert_eq!(locator.nearest(&(point + vec3(1.0e-7, -2.0e-7, 3.0e-7))), *value);
+        }
+        assert_eq!(locator.nearest(&vec3(1.0, 1.0, 1.0)), 999);
+    }
+
+    #[test]
+    fn test_point_locator_across_cell_boundary()
+    {
+        // The point in the neighbouring cell is further away than the point just across the boundary of the next cell
+        let locator = PointLocator::with_cell_size(vec![(vec3(-0.99, 0.5, 0.5), 0), (vec3(2.01, 0.5, 0.5), 1)], 1.0);
+        assert_eq!(locator.nearest(&vec3(0.99, 0.5, 0.5)), 1);
+        assert_eq!(locator.nearest(&vec3(0.4, 0.5, 0.5)), 0);
+        assert_eq!(locator.nearest(&vec3(0.99, 0.5, -10.0)), 1);
+        assert_eq!(locator.nearest(&vec3(-100.0, 0.5, 0.5)), 0);
+    }
+}
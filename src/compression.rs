@@ -0,0 +1,126 @@
+//! Transparent compression of the files read by the [importer](../importer/index.html)
+//! and written by the [exporter](../exporter/index.html).
+//! The compression is deduced from the last extension of the file name, for example `model.obj.gz` or `model.poly.zst`.
+
+use std::io::{Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd
+}
+
+impl Compression {
+    ///
+    /// Returns the compression given by the extension, ie. `Gzip` for `gz`, `Zstd` for `zst` and otherwise `None`.
+    ///
+    pub fn from_extension(extension: &str) -> Compression
+    {
+        match extension {
+            "gz" => Compression::Gzip,
+            "zst" => Compression::Zstd,
+            _ => Compression::None
+        }
+    }
+
+    ///
+    /// Returns the extension appended to the file name when using this compression.
+    ///
+    pub fn extension(&self) -> Option<&'static str>
+    {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst")
+        }
+    }
+}
+
+///
+/// Creates the file at the given path and returns a stream which compresses the data written to it on the fly.
+/// The compressed stream is finished, ie. the end of the compressed data is written, when the returned stream is dropped.
+/// Flush the stream before dropping it to get the errors when writing the data.
+///
+pub fn write(path: &str, compression: Compression) -> Result<Box<dyn Write>, std::io::Error>
+{
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default())),
+        Compression::Zstd => Box::new(zstd::stream::write::Encoder::new(file, 0)?.auto_finish())
+    })
+}
+
+///
+/// Opens the file at the given path and returns a stream which decompresses the content of the file on the fly.
+///
+pub fn read(path: &str, compression: Compression) -> Result<Box<dyn Read>, std::io::Error>
+{
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(flate2::read::GzDecoder::new(file)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(file)?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn temp_path(filename: &str) -> String
+    {
+        std::env::temp_dir().join(format!("geo-proc-{}-{}", std::process::id(), filename)).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_round_trip()
+    {
+        let data: Vec<u8> = (0..10000).map(|i| (i % 7) as u8).collect();
+        for (compression, magic) in [(Compression::None, &data[0..2]), (Compression::Gzip, &[0x1f, 0x8b][..]), (Compression::Zstd, &[0x28, 0xb5][..])].iter() {
+            let path = temp_path(&format!("round-trip.{}", compression.extension().unwrap_or("bin")));
+            {
+                let mut output = write(&path, *compression).unwrap();
+                for chunk in data.chunks(1000) {
+                    output.write_all(chunk).unwrap();
+                }
+                output.flush().unwrap();
+            }
+            let stored = std::fs::read(&path).unwrap();
+            let mut read_data = Vec::new();
+            {
+                let mut input = read(&path, *compression).unwrap();
+                let mut buffer = [0; 100];
+                loop {
+                    let no_bytes = input.read(&mut buffer).unwrap();
+                    if no_bytes == 0 {
+                        break;
+                    }
+                    read_data.extend_from_slice(&buffer[..no_bytes]);
+                }
+            }
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(&stored[0..2], *magic);
+            assert!(*compression == Compression::None || stored.len() < data.len() / 10);
+            assert_eq!(read_data, data);
+        }
+    }
+
+    #[test]
+    fn test_compressed_mesh_files()
+    {
+        let cube = cube(2);
+        for extension in ["obj.gz", "3mf.zst", "gpm.gz"].iter() {
+            let path = temp_path(&format!("cube.{}", extension));
+            crate::exporter::save(&cube, &path).unwrap();
+            let meshes = crate::importer::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(meshes.len(), 1);
+            assert_eq!(sorted_triangles(&meshes[0]), sorted_triangles(&cube));
+        }
+    }
+}
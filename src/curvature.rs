@@ -0,0 +1,242 @@
+//! Estimation of the curvature of the surface at each vertex, following Meyer et al.;
+//! the mean curvature from the cotangent Laplacian, the Gaussian curvature from the angle deficit, both divided by the mixed Voronoi area,
+//! and the principal directions from a least squares fit of the curvature tensor to the normal curvatures along the edges.
+//! The principal curvatures are computed from the mean and Gaussian curvature, not from the fitted tensor, so on coarse or irregular meshes
+//! they may differ from the normal curvatures along the principal directions, and where the surface is almost umbilic the directions are arbitrary.
+//!
+//! The curvatures are positive where the surface bends away from the normals, for example everywhere on a sphere with outward normals.
+
+use tri_mesh::prelude::*;
+use crate::attributes::Attributes;
+use crate::editable_mesh::EditableMesh;
+use crate::indexed_mesh::IndexedMesh;
+use std::collections::{HashMap, HashSet};
+
+/// The name of the vertex layer with the mean curvature, see [add_curvature_layers](fn.add_curvature_layers.html).
+pub const MEAN_CURVATURE: &str = "mean_curvature";
+/// The name of the vertex layer with the Gaussian curvature.
+pub const GAUSSIAN_CURVATURE: &str = "gaussian_curvature";
+/// The name of the vertex layer with the largest principal curvature.
+pub const MAX_CURVATURE: &str = "max_curvature";
+/// The name of the vertex layer with the smallest principal curvature.
+pub const MIN_CURVATURE: &str = "min_curvature";
+/// The name of the vertex layer with the direction of the largest principal curvature.
+pub const MAX_CURVATURE_DIRECTION: &str = "max_curvature_direction";
+/// The name of the vertex layer with the direction of the smallest principal curvature.
+pub const MIN_CURVATURE_DIRECTION: &str = "min_curvature_direction";
+
+///
+/// The curvature of the surface at a vertex.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curvature {
+    pub mean: f64,
+    pub gaussian: f64,
+    /// The largest principal curvature.
+    pub max: f64,
+    /// The smallest principal curvature.
+    pub min: f64,
+    /// The unit tangent direction of the largest principal curvature, from a separate fit, see the [module documentation](index.html).
+    pub max_direction: Vec3,
+    /// The unit tangent direction of the smallest principal curvature.
+    pub min_direction: Vec3
+}
+
+///
+/// Returns the curvature at each vertex. Vertices on the boundary, on non-manifold edges and without faces have no curvature and are left out.
+///
+pub fn vertex_curvatures(mesh: &Mesh) -> HashMap<VertexID, Curvature>
+{
+    let editable = EditableMesh::new(&IndexedMesh::new(mesh), &HashSet::new(), false);
+    mesh.vertex_iter().zip(curvatures_indexed(&editable).into_iter())
+        .filter_map(|(vertex_id, curvature)| curvature.map(|curvature| (vertex_id, curvature)))
+        .collect()
+}
+
+///
+/// Adds the curvature at each vertex as the vertex layers [MEAN_CURVATURE](constant.MEAN_CURVATURE.html), [GAUSSIAN_CURVATURE](constant.GAUSSIAN_CURVATURE.html),
+/// [MAX_CURVATURE](constant.MAX_CURVATURE.html) and [MIN_CURVATURE](constant.MIN_CURVATURE.html) (scalars) and
+/// [MAX_CURVATURE_DIRECTION](constant.MAX_CURVATURE_DIRECTION.html) and [MIN_CURVATURE_DIRECTION](constant.MIN_CURVATURE_DIRECTION.html) (vectors),
+/// so they can be saved with [save_with_attributes](../exporter/fn.save_with_attributes.html), for example to VTK or PLY.
+/// Existing values in the layers are replaced. The vertices without a curvature, for example on the boundary, have no values in the layers,
+/// which the VTK and PLY exporters write as NaN.
+///
+pub fn add_curvature_layers(mesh: &Mesh, attributes: &mut Attributes)
+{
+    let curvatures = vertex_curvatures(mesh);
+    let scalars: [(&str, fn(&Curvature) -> f64); 4] = [(MEAN_CURVATURE, |c| c.mean), (GAUSSIAN_CURVATURE, |c| c.gaussian),
+        (MAX_CURVATURE, |c| c.max), (MIN_CURVATURE, |c| c.min)];
+    for (name, value) in scalars.iter() {
+        let layer = attributes.vertex_layer_mut::<f64>(name);
+        layer.clear();
+        layer.extend(curvatures.iter().map(|(vertex_id, curvature)| (*vertex_id, value(curvature))));
+    }
+    let directions: [(&str, fn(&Curvature) -> Vec3); 2] = [(MAX_CURVATURE_DIRECTION, |c| c.max_direction), (MIN_CURVATURE_DIRECTION, |c| c.min_direction)];
+    for (name, direction) in directions.iter() {
+        let layer = attributes.vertex_layer_mut::<Vec3>(name);
+        layer.clear();
+        layer.extend(curvatures.iter().map(|(vertex_id, curvature)| (*vertex_id, direction(curvature))));
+    }
+}
+
+/// Returns the curvature at each vertex of the editable mesh, or `None` for vertices on the boundary, on non-manifold edges and without triangles.
+fn curvatures_indexed(mesh: &EditableMesh) -> Vec<Option<Curvature>>
+{
+    (0..mesh.positions.len()).map(|vertex| {
+        if mesh.vertex_triangles[vertex].len() == 0 || mesh.is_locked[vertex] || mesh.is_on_boundary(vertex) {
+            return None;
+        }
+        let p = mesh.positions[vertex];
+        let normal = mesh.vertex_normal(vertex);
+        let mut area = 0.0;
+        let mut angle_sum = 0.0;
+        for triangle in mesh.vertex_triangles[vertex].iter() {
+            let indices = mesh.triangles[*triangle];
+            let k = (0..3).find(|k| indices[*k] == vertex).unwrap();
+            let (q, r) = (mesh.positions[indices[(k + 1) % 3]], mesh.positions[indices[(k + 2) % 3]]);
+            angle_sum += (q - p).angle(r - p).0;
+            area += mixed_area(&p, &q, &r);
+        }
+        if area <= 0.0 || normal.magnitude2() == 0.0 {
+            return None;
+        }
+
+        let neighbours: Vec<usize> = mesh.neighbours(vertex).into_iter().collect();
+        let mut laplacian = vec3(0.0, 0.0, 0.0);
+        for neighbour in neighbours.iter() {
+            laplacian += mesh.cotangent_weight(vertex, *neighbour) * (p - mesh.positions[*neighbour]);
+        }
+        let mean = 0.25 * laplacian.dot(normal) / area;
+        let gaussian = (2.0 * std::f64::consts::PI - angle_sum) / area;
+        let discriminant = (mean * mean - gaussian).max(0.0).sqrt();
+        let (max, min) = (mean + discriminant, mean - discriminant);
+        let (max_direction, min_direction) = principal_directions(mesh, vertex, &normal, &neighbours);
+        Some(Curvature {mean, gaussian, max, min, max_direction, min_direction})
+    }).collect()
+}
+
+/// Returns the part of the area of the triangle which is closer to the first corner than to the others, or a fraction of the area if the triangle is obtuse.
+fn mixed_area(p: &Vec3, q: &Vec3, r: &Vec3) -> f64
+{
+    let area = 0.5 * (q - p).cross(r - p).magnitude();
+    if (q - p).dot(r - p) < 0.0 {
+        0.5 * area
+    }
+    else if (p - q).dot(r - q) < 0.0 || (p - r).dot(q - r) < 0.0 {
+        0.25 * area
+    }
+    else {
+        let cot = |a: Vec3, b: Vec3| a.dot(b) / a.cross(b).magnitude().max(std::f64::MIN_POSITIVE);
+        0.125 * ((r - p).magnitude2() * cot(p - q, r - q) + (q - p).magnitude2() * cot(p - r, q - r))
+    }
+}
+
+/// Returns the directions of the largest and smallest eigenvalue of the symmetric 2x2 tensor in the tangent plane
+/// which best fits the normal curvatures along the edges in the least squares sense.
+fn principal_directions(mesh: &EditableMesh, vertex: usize, normal: &Vec3, neighbours: &[usize]) -> (Vec3, Vec3)
+{
+    let p = mesh.positions[vertex];
+    let helper = if normal.x.abs() < 0.9 { vec3(1.0, 0.0, 0.0) } else { vec3(0.0, 1.0, 0.0) };
+    let u = normal.cross(helper).normalize();
+    let v = normal.cross(u);
+
+    // The normal equations for the tensor [[a, b], [b, c]], where the normal curvature in the direction (x, y) is a x^2 + 2 b x y + c y^2
+    let mut matrix = [[0.0; 3]; 3];
+    let mut rhs = [0.0; 3];
+    for neighbour in neighbours.iter() {
+        let d = mesh.positions[*neighbour] - p;
+        let tangent = d - *normal * normal.dot(d);
+        if tangent.magnitude2() <= std::f64::MIN_POSITIVE {
+            continue;
+        }
+        let tangent = tangent.normalize();
+        let (x, y) = (tangent.dot(u), tangent.dot(v));
+        let row = [x * x, 2.0 * x * y, y * y];
+        let curvature = -2.0 * normal.dot(d) / d.magnitude2();
+        for i in 0..3 {
+            for j in 0..3 {
+                matrix[i][j] += row[i] * row[j];
+            }
+            rhs[i] += row[i] * curvature;
+        }
+    }
+
+    let det = |m: &[[f64; 3]; 3]| m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    let determinant = det(&matrix);
+    if determinant.abs() <= std::f64::EPSILON * det(&[[matrix[0][0], 0.0, 0.0], [0.0, matrix[1][1], 0.0], [0.0, 0.0, matrix[2][2]]]).abs() {
+        return (u, v);
+    }
+    let mut solution = [0.0; 3];
+    for k in 0..3 {
+        let mut replaced = matrix;
+        for i in 0..3 {
+            replaced[i][k] = rhs[i];
+        }
+        solution[k] = det(&replaced) / determinant;
+    }
+
+    let [a, b, c] = solution;
+    let angle = 0.5 * (2.0 * b).atan2(a - c);
+    let max_direction = angle.cos() * u + angle.sin() * v;
+    (max_direction, normal.cross(max_direction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_sphere()
+    {
+        let radius = 2.0;
+        let mesh = sphere(radius, 16);
+        let curvatures = vertex_curvatures(&mesh);
+        assert_eq!(curvatures.len(), mesh.no_vertices());
+        for (vertex_id, curvature) in curvatures.iter() {
+            let normal = mesh.vertex_position(*vertex_id) / radius;
+            assert!((curvature.mean - 1.0 / radius).abs() < 0.02 / radius);
+            assert!((curvature.gaussian - 1.0 / (radius * radius)).abs() < 0.05 / (radius * radius));
+            assert!(curvature.min <= curvature.mean && curvature.mean <= curvature.max);
+            assert!((curvature.max - curvature.min).abs() < 0.1 / radius);
+            for direction in [curvature.max_direction, curvature.min_direction].iter() {
+                assert!((direction.magnitude() - 1.0).abs() < 1.0e-9);
+                assert!(direction.dot(normal).abs() < 0.1);
+            }
+            assert!(curvature.max_direction.dot(curvature.min_direction).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_cylinder_directions()
+    {
+        // The side of a cylinder with radius 1 along the z axis, without the caps
+        let (n, m) = (32, 8);
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for i in 0..=m {
+            for j in 0..n {
+                let angle = 2.0 * std::f64::consts::PI * j as f64 / n as f64;
+                positions.extend(&[angle.cos(), angle.sin(), 0.25 * i as f64]);
+            }
+        }
+        let vertex = |i: u32, j: u32| i * n + j % n;
+        for i in 0..m {
+            for j in 0..n {
+                indices.extend(&[vertex(i, j), vertex(i, j + 1), vertex(i + 1, j + 1), vertex(i, j), vertex(i + 1, j + 1), vertex(i + 1, j)]);
+            }
+        }
+        let mesh = self::mesh(positions, indices);
+        let curvatures = vertex_curvatures(&mesh);
+
+        // The vertices on the two boundary circles have no curvature
+        assert_eq!(curvatures.len(), (n * (m - 1)) as usize);
+        for curvature in curvatures.values() {
+            assert!((curvature.mean - 0.5).abs() < 0.02);
+            assert!(curvature.gaussian.abs() < 1.0e-9);
+            assert!(curvature.max_direction.z.abs() < 0.1);
+            assert!(curvature.min_direction.z.abs() > 0.99);
+        }
+    }
+}
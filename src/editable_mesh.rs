@@ -0,0 +1,343 @@
+//! An indexed triangle list with the connectivity needed to collapse, split and flip edges locally, used by the local operations, for example simplification and remeshing.
+//! Edges can be preserved, in which case their vertices only move along the preserved edges and the corners of the preserved edges are kept.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::IndexedMesh;
+use std::collections::HashSet;
+
+/// An operation is rejected if it rotates the normal of a face by more than the angle with this cosine (about 78 degrees).
+pub(crate) const MIN_NORMAL_COSINE: f64 = 0.2;
+
+#[derive(Debug, Clone)]
+pub(crate) struct EditableMesh {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>,
+    pub is_removed: Vec<bool>,
+    pub vertex_triangles: Vec<Vec<usize>>,
+    /// The neighbours of each vertex along preserved edges.
+    pub constraints: Vec<Vec<usize>>,
+    /// Vertices on non-manifold edges, which are never moved or removed.
+    pub is_locked: Vec<bool>
+}
+
+impl EditableMesh {
+    ///
+    /// Creates an editable mesh where the feature edges, given with the smallest index first, and optionally the boundary edges are preserved.
+    ///
+    pub fn new(mesh: &IndexedMesh, feature_edges: &HashSet<(usize, usize)>, preserve_boundaries: bool) -> Self
+    {
+        let no_vertices = mesh.positions.len();
+        let mut constraints = vec![Vec::new(); no_vertices];
+        let mut is_locked = vec![false; no_vertices];
+        for ((i0, i1), triangles) in mesh.edge_triangles().iter() {
+            if triangles.len() > 2 {
+                is_locked[*i0] = true;
+                is_locked[*i1] = true;
+            }
+            else if (triangles.len() == 1 && preserve_boundaries) || feature_edges.contains(&(*i0, *i1)) {
+                constraints[*i0].push(*i1);
+                constraints[*i1].push(*i0);
+            }
+        }
+        EditableMesh {positions: mesh.positions.clone(), triangles: mesh.triangles.clone(), is_removed: vec![false; mesh.triangles.len()],
+            vertex_triangles: mesh.vertex_triangles(), constraints, is_locked}
+    }
+
+    ///
+    /// Returns the indexed mesh without the removed triangles.
+    ///
+    pub fn to_indexed(&self) -> IndexedMesh
+    {
+        let triangles = self.triangles.iter().zip(self.is_removed.iter()).filter(|(_, is_removed)| !**is_removed)
+            .map(|(triangle, _)| *triangle).collect();
+        IndexedMesh {positions: self.positions.clone(), triangles}
+    }
+
+    pub fn no_triangles(&self) -> usize
+    {
+        self.is_removed.iter().filter(|is_removed| !**is_removed).count()
+    }
+
+    ///
+    /// Returns the edges of the triangles which are not removed, the smallest index first.
+    ///
+    pub fn edges(&self) -> Vec<(usize, usize)>
+    {
+        let mut edges: Vec<(usize, usize)> = (0..self.triangles.len()).filter(|triangle| !self.is_removed[*triangle])
+            .flat_map(|triangle| {
+                let [i0, i1, i2] = self.triangles[triangle];
+                vec![(i0.min(i1), i0.max(i1)), (i1.min(i2), i1.max(i2)), (i2.min(i0), i2.max(i0))]
+            }).collect();
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+
+    pub fn neighbours(&self, vertex: usize) -> HashSet<usize>
+    {
+        self.vertex_triangles[vertex].iter().flat_map(|triangle| self.triangles[*triangle].iter().cloned())
+            .filter(|index| *index != vertex).collect()
+    }
+
+    pub fn edge_triangles(&self, i0: usize, i1: usize) -> Vec<usize>
+    {
+        self.vertex_triangles[i0].iter().cloned().filter(|triangle| self.triangles[*triangle].contains(&i1)).collect()
+    }
+
+    pub fn is_on_boundary(&self, vertex: usize) -> bool
+    {
+        self.neighbours(vertex).iter().any(|neighbour| self.edge_triangles(vertex, *neighbour).len() == 1)
+    }
+
+    pub fn is_constrained(&self, i0: usize, i1: usize) -> bool
+    {
+        self.constraints[i0].contains(&i1)
+    }
+
+    ///
+    /// Returns whether the vertex can move freely, ie. is not on a preserved or non-manifold edge.
+    ///
+    pub fn is_free(&self, vertex: usize) -> bool
+    {
+        !self.is_locked[vertex] && self.constraints[vertex].len() == 0
+    }
+
+    ///
+    /// Returns the normal of the vertex, ie. the area weighted average of the normals of the triangles around it.
+    ///
+    pub fn vertex_normal(&self, vertex: usize) -> Vec3
+    {
+        let mut normal = vec3(0.0, 0.0, 0.0);
+        for triangle in self.vertex_triangles[vertex].iter() {
+            let (p0, p1, p2) = self.triangle_positions(*triangle);
+            normal += (p1 - p0).cross(p2 - p0);
+        }
+        if normal.magnitude2() > 0.0 { normal.normalize() } else { normal }
+    }
+
+    ///
+    /// Returns the sum of the cotangents of the angles opposite to the edge, where the angles in degenerate triangles are ignored.
+    ///
+    pub fn cotangent_weight(&self, i0: usize, i1: usize) -> f64
+    {
+        let (p0, p1) = (self.positions[i0], self.positions[i1]);
+        self.edge_triangles(i0, i1).iter().map(|triangle| {
+            let opposite = *self.triangles[*triangle].iter().find(|index| **index != i0 && **index != i1).unwrap();
+            let (d0, d1) = (p0 - self.positions[opposite], p1 - self.positions[opposite]);
+            let sine = d0.cross(d1).magnitude();
+            if sine > std::f64::EPSILON * d0.magnitude() * d1.magnitude() { d0.dot(d1) / sine } else { 0.0 }
+        }).sum()
+    }
+
+    pub fn triangle_positions(&self, triangle: usize) -> (Vec3, Vec3, Vec3)
+    {
+        let [i0, i1, i2] = self.triangles[triangle];
+        (self.positions[i0], self.positions[i1], self.positions[i2])
+    }
+
+    ///
+    /// Returns the ways the edge can be collapsed with respect to the preserved edges, as pairs of the removed and the kept vertex.
+    /// If the kept vertex is not free, it must also keep its position.
+    ///
+    pub fn collapse_directions(&self, i0: usize, i1: usize) -> Vec<(usize, usize)>
+    {
+        if self.is_locked[i0] || self.is_locked[i1] {
+            return Vec::new();
+        }
+        match (self.constraints[i0].len(), self.constraints[i1].len()) {
+            (0, 0) => vec![(i0, i1), (i1, i0)],
+            (_, 0) => vec![(i1, i0)],
+            (0, _) => vec![(i0, i1)],
+            (n0, n1) => {
+                // Corners, where the number of preserved edges is not two, are kept
+                let mut directions = Vec::new();
+                if self.is_constrained(i0, i1) {
+                    if n0 == 2 { directions.push((i0, i1)); }
+                    if n1 == 2 { directions.push((i1, i0)); }
+                }
+                directions
+            }
+        }
+    }
+
+    ///
+    /// Returns whether collapsing the removed vertex into the kept vertex, which is moved to the position, keeps the mesh a two-manifold and does not fold any triangles over.
+    ///
+    pub fn is_collapsible(&self, removed: usize, kept: usize, position: &Vec3) -> bool
+    {
+        let edge_triangles = self.edge_triangles(removed, kept);
+        if edge_triangles.len() == 0 || edge_triangles.len() > 2 {
+            return false;
+        }
+
+        // The link condition; the only common neighbours are the opposite vertices of the triangles next to the edge
+        let neighbours0 = self.neighbours(removed);
+        let neighbours1 = self.neighbours(kept);
+        if neighbours0.intersection(&neighbours1).count() != edge_triangles.len() {
+            return false;
+        }
+        // Avoid collapsing a tetrahedron or a single triangle into nothing
+        if neighbours0.union(&neighbours1).filter(|index| **index != removed && **index != kept).count() <= edge_triangles.len() {
+            return false;
+        }
+        // Avoid pinching the surface together at an edge between two boundaries
+        if edge_triangles.len() == 2 && self.is_on_boundary(removed) && self.is_on_boundary(kept) {
+            return false;
+        }
+
+        for vertex in [removed, kept].iter() {
+            for triangle in self.vertex_triangles[*vertex].iter().filter(|triangle| !edge_triangles.contains(triangle)) {
+                let moved: Vec<Vec3> = self.triangles[*triangle].iter()
+                    .map(|index| if *index == removed || *index == kept { *position } else { self.positions[*index] }).collect();
+                if self.is_folded(*triangle, &moved[0], &moved[1], &moved[2]) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    ///
+    /// Collapses the edge by merging the removed vertex into the kept vertex, which is moved to the position. Returns the number of removed triangles.
+    ///
+    pub fn collapse(&mut self, removed: usize, kept: usize, position: Vec3) -> usize
+    {
+        self.positions[kept] = position;
+        let mut no_removed = 0;
+        for triangle in std::mem::replace(&mut self.vertex_triangles[removed], Vec::new()) {
+            if self.triangles[triangle].contains(&kept) {
+                self.is_removed[triangle] = true;
+                for index in self.triangles[triangle].iter().filter(|index| **index != removed) {
+                    self.vertex_triangles[*index].retain(|t| *t != triangle);
+                }
+                no_removed += 1;
+            }
+            else {
+                for index in self.triangles[triangle].iter_mut().filter(|index| **index == removed) {
+                    *index = kept;
+                }
+                self.vertex_triangles[kept].push(triangle);
+            }
+        }
+
+        for neighbour in std::mem::replace(&mut self.constraints[removed], Vec::new()) {
+            self.constraints[neighbour].retain(|index| *index != removed);
+            if neighbour != kept && !self.constraints[neighbour].contains(&kept) {
+                self.constraints[neighbour].push(kept);
+                self.constraints[kept].push(neighbour);
+            }
+        }
+        no_removed
+    }
+
+    ///
+    /// Splits the edge and the triangles next to it at the position and returns the new vertex.
+    ///
+    pub fn split_edge(&mut self, i0: usize, i1: usize, position: Vec3) -> usize
+    {
+        let edge_triangles = self.edge_triangles(i0, i1);
+        let vertex = self.positions.len();
+        self.positions.push(position);
+        self.vertex_triangles.push(Vec::new());
+        self.constraints.push(Vec::new());
+        self.is_locked.push(edge_triangles.len() > 2);
+
+        for triangle in edge_triangles {
+            let mut other = self.triangles[triangle];
+            for index in self.triangles[triangle].iter_mut().filter(|index| **index == i1) {
+                *index = vertex;
+            }
+            for index in other.iter_mut().filter(|index| **index == i0) {
+                *index = vertex;
+            }
+            self.vertex_triangles[i1].retain(|t| *t != triangle);
+            self.vertex_triangles[vertex].push(triangle);
+
+            let new_triangle = self.triangles.len();
+            self.triangles.push(other);
+            self.is_removed.push(false);
+            for index in other.iter() {
+                self.vertex_triangles[*index].push(new_triangle);
+            }
+        }
+
+        if self.is_constrained(i0, i1) {
+            self.constraints[i0].retain(|index| *index != i1);
+            self.constraints[i1].retain(|index| *index != i0);
+            self.constraints[i0].push(vertex);
+            self.constraints[i1].push(vertex);
+            self.constraints[vertex] = vec![i0, i1];
+        }
+        vertex
+    }
+
+    ///
+    /// Returns the vertices opposite to the edge in the two triangles next to it, if the edge can be flipped,
+    /// ie. if it is a consistently oriented manifold edge which is not preserved and the flipped edge does not already exist.
+    ///
+    pub fn flip_vertices(&self, i0: usize, i1: usize) -> Option<(usize, usize)>
+    {
+        if self.is_constrained(i0, i1) || self.is_locked[i0] || self.is_locked[i1] {
+            return None;
+        }
+        let edge_triangles = self.edge_triangles(i0, i1);
+        if edge_triangles.len() != 2 {
+            return None;
+        }
+        let opposite = |triangle: usize| -> (usize, bool) {
+            let indices = self.triangles[triangle];
+            let k = (0..3).find(|k| indices[*k] != i0 && indices[*k] != i1).unwrap();
+            (indices[k], indices[(k + 1) % 3] == i0)
+        };
+        let (c, forward0) = opposite(edge_triangles[0]);
+        let (d, forward1) = opposite(edge_triangles[1]);
+        if forward0 == forward1 || c == d || self.neighbours(c).contains(&d) {
+            return None;
+        }
+        Some((c, d))
+    }
+
+    ///
+    /// Flips the edge so it connects the opposite vertices of the two triangles next to it, if the edge can be flipped and the flip does not fold the triangles over.
+    /// Returns whether the edge is flipped.
+    ///
+    pub fn flip_edge(&mut self, i0: usize, i1: usize) -> bool
+    {
+        let (c, d) = match self.flip_vertices(i0, i1) {
+            Some(vertices) => vertices,
+            None => return false
+        };
+        let edge_triangles = self.edge_triangles(i0, i1);
+        // Orient the edge, so the first triangle is (a, b, c) and the second is (b, a, d)
+        let (t0, t1) = if self.triangles[edge_triangles[0]].contains(&c) { (edge_triangles[0], edge_triangles[1]) } else { (edge_triangles[1], edge_triangles[0]) };
+        let indices = self.triangles[t0];
+        let k = (0..3).find(|k| indices[*k] == c).unwrap();
+        let (a, b) = (indices[(k + 1) % 3], indices[(k + 2) % 3]);
+
+        let new0 = [a, d, c];
+        let new1 = [d, b, c];
+        for new in [new0, new1].iter() {
+            let (p0, p1, p2) = (self.positions[new[0]], self.positions[new[1]], self.positions[new[2]]);
+            if self.is_folded(t0, &p0, &p1, &p2) || self.is_folded(t1, &p0, &p1, &p2) {
+                return false;
+            }
+        }
+
+        self.triangles[t0] = new0;
+        self.triangles[t1] = new1;
+        self.vertex_triangles[b].retain(|t| *t != t0);
+        self.vertex_triangles[a].retain(|t| *t != t1);
+        self.vertex_triangles[d].push(t0);
+        self.vertex_triangles[c].push(t1);
+        true
+    }
+
+    /// Returns whether the triangle with the new positions is (almost) degenerate or its normal is rotated too much compared to the given triangle.
+    fn is_folded(&self, triangle: usize, p0: &Vec3, p1: &Vec3, p2: &Vec3) -> bool
+    {
+        let (q0, q1, q2) = self.triangle_positions(triangle);
+        let old_normal = (q1 - q0).cross(q2 - q0);
+        let new_normal = (p1 - p0).cross(p2 - p0);
+        new_normal.dot(old_normal) <= MIN_NORMAL_COSINE * new_normal.magnitude() * old_normal.magnitude()
+    }
+}
@@ -1,9 +1,14 @@
-
 use tri_mesh::prelude::*;
+use crate::compression::{self, Compression};
+use crate::attributes::{Attributes, Color, Layer};
+use crate::stitching::Origin;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 
 #[derive(Debug)]
 pub enum Error {
     IO(std::io::Error),
+    Zip(zip::result::ZipError),
     FileTypeNotSupported {message: String},
     ExtensionNotSpecified {message: String},
     FileNameNotSpecified {message: String}
@@ -15,7 +20,79 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<zip::result::ZipError> for Error {
+    fn from(other: zip::result::ZipError) -> Self {
+        Error::Zip(other)
+    }
+}
+
+///
+/// The unit of the coordinates in a file format which stores it, for example 3MF.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Micron,
+    Millimeter,
+    Centimeter,
+    Inch,
+    Foot,
+    Meter
+}
+
+impl Unit {
+    ///
+    /// The name of the unit as written in a 3MF model file.
+    ///
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Unit::Micron => "micron",
+            Unit::Millimeter => "millimeter",
+            Unit::Centimeter => "centimeter",
+            Unit::Inch => "inch",
+            Unit::Foot => "foot",
+            Unit::Meter => "meter"
+        }
+    }
+
+    ///
+    /// Returns the unit with the given name or `None` if the name is not a valid 3MF unit.
+    ///
+    pub fn from_name(name: &str) -> Option<Unit>
+    {
+        match name {
+            "micron" => Some(Unit::Micron),
+            "millimeter" => Some(Unit::Millimeter),
+            "centimeter" => Some(Unit::Centimeter),
+            "inch" => Some(Unit::Inch),
+            "foot" => Some(Unit::Foot),
+            "meter" => Some(Unit::Meter),
+            _ => None
+        }
+    }
+}
+
+impl Default for Unit {
+    fn default() -> Self {
+        Unit::Millimeter
+    }
+}
+
 pub fn save(mesh: &Mesh, path: &str) -> Result<(), Error>
+{
+    save_with_attributes(mesh, &Attributes::new(), path)
+}
+
+///
+/// Saves the mesh and the attribute layers which the file format supports. The file type is deduced from the extension:
+/// - VTK, PLY and GPM: All layers.
+/// - OBJ: A vertex colour layer named `color`.
+/// - Poly: A face tag layer named `marker` as facet boundary markers.
+/// - STL: A face tag layer named `origin`, see [Stitched::attributes](../stitching/struct.Stitched.html#method.attributes),
+///   where the faces of each origin are written as a solid with the [name](../stitching/enum.Origin.html#method.name) of the origin.
+/// - 3MF: No layers. The coordinates are tagged with the default unit, use [save_3mf](fn.save_3mf.html) to choose the unit.
+///
+pub fn save_with_attributes(mesh: &Mesh, attributes: &Attributes, path: &str) -> Result<(), Error>
 {
     let path_split: Vec<&str> = path.split('/').collect();
     if path_split.len() == 0
@@ -24,38 +101,831 @@ pub fn save(mesh: &Mesh, path: &str) -> Result<(), Error>
     }
     let filename = path_split.last().unwrap();
     let splitted: Vec<&str> = filename.split('.').collect();
-    if splitted.len() == 1
+    let compression = Compression::from_extension(splitted.last().unwrap());
+    let no_extensions = if compression == Compression::None { 1 } else { 2 };
+    if splitted.len() <= no_extensions
     {
         return Err(Error::ExtensionNotSpecified {message: format!("Extension for file {} is not specified!", splitted[0])})
     }
-    let extension = splitted[1];
+    let extension = splitted[splitted.len() - no_extensions];
 
     let data = if extension == "obj" {
-        Ok(mesh.parse_as_obj())
+        let object = ObjObject::new(splitted[0], mesh);
+        let object = match attributes.vertex_layer::<Color>("color") {
+            Some(colors) => object.with_vertex_colors(colors),
+            None => object
+        };
+        Ok(parse_as_obj(&[object], true, None).into_bytes())
     }
     else if extension == "poly" {
-        Ok(parse_as_poly(mesh))
+        match attributes.face_layer::<i32>("marker") {
+            Some(markers) => Ok(parse_as_poly_with_markers(mesh, markers).into_bytes()),
+            None => Ok(parse_as_poly(mesh).into_bytes())
+        }
+    }
+    else if extension == "3mf" {
+        parse_as_3mf(&[mesh], Unit::default())
+    }
+    else if extension == "stl" {
+        match attributes.face_layer::<i32>("origin") {
+            Some(origins) => Ok(parse_as_stl(mesh, &|face_id| Origin::from_tag(origins.get(&face_id).cloned().unwrap_or(0)).name()).into_bytes()),
+            None => Ok(parse_as_stl(mesh, &|_| splitted[0].to_string()).into_bytes())
+        }
+    }
+    else if extension == "gpm" {
+        Ok(parse_as_gpm(mesh, &attributes.gpm_attributes(mesh)))
+    }
+    else if extension == "vtk" {
+        Ok(parse_as_vtk(mesh, attributes).into_bytes())
+    }
+    else if extension == "ply" {
+        Ok(parse_as_ply(mesh, attributes).into_bytes())
     }
     else { Err(Error::FileTypeNotSupported {message: format!("Extension {} of file {} is not supported!", extension, splitted[0])}) };
-    std::fs::write(path, data?)?;
+    write_compressed(path, &data?, compression)
+}
+
+///
+/// Saves the meshes in a 3MF file at the given path, one object and build item for each mesh, with the coordinates tagged with the given unit.
+/// The file is compressed if the path has a compression extension, for example `model.3mf.gz`.
+///
+pub fn save_3mf(meshes: &[&Mesh], unit: Unit, path: &str) -> Result<(), Error>
+{
+    let compression = Compression::from_extension(path.split('.').last().unwrap());
+    let uncompressed_path = match compression.extension() {
+        Some(extension) => &path[..path.len() - extension.len() - 1],
+        None => path
+    };
+    if !uncompressed_path.ends_with(".3mf")
+    {
+        return Err(Error::FileTypeNotSupported {message: format!("The file {} is not a 3MF file!", path)})
+    }
+    write_compressed(path, &parse_as_3mf(meshes, unit)?, compression)
+}
+
+///
+/// Saves the objects in an OBJ file at the given path and, if any materials are given, the materials in an MTL file
+/// with the same name next to it (for example `model.obj` and `model.mtl`). If `with_normals` is true, the vertex normals are written.
+/// The OBJ file is compressed if the path has a compression extension (for example `model.obj.gz`), the MTL file is never compressed.
+///
+pub fn save_obj(objects: &[ObjObject], materials: &[Material], with_normals: bool, path: &str) -> Result<(), Error>
+{
+    let compression = Compression::from_extension(path.split('.').last().unwrap());
+    let uncompressed_path = match compression.extension() {
+        Some(extension) => &path[..path.len() - extension.len() - 1],
+        None => path
+    };
+    if !uncompressed_path.ends_with(".obj")
+    {
+        return Err(Error::FileTypeNotSupported {message: format!("The file {} is not an OBJ file!", path)})
+    }
+    let stem = &uncompressed_path[..uncompressed_path.len() - 4];
+    let filename = stem.split('/').last().unwrap();
+    if filename.len() == 0
+    {
+        return Err(Error::FileNameNotSpecified {message: format!("Filename is not specified!")})
+    }
+
+    if materials.len() > 0
+    {
+        let material_library = format!("{}.mtl", filename);
+        std::fs::write(format!("{}.mtl", stem), parse_as_mtl(materials))?;
+        write_compressed(path, parse_as_obj(objects, with_normals, Some(&material_library)).as_bytes(), compression)?;
+    }
+    else {
+        write_compressed(path, parse_as_obj(objects, with_normals, None).as_bytes(), compression)?;
+    }
     Ok(())
 }
 
+/// Writes the data to the file through a compressing stream, see [compression::write](../compression/fn.write.html).
+fn write_compressed(path: &str, data: &[u8], compression: Compression) -> Result<(), Error>
+{
+    let mut output = compression::write(path, compression)?;
+    output.write_all(data)?;
+    output.flush()?;
+    Ok(())
+}
+
+///
+/// A named object to write to an OBJ file, see [parse_as_obj](fn.parse_as_obj.html).
+///
+pub struct ObjObject<'a> {
+    name: String,
+    mesh: &'a Mesh,
+    texture_coordinates: Option<&'a HashMap<VertexID, (f64, f64)>>,
+    vertex_colors: Option<&'a HashMap<VertexID, Color>>,
+    material: Option<String>,
+    groups: Vec<(String, Vec<FaceID>, Option<String>)>
+}
+
+impl<'a> ObjObject<'a> {
+    ///
+    /// Creates an object with the given name containing all faces of the mesh.
+    ///
+    pub fn new(name: &str, mesh: &'a Mesh) -> Self
+    {
+        ObjObject {name: name.to_string(), mesh, texture_coordinates: None, vertex_colors: None, material: None, groups: Vec::new()}
+    }
+
+    ///
+    /// Writes a texture coordinate for each vertex. Vertices which are not in the map get the texture coordinate (0, 0).
+    ///
+    pub fn with_texture_coordinates(mut self, texture_coordinates: &'a HashMap<VertexID, (f64, f64)>) -> Self
+    {
+        self.texture_coordinates = Some(texture_coordinates);
+        self
+    }
+
+    ///
+    /// Writes a colour after the position of each vertex (`v x y z r g b`), which is supported by for example MeshLab.
+    /// Vertices which are not in the map are white.
+    ///
+    pub fn with_vertex_colors(mut self, vertex_colors: &'a HashMap<VertexID, Color>) -> Self
+    {
+        self.vertex_colors = Some(vertex_colors);
+        self
+    }
+
+    ///
+    /// Uses the material with the given name for the faces which are not in a group with its own material.
+    ///
+    pub fn with_material(mut self, material_name: &str) -> Self
+    {
+        self.material = Some(material_name.to_string());
+        self
+    }
+
+    ///
+    /// Writes the given faces in a group with the given name and optionally with its own material.
+    /// The faces of the mesh which are not in any group are written before the groups and a face which is in several groups is only written in the first.
+    ///
+    pub fn with_group(mut self, name: &str, faces: Vec<FaceID>, material_name: Option<&str>) -> Self
+    {
+        self.groups.push((name.to_string(), faces, material_name.map(|m| m.to_string())));
+        self
+    }
+}
+
+///
+/// A material written to an MTL file, see [parse_as_mtl](fn.parse_as_mtl.html).
+///
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub ambient: (f64, f64, f64),
+    pub diffuse: (f64, f64, f64),
+    pub specular: (f64, f64, f64),
+    pub shininess: f64,
+    pub opacity: f64,
+    pub diffuse_texture: Option<String>
+}
+
+impl Material {
+    ///
+    /// Creates a grey, diffuse and opaque material with the given name.
+    ///
+    pub fn new(name: &str) -> Self
+    {
+        Material {name: name.to_string(), ambient: (0.0, 0.0, 0.0), diffuse: (0.8, 0.8, 0.8), specular: (0.0, 0.0, 0.0),
+            shininess: 0.0, opacity: 1.0, diffuse_texture: None}
+    }
+}
+
+///
+/// Returns the objects in the OBJ file format. Each object is written in an `o` section followed by its groups in `g` sections.
+/// If `with_normals` is true, the vertex normals are written and referenced by the faces.
+/// If a material library is given, it is referenced with `mtllib` and the materials are referenced with `usemtl`.
+/// A `usemtl` is written whenever the material changes, and faces without a material after faces with a material are written after `usemtl default`.
+///
+pub fn parse_as_obj(objects: &[ObjObject], with_normals: bool, material_library: Option<&str>) -> String
+{
+    let mut output = String::new();
+    if let Some(library) = material_library {
+        output.push_str(&format!("mtllib {}\n", library));
+    }
+
+    let mut offset = 1;
+    let mut current_material = None;
+    for object in objects {
+        let mesh = object.mesh;
+        output.push_str(&format!("o {}\n", object.name));
+
+        let mut indices = HashMap::new();
+        for (i, vertex_id) in mesh.vertex_iter().enumerate() {
+            indices.insert(vertex_id, offset + i);
+            let p = mesh.vertex_position(vertex_id);
+            match object.vertex_colors {
+                Some(colors) => {
+                    let c = colors.get(&vertex_id).cloned().unwrap_or(Color::new(1.0, 1.0, 1.0, 1.0));
+                    output.push_str(&format!("v {} {} {} {} {} {}\n", p.x, p.y, p.z, c.r, c.g, c.b));
+                },
+                None => output.push_str(&format!("v {} {} {}\n", p.x, p.y, p.z))
+            }
+        }
+        if let Some(texture_coordinates) = object.texture_coordinates {
+            for vertex_id in mesh.vertex_iter() {
+                let (u, v) = texture_coordinates.get(&vertex_id).cloned().unwrap_or((0.0, 0.0));
+                output.push_str(&format!("vt {} {}\n", u, v));
+            }
+        }
+        if with_normals {
+            for vertex_id in mesh.vertex_iter() {
+                let n = mesh.vertex_normal(vertex_id);
+                output.push_str(&format!("vn {} {} {}\n", n.x, n.y, n.z));
+            }
+        }
+
+        let write_face = |output: &mut String, face_id: FaceID| {
+            let (v0, v1, v2) = mesh.face_vertices(face_id);
+            output.push_str("f");
+            for vertex_id in [v0, v1, v2].iter() {
+                let index = indices[vertex_id];
+                match (object.texture_coordinates.is_some(), with_normals) {
+                    (false, false) => output.push_str(&format!(" {}", index)),
+                    (true, false) => output.push_str(&format!(" {}/{}", index, index)),
+                    (false, true) => output.push_str(&format!(" {}//{}", index, index)),
+                    (true, true) => output.push_str(&format!(" {}/{}/{}", index, index, index))
+                }
+            }
+            output.push_str("\n");
+        };
+
+        let grouped: HashSet<FaceID> = object.groups.iter().flat_map(|(_, faces, _)| faces.iter().cloned()).collect();
+        use_material(&mut output, &mut current_material, object.material.as_ref());
+        for face_id in mesh.face_iter() {
+            if !grouped.contains(&face_id) {
+                write_face(&mut output, face_id);
+            }
+        }
+
+        let mut written = HashSet::new();
+        for (name, faces, material) in object.groups.iter() {
+            output.push_str(&format!("g {}\n", name));
+            use_material(&mut output, &mut current_material, material.as_ref().or(object.material.as_ref()));
+            for face_id in faces {
+                if written.insert(*face_id) {
+                    write_face(&mut output, *face_id);
+                }
+            }
+        }
+        offset += mesh.no_vertices();
+    }
+    output
+}
+
+/// Writes a `usemtl` if the material is different from the current material, which is then updated.
+fn use_material<'a>(output: &mut String, current_material: &mut Option<&'a String>, material: Option<&'a String>)
+{
+    if material != *current_material {
+        output.push_str(&format!("usemtl {}\n", material.map(|m| m.as_str()).unwrap_or("default")));
+        *current_material = material;
+    }
+}
+
+///
+/// Returns the materials in the MTL file format.
+///
+pub fn parse_as_mtl(materials: &[Material]) -> String
+{
+    let mut output = String::new();
+    for material in materials {
+        output.push_str(&format!("newmtl {}\n", material.name));
+        output.push_str(&format!("Ka {} {} {}\n", material.ambient.0, material.ambient.1, material.ambient.2));
+        output.push_str(&format!("Kd {} {} {}\n", material.diffuse.0, material.diffuse.1, material.diffuse.2));
+        output.push_str(&format!("Ks {} {} {}\n", material.specular.0, material.specular.1, material.specular.2));
+        output.push_str(&format!("Ns {}\n", material.shininess));
+        output.push_str(&format!("d {}\n", material.opacity));
+        if let Some(ref texture) = material.diffuse_texture {
+            output.push_str(&format!("map_Kd {}\n", texture));
+        }
+        output.push_str("\n");
+    }
+    output
+}
+
+///
+/// Returns the mesh in the ASCII STL format with one `solid` block for each patch name returned by the given function.
+/// The solids are written in the order in which their names are first returned when iterating the faces.
+/// Whitespace in the names are replaced by underscores, so each solid can be used as a boundary patch in OpenFOAM (`snappyHexMesh`).
+///
+pub fn parse_as_stl(mesh: &Mesh, patch_name: &dyn Fn(FaceID) -> String) -> String
+{
+    let mut patch_names = Vec::new();
+    let mut patches: HashMap<String, Vec<FaceID>> = HashMap::new();
+    for face_id in mesh.face_iter() {
+        let name: String = patch_name(face_id).split_whitespace().collect::<Vec<&str>>().join("_");
+        if !patches.contains_key(&name) {
+            patch_names.push(name.clone());
+        }
+        patches.entry(name).or_insert(Vec::new()).push(face_id);
+    }
+
+    let mut output = String::new();
+    for name in patch_names {
+        output.push_str(&format!("solid {}\n", name));
+        for face_id in patches[&name].iter() {
+            let normal = mesh.face_normal(*face_id);
+            let (p0, p1, p2) = mesh.face_positions(*face_id);
+            output.push_str(&format!("  facet normal {} {} {}\n    outer loop\n", normal.x, normal.y, normal.z));
+            for p in [p0, p1, p2].iter() {
+                output.push_str(&format!("      vertex {} {} {}\n", p.x, p.y, p.z));
+            }
+            output.push_str("    endloop\n  endfacet\n");
+        }
+        output.push_str(&format!("endsolid {}\n", name));
+    }
+    output
+}
+
+///
+/// Returns the mesh in the poly format, where all facets get the boundary marker 0.
+///
 pub fn parse_as_poly(mesh: &Mesh) -> String
+{
+    parse_as_poly_with_markers(mesh, &HashMap::new())
+}
+
+///
+/// Returns the mesh in the poly format with the given boundary marker for each facet. Faces without a marker get the marker 0.
+///
+pub fn parse_as_poly_with_markers(mesh: &Mesh, markers: &HashMap<FaceID, i32>) -> String
 {
     let mut output = format!("{} 3 0 0\n", mesh.no_vertices());
 
-    let positions = &mesh.positions_buffer();
-    for i in 0..mesh.no_vertices()
+    let mut indices = HashMap::new();
+    for (i, vertex_id) in mesh.vertex_iter().enumerate() {
+        indices.insert(vertex_id, i + 1);
+        let p = mesh.vertex_position(vertex_id);
+        output.push_str(&format!("{} {} {} {}\n", i + 1, p.x, p.y, p.z));
+    }
+
+    output.push_str(&format!("{} 1\n", mesh.no_faces()));
+    for face_id in mesh.face_iter() {
+        let (v0, v1, v2) = mesh.face_vertices(face_id);
+        output.push_str(&format!("1 0 {}\n", markers.get(&face_id).cloned().unwrap_or(0)));
+        output.push_str(&format!("3 {} {} {}\n", indices[&v0], indices[&v1], indices[&v2]));
+    }
+    output
+}
+
+///
+/// Returns the mesh and all attribute layers in the legacy ASCII VTK format (polygonal data),
+/// with the vertex layers as point data and the face layers as cell data.
+/// Vertices and faces without a value in a scalar or vector layer get NaN, so they are not mistaken for zeros, and zero in a tag or colour layer.
+///
+pub fn parse_as_vtk(mesh: &Mesh, attributes: &Attributes) -> String
+{
+    let mut output = String::new();
+    output.push_str("# vtk DataFile Version 3.0\ngeo-proc\nASCII\nDATASET POLYDATA\n");
+
+    let mut indices = HashMap::new();
+    output.push_str(&format!("POINTS {} double\n", mesh.no_vertices()));
+    for (i, vertex_id) in mesh.vertex_iter().enumerate() {
+        indices.insert(vertex_id, i);
+        let p = mesh.vertex_position(vertex_id);
+        output.push_str(&format!("{} {} {}\n", p.x, p.y, p.z));
+    }
+
+    output.push_str(&format!("POLYGONS {} {}\n", mesh.no_faces(), mesh.no_faces() * 4));
+    for face_id in mesh.face_iter() {
+        let (v0, v1, v2) = mesh.face_vertices(face_id);
+        output.push_str(&format!("3 {} {} {}\n", indices[&v0], indices[&v1], indices[&v2]));
+    }
+
+    let vertex_layers = attributes.vertex_layers();
+    if vertex_layers.len() > 0 {
+        output.push_str(&format!("POINT_DATA {}\n", mesh.no_vertices()));
+        let vertices: Vec<VertexID> = mesh.vertex_iter().collect();
+        for (name, layer) in vertex_layers {
+            write_vtk_layer(&mut output, name, layer, &vertices);
+        }
+    }
+
+    let face_layers = attributes.face_layers();
+    if face_layers.len() > 0 {
+        output.push_str(&format!("CELL_DATA {}\n", mesh.no_faces()));
+        let faces: Vec<FaceID> = mesh.face_iter().collect();
+        for (name, layer) in face_layers {
+            write_vtk_layer(&mut output, name, layer, &faces);
+        }
+    }
+    output
+}
+
+fn write_vtk_layer<K: Eq + std::hash::Hash + Copy>(output: &mut String, name: &str, layer: &Layer<K>, keys: &[K])
+{
+    let name = name.split_whitespace().collect::<Vec<&str>>().join("_");
+    match layer {
+        Layer::Scalar(_) => output.push_str(&format!("SCALARS {} double 1\nLOOKUP_TABLE default\n", name)),
+        Layer::Tag(_) => output.push_str(&format!("SCALARS {} int 1\nLOOKUP_TABLE default\n", name)),
+        Layer::Vector(_) => output.push_str(&format!("VECTORS {} double\n", name)),
+        Layer::Color(_) => output.push_str(&format!("COLOR_SCALARS {} 4\n", name))
+    }
+    for key in keys {
+        let values = components_or_missing(layer, key);
+        output.push_str(&values.iter().map(|v| format!("{}", v)).collect::<Vec<String>>().join(" "));
+        output.push_str("\n");
+    }
+}
+
+///
+/// Returns the mesh and all attribute layers in the ASCII PLY format, with the vertex layers as vertex properties and
+/// the face layers as face properties. A vertex layer named `color` is written as the standard `red`, `green`, `blue` and `alpha` properties,
+/// other vectors and colours as one property for each component, for example `normal_x` or `paint_red`.
+/// Vertices and faces without a value in a scalar or vector layer get NaN, so they are not mistaken for zeros, and zero in a tag or colour layer.
+///
+pub fn parse_as_ply(mesh: &Mesh, attributes: &Attributes) -> String
+{
+    let vertex_layers = attributes.vertex_layers();
+    let face_layers = attributes.face_layers();
+
+    let mut output = String::new();
+    output.push_str("ply\nformat ascii 1.0\ncomment geo-proc\n");
+    output.push_str(&format!("element vertex {}\nproperty double x\nproperty double y\nproperty double z\n", mesh.no_vertices()));
+    for (name, layer) in vertex_layers.iter() {
+        write_ply_properties(&mut output, name, layer, true);
+    }
+    output.push_str(&format!("element face {}\nproperty list uchar int vertex_indices\n", mesh.no_faces()));
+    for (name, layer) in face_layers.iter() {
+        write_ply_properties(&mut output, name, layer, false);
+    }
+    output.push_str("end_header\n");
+
+    let mut indices = HashMap::new();
+    for (i, vertex_id) in mesh.vertex_iter().enumerate() {
+        indices.insert(vertex_id, i);
+        let p = mesh.vertex_position(vertex_id);
+        output.push_str(&format!("{} {} {}", p.x, p.y, p.z));
+        for (_, layer) in vertex_layers.iter() {
+            write_ply_values(&mut output, layer, &vertex_id);
+        }
+        output.push_str("\n");
+    }
+    for face_id in mesh.face_iter() {
+        let (v0, v1, v2) = mesh.face_vertices(face_id);
+        output.push_str(&format!("3 {} {} {}", indices[&v0], indices[&v1], indices[&v2]));
+        for (_, layer) in face_layers.iter() {
+            write_ply_values(&mut output, layer, &face_id);
+        }
+        output.push_str("\n");
+    }
+    output
+}
+
+fn write_ply_properties<K: Eq + std::hash::Hash + Copy>(output: &mut String, name: &str, layer: &Layer<K>, is_vertex_layer: bool)
+{
+    let name = name.split_whitespace().collect::<Vec<&str>>().join("_");
+    match layer {
+        Layer::Scalar(_) => output.push_str(&format!("property double {}\n", name)),
+        Layer::Tag(_) => output.push_str(&format!("property int {}\n", name)),
+        Layer::Vector(_) => {
+            for component in ["x", "y", "z"].iter() {
+                output.push_str(&format!("property double {}_{}\n", name, component));
+            }
+        },
+        Layer::Color(_) => {
+            for component in ["red", "green", "blue", "alpha"].iter() {
+                if is_vertex_layer && name == "color" {
+                    output.push_str(&format!("property uchar {}\n", component));
+                }
+                else {
+                    output.push_str(&format!("property uchar {}_{}\n", name, component));
+                }
+            }
+        }
+    }
+}
+
+fn write_ply_values<K: Eq + std::hash::Hash + Copy>(output: &mut String, layer: &Layer<K>, key: &K)
+{
+    let values = components_or_missing(layer, key);
+    match layer {
+        Layer::Tag(_) => output.push_str(&format!(" {}", values[0] as i32)),
+        Layer::Color(_) => {
+            for value in values {
+                output.push_str(&format!(" {}", (value.max(0.0).min(1.0) * 255.0).round() as u8));
+            }
+        },
+        _ => {
+            for value in values {
+                output.push_str(&format!(" {}", value));
+            }
+        }
+    }
+}
+
+/// Returns the components of the value for the key, or NaN for each component if a scalar or vector is missing and zero if a tag or colour is missing.
+fn components_or_missing<K: Eq + std::hash::Hash + Copy>(layer: &Layer<K>, key: &K) -> Vec<f64>
+{
+    layer.components(key).unwrap_or_else(|| {
+        let missing = match layer {
+            Layer::Scalar(_) | Layer::Vector(_) => std::f64::NAN,
+            Layer::Tag(_) | Layer::Color(_) => 0.0
+        };
+        vec![missing; layer.no_components()]
+    })
+}
+
+///
+/// Returns the content of a 3MF file (a zip container) containing one object and one build item for each of the given meshes.
+/// The coordinates are written as they are and tagged with the given unit.
+///
+pub fn parse_as_3mf(meshes: &[&Mesh], unit: Unit) -> Result<Vec<u8>, Error>
+{
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default();
+
+    writer.start_file("[Content_Types].xml", options)?;
+    writer.write_all(THREEMF_CONTENT_TYPES.as_bytes())?;
+
+    writer.start_file("_rels/.rels", options)?;
+    writer.write_all(threemf_relationships().as_bytes())?;
+
+    writer.start_file("3D/3dmodel.model", options)?;
+    writer.write_all(parse_as_3mf_model(meshes, unit).as_bytes())?;
+
+    Ok(writer.finish()?.into_inner())
+}
+
+fn parse_as_3mf_model(meshes: &[&Mesh], unit: Unit) -> String
+{
+    let mut output = String::new();
+    output.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str(&format!("<model unit=\"{}\" xml:lang=\"en-US\" xmlns=\"{}\">\n", unit.name(), THREEMF_CORE_NAMESPACE));
+    output.push_str(" <resources>\n");
+    for (i, mesh) in meshes.iter().enumerate()
+    {
+        output.push_str(&format!("  <object id=\"{}\" type=\"model\">\n   <mesh>\n    <vertices>\n", i+1));
+        let positions = mesh.positions_buffer();
+        for p in positions.chunks(3)
+        {
+            output.push_str(&format!("     <vertex x=\"{}\" y=\"{}\" z=\"{}\"/>\n", p[0], p[1], p[2]));
+        }
+        output.push_str("    </vertices>\n    <triangles>\n");
+        let indices = mesh.indices_buffer();
+        for t in indices.chunks(3)
+        {
+            output.push_str(&format!("     <triangle v1=\"{}\" v2=\"{}\" v3=\"{}\"/>\n", t[0], t[1], t[2]));
+        }
+        output.push_str("    </triangles>\n   </mesh>\n  </object>\n");
+    }
+    output.push_str(" </resources>\n <build>\n");
+    for i in 0..meshes.len()
     {
-        output = format!("{}{} {} {} {}\n", output, i+1, positions[i*3], positions[i*3 + 1], positions[i*3 + 2]);
+        output.push_str(&format!("  <item objectid=\"{}\"/>\n", i+1));
     }
+    output.push_str(" </build>\n</model>\n");
+    output
+}
 
-    output = format!("{}{} 0\n", output, mesh.no_faces());
-    let indices = mesh.indices_buffer();
-    for i in 0..mesh.no_faces() {
-        output = format!("{}1 0 0\n", output);
-        output = format!("{}3 {} {} {}\n", output, indices[i*3] + 1, indices[i*3 + 1] + 1, indices[i*3 + 2] + 1);
+///
+/// Where the values of a [GpmAttribute](struct.GpmAttribute.html) are defined.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GpmAttributeLocation {
+    Vertex,
+    Face
+}
+
+///
+/// How the values of a [GpmAttribute](struct.GpmAttribute.html) are interpreted. The values are always stored as `f64`.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GpmAttributeKind {
+    Real,
+    Integer,
+    Color
+}
+
+///
+/// A named attribute stored in a GPM file. The values are ordered as the vertices or faces of the mesh
+/// (ie. as in the positions and indices buffers) and each vertex or face has `no_components` values.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpmAttribute {
+    pub name: String,
+    pub location: GpmAttributeLocation,
+    pub kind: GpmAttributeKind,
+    pub no_components: usize,
+    pub values: Vec<f64>
+}
+
+pub(crate) const GPM_MAGIC: &[u8; 4] = b"GPM\0";
+/// Version 2 added the kind of the attributes.
+pub(crate) const GPM_VERSION: u32 = 2;
+
+///
+/// Returns the mesh and the given attributes in the native binary GPM format, which is fast to save and load.
+/// The content is a header with a magic number, the format version and the number of vertices, faces and attributes,
+/// followed by the positions, indices and attributes in little endian and finally a CRC32 checksum of everything before it.
+///
+/// # Panics
+///
+/// If the number of values of an attribute does not match the number of vertices or faces times the number of components.
+///
+pub fn parse_as_gpm(mesh: &Mesh, attributes: &[GpmAttribute]) -> Vec<u8>
+{
+    let mut output = Vec::new();
+    output.extend_from_slice(GPM_MAGIC);
+    output.extend_from_slice(&GPM_VERSION.to_le_bytes());
+    output.extend_from_slice(&(mesh.no_vertices() as u32).to_le_bytes());
+    output.extend_from_slice(&(mesh.no_faces() as u32).to_le_bytes());
+    output.extend_from_slice(&(attributes.len() as u32).to_le_bytes());
+
+    for value in mesh.positions_buffer() {
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+    for index in mesh.indices_buffer() {
+        output.extend_from_slice(&index.to_le_bytes());
+    }
+
+    for attribute in attributes {
+        let no_elements = match attribute.location {
+            GpmAttributeLocation::Vertex => mesh.no_vertices(),
+            GpmAttributeLocation::Face => mesh.no_faces()
+        };
+        assert_eq!(attribute.values.len(), no_elements * attribute.no_components,
+                   "The number of values of attribute {} does not match the mesh", attribute.name);
+
+        output.extend_from_slice(&(attribute.name.len() as u32).to_le_bytes());
+        output.extend_from_slice(attribute.name.as_bytes());
+        output.push(match attribute.location { GpmAttributeLocation::Vertex => 0, GpmAttributeLocation::Face => 1 });
+        output.push(match attribute.kind { GpmAttributeKind::Real => 0, GpmAttributeKind::Integer => 1, GpmAttributeKind::Color => 2 });
+        output.extend_from_slice(&(attribute.no_components as u32).to_le_bytes());
+        for value in attribute.values.iter() {
+            output.extend_from_slice(&value.to_le_bytes());
+        }
     }
+
+    let checksum = crc32fast::hash(&output);
+    output.extend_from_slice(&checksum.to_le_bytes());
     output
-}
\ No newline at end of file
+}
+
+pub(crate) const THREEMF_CORE_NAMESPACE: &str = "http://schemas.microsoft.com/3dmanufacturing/core/2015/02";
+pub(crate) const THREEMF_MODEL_RELATIONSHIP_TYPE: &str = "http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel";
+
+const THREEMF_CONTENT_TYPES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">
+ <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>
+ <Default Extension=\"model\" ContentType=\"application/vnd.ms-package.3dmanufacturing-3dmodel+xml\"/>
+</Types>
+";
+
+fn threemf_relationships() -> String
+{
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">
+ <Relationship Target=\"/3D/3dmodel.model\" Id=\"rel0\" Type=\"{}\"/>
+</Relationships>
+", THREEMF_MODEL_RELATIONSHIP_TYPE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_obj_groups_and_materials()
+    {
+        let cube = cube(1);
+        let faces: Vec<FaceID> = cube.face_iter().collect();
+        let object = ObjObject::new("cube", &cube)
+            .with_group("red", faces[0..4].to_vec(), Some("red"))
+            .with_group("plain", faces[2..8].to_vec(), None);
+        let output = parse_as_obj(&[object], false, Some("cube.mtl"));
+
+        let lines: Vec<&str> = output.lines().filter(|line| !line.starts_with("v ")).collect();
+        let no_faces = |start: usize| lines[start..].iter().take_while(|line| line.starts_with("f ")).count();
+        assert_eq!(lines[0..2], ["mtllib cube.mtl", "o cube"]);
+        assert_eq!(no_faces(2), 4);
+        assert_eq!(lines[6..8], ["g red", "usemtl red"]);
+        assert_eq!(no_faces(8), 4);
+        assert_eq!(lines[12..14], ["g plain", "usemtl default"]);
+        assert_eq!(no_faces(14), 4);
+        assert_eq!(lines.len(), 18);
+        assert!(lines.iter().all(|line| !line.starts_with("f ") || !line.contains('/')));
+    }
+
+    #[test]
+    fn test_obj_round_trip()
+    {
+        let cube = cube(2);
+        let sphere = sphere(1.0, 4);
+        let faces: Vec<FaceID> = sphere.face_iter().collect();
+        let objects = [ObjObject::new("cube", &cube).with_material("grey"),
+            ObjObject::new("sphere", &sphere).with_group("top", faces[0..8].to_vec(), None)];
+        let meshes = crate::importer::parse_obj(&parse_as_obj(&objects, false, None)).unwrap();
+
+        let names: Vec<&str> = meshes.iter().map(|named| named.name.as_str()).collect();
+        assert_eq!(names, ["cube", "sphere", "sphere/top"]);
+        assert_eq!(sorted_triangles(&meshes[0].mesh), sorted_triangles(&cube));
+        assert_eq!(meshes[1].mesh.no_faces(), sphere.no_faces() - 8);
+        assert_eq!(meshes[2].mesh.no_faces(), 8);
+    }
+
+    #[test]
+    fn test_stl_patches()
+    {
+        let cube = cube(1);
+        let patch_name = |face_id| {
+            let normal = cube.face_normal(face_id);
+            if normal.z > 0.5 { "top side" } else if normal.z < -0.5 { "bottom" } else { "walls" }
+        };
+        let output = parse_as_stl(&cube, &|face_id| patch_name(face_id).to_string());
+
+        let mut expected = Vec::new();
+        for face_id in cube.face_iter() {
+            let solid = format!("solid {}", patch_name(face_id).replace(' ', "_"));
+            if !expected.contains(&solid) {
+                expected.push(solid);
+            }
+        }
+        let solids: Vec<&str> = output.lines().filter(|line| line.starts_with("solid ")).collect();
+        assert_eq!(solids, expected);
+        assert_eq!(output.lines().filter(|line| line.starts_with("endsolid ")).count(), 3);
+        assert_eq!(output.matches("facet normal").count(), 12);
+        assert_eq!(output.matches("vertex ").count(), 36);
+
+        let top = &output[output.find("solid top_side").unwrap()..output.find("endsolid top_side").unwrap()];
+        assert_eq!(top.matches("facet normal 0 0 1\n").count(), 2);
+        assert_eq!(top.matches(" 1\n").count(), 8);
+    }
+
+    #[test]
+    fn test_stl_single_solid()
+    {
+        let path = std::env::temp_dir().join(format!("geo-proc-{}-box.stl", std::process::id())).to_str().unwrap().to_string();
+        save(&cube(2), &path).unwrap();
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(output.starts_with("solid "));
+        assert_eq!(output.lines().filter(|line| line.starts_with("solid ")).count(), 1);
+        assert_eq!(output.matches("endfacet").count(), 48);
+    }
+
+    #[test]
+    fn test_stl_origin_patches()
+    {
+        let cube = cube(1);
+        let mut attributes = Attributes::new();
+        for face_id in cube.face_iter() {
+            let origin = if cube.face_normal(face_id).z > 0.5 { Origin::Source(1) } else { Origin::Model };
+            attributes.face_layer_mut::<i32>("origin").insert(face_id, origin.tag());
+        }
+        let path = std::env::temp_dir().join(format!("geo-proc-{}-patches.stl", std::process::id())).to_str().unwrap().to_string();
+        save_with_attributes(&cube, &attributes, &path).unwrap();
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut solids: Vec<&str> = output.lines().filter(|line| line.starts_with("solid ")).collect();
+        solids.sort();
+        assert_eq!(solids, ["solid model", "solid source1"]);
+    }
+
+    #[test]
+    fn test_poly_markers()
+    {
+        let cube = cube(1);
+        let mut markers = HashMap::new();
+        for face_id in cube.face_iter().take(2) {
+            markers.insert(face_id, 3);
+        }
+        let output = parse_as_poly_with_markers(&cube, &markers);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "8 3 0 0");
+        assert_eq!(lines[9], "12 1");
+        let facets: Vec<&str> = lines[10..].iter().step_by(2).cloned().collect();
+        assert_eq!(facets.iter().filter(|line| **line == "1 0 3").count(), 2);
+        assert_eq!(facets.iter().filter(|line| **line == "1 0 0").count(), 10);
+
+        assert_eq!(parse_as_poly(&cube), parse_as_poly_with_markers(&cube, &HashMap::new()));
+    }
+
+    #[test]
+    fn test_missing_values()
+    {
+        let mesh = cube(1);
+        let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+        let mut attributes = Attributes::new();
+        attributes.vertex_layer_mut::<f64>("curvature").insert(vertex_ids[0], 1.5);
+        attributes.vertex_layer_mut::<Vec3>("direction").insert(vertex_ids[0], vec3(1.0, 0.0, 0.0));
+        attributes.vertex_layer_mut::<i32>("tag").insert(vertex_ids[0], 3);
+
+        let vtk = parse_as_vtk(&mesh, &attributes);
+        let lines: Vec<&str> = vtk.lines().skip_while(|line| !line.starts_with("POINT_DATA")).collect();
+        let values = |header: &str| lines.iter().skip_while(|line| !line.starts_with(header)).skip(1).filter(|line| !line.starts_with("LOOKUP_TABLE"))
+            .take(8).cloned().collect::<Vec<&str>>();
+        assert_eq!(values("SCALARS curvature"), ["1.5", "NaN", "NaN", "NaN", "NaN", "NaN", "NaN", "NaN"]);
+        assert_eq!(values("VECTORS direction")[0..2], ["1 0 0", "NaN NaN NaN"]);
+        assert_eq!(values("SCALARS tag")[0..2], ["3", "0"]);
+
+        let ply = parse_as_ply(&mesh, &attributes);
+        let vertex_lines: Vec<&str> = ply.lines().skip_while(|line| *line != "end_header").skip(1).take(8).collect();
+        assert!(vertex_lines[0].ends_with(" 1.5 1 0 0 3"));
+        assert!(vertex_lines[1..].iter().all(|line| line.matches("NaN").count() == 4 && line.ends_with(" 0")));
+    }
+}
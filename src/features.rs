@@ -0,0 +1,254 @@
+//! Detection of feature edges, ie. the sharp edges, boundary edges and non-manifold edges of a mesh, and chaining of the edges into polylines.
+//!
+//! The feature edges can be preserved by [simplify](../simplification/fn.simplify.html), [remesh](../remeshing/fn.remesh.html),
+//! [smooth](../smoothing/fn.smooth.html) and [subdivide](../subdivision/fn.subdivide.html) by passing them in the options,
+//! so for example the corners of a CAD model are not rounded off. The first three can also find the sharp edges themselves, given the `feature_angle` option.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::{IndexedMesh, edge_indices};
+use std::collections::{HashMap, HashSet};
+
+///
+/// The default angle threshold for sharp edges, 30 degrees.
+///
+pub const DEFAULT_ANGLE_THRESHOLD: f64 = std::f64::consts::PI / 6.0;
+
+///
+/// The feature edges found by [feature_edges](fn.feature_edges.html).
+/// An edge is given by its two end vertices, the vertex which comes first in [vertex_iter](../prelude/struct.Mesh.html#method.vertex_iter) first,
+/// and the edges are listed in the order of their vertices in the mesh.
+///
+#[derive(Debug, Clone, Default)]
+pub struct FeatureEdges {
+    /// Edges shared by two faces where the angle between the face normals is larger than the angle threshold.
+    pub sharp_edges: Vec<(VertexID, VertexID)>,
+    /// Edges with only one neighbouring face.
+    pub boundary_edges: Vec<(VertexID, VertexID)>,
+    /// Edges shared by more than two faces.
+    pub non_manifold_edges: Vec<(VertexID, VertexID)>
+}
+
+impl FeatureEdges {
+    ///
+    /// Returns all the feature edges, first the sharp edges, then the boundary edges and last the non-manifold edges.
+    ///
+    pub fn edges(&self) -> Vec<(VertexID, VertexID)>
+    {
+        self.sharp_edges.iter().chain(self.boundary_edges.iter()).chain(self.non_manifold_edges.iter()).cloned().collect()
+    }
+
+    ///
+    /// Returns all the feature edges chained into polylines, see [polylines](fn.polylines.html).
+    ///
+    pub fn polylines(&self) -> Vec<Vec<VertexID>>
+    {
+        polylines(&self.edges())
+    }
+
+    ///
+    /// Returns the total number of feature edges.
+    ///
+    pub fn no_edges(&self) -> usize
+    {
+        self.sharp_edges.len() + self.boundary_edges.len() + self.non_manifold_edges.len()
+    }
+}
+
+///
+/// Returns the sharp, boundary and non-manifold edges of the mesh.
+/// An edge is sharp if the angle in radians between the normals of its two faces is larger than the angle threshold, for example
+/// [DEFAULT_ANGLE_THRESHOLD](constant.DEFAULT_ANGLE_THRESHOLD.html). Edges between inconsistently oriented faces are compared as if the faces were consistently oriented,
+/// and edges next to degenerate faces are never sharp.
+///
+pub fn feature_edges(mesh: &Mesh, angle_threshold: f64) -> FeatureEdges
+{
+    let indexed = IndexedMesh::new(mesh);
+    let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+    let normals: Vec<Vec3> = (0..indexed.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = indexed.triangle_positions(triangle);
+        let normal = (p1 - p0).cross(p2 - p0);
+        if normal.magnitude2() > 0.0 { normal.normalize() } else { normal }
+    }).collect();
+
+    let edges = indexed.edge_triangles();
+    let mut edge_list: Vec<(&(usize, usize), &Vec<(usize, bool)>)> = edges.iter().collect();
+    edge_list.sort_by_key(|(edge, _)| **edge);
+    let mut features = FeatureEdges::default();
+    let min_cosine = angle_threshold.cos();
+    for ((i0, i1), triangles) in edge_list {
+        let edge = (vertex_ids[*i0], vertex_ids[*i1]);
+        match triangles.len() {
+            1 => features.boundary_edges.push(edge),
+            2 => {
+                let (n0, n1) = (normals[triangles[0].0], normals[triangles[1].0]);
+                if n0.magnitude2() == 0.0 || n1.magnitude2() == 0.0 {
+                    continue;
+                }
+                // Consistently oriented triangles traverse the edge in opposite directions
+                let cosine = if triangles[0].1 != triangles[1].1 { n0.dot(n1) } else { -n0.dot(n1) };
+                if cosine < min_cosine {
+                    features.sharp_edges.push(edge);
+                }
+            },
+            _ => features.non_manifold_edges.push(edge)
+        }
+    }
+    features
+}
+
+///
+/// Returns the given edges and, if the angle threshold is given, the sharp edges of the mesh, as edges of an indexed mesh created from the mesh.
+///
+pub(crate) fn preserved_edge_indices(mesh: &Mesh, edges: &[(VertexID, VertexID)], angle_threshold: Option<f64>) -> HashSet<(usize, usize)>
+{
+    let mut indices = edge_indices(mesh, edges);
+    if let Some(angle_threshold) = angle_threshold {
+        indices.extend(edge_indices(mesh, &feature_edges(mesh, angle_threshold).sharp_edges));
+    }
+    indices
+}
+
+///
+/// Chains the edges into polylines, each given by its vertices in order. A polyline ends at vertices where the number of edges is different from two,
+/// so the polylines meet at corners, and a closed loop without such vertices is given with the first vertex repeated at the end.
+/// The polylines are found in the order the vertices first appear in the edges.
+///
+pub fn polylines(edges: &[(VertexID, VertexID)]) -> Vec<Vec<VertexID>>
+{
+    // The vertices are numbered in the order they first appear, since the vertex IDs are not ordered
+    let mut vertices: Vec<VertexID> = Vec::new();
+    let mut indices: HashMap<VertexID, usize> = HashMap::new();
+    let mut neighbours: Vec<Vec<usize>> = Vec::new();
+    for (v0, v1) in edges.iter().filter(|(v0, v1)| v0 != v1) {
+        let mut index = |vertex_id: VertexID| *indices.entry(vertex_id).or_insert_with(|| {
+            vertices.push(vertex_id);
+            neighbours.push(Vec::new());
+            vertices.len() - 1
+        });
+        let (i0, i1) = (index(*v0), index(*v1));
+        if !neighbours[i0].contains(&i1) {
+            neighbours[i0].push(i1);
+            neighbours[i1].push(i0);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut polylines = Vec::new();
+    let mut walk = |start: usize, next: usize, visited: &mut HashSet<(usize, usize)>| {
+        let mut polyline = vec![vertices[start]];
+        let (mut previous, mut current) = (start, next);
+        loop {
+            visited.insert((previous.min(current), previous.max(current)));
+            polyline.push(vertices[current]);
+            let list = &neighbours[current];
+            if current == start || list.len() != 2 {
+                break;
+            }
+            let following = if list[0] == previous { list[1] } else { list[0] };
+            if visited.contains(&(current.min(following), current.max(following))) {
+                break;
+            }
+            previous = current;
+            current = following;
+        }
+        polylines.push(polyline);
+    };
+
+    // Open polylines start and end at corners, the remaining edges form closed loops
+    for is_corner in [true, false].iter() {
+        for vertex in (0..neighbours.len()).filter(|vertex| (neighbours[*vertex].len() != 2) == *is_corner) {
+            for next in neighbours[vertex].clone() {
+                if !visited.contains(&(vertex.min(next), vertex.max(next))) {
+                    walk(vertex, next, &mut visited);
+                }
+            }
+        }
+    }
+    polylines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_model_cube()
+    {
+        // The model of the stitching example is a cube where the vertices of each side are separate
+        let source = include_str!("../examples/stitching_data/model.obj");
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for line in source.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => positions.extend(words.map(|word| word.parse::<f64>().unwrap())),
+                Some("f") => indices.extend(words.map(|word| word.split('/').next().unwrap().parse::<u32>().unwrap() - 1)),
+                _ => {}
+            }
+        }
+        let mut mesh = self::mesh(positions, indices);
+        crate::welding::weld(&mut mesh, crate::welding::Tolerance::Relative(1.0e-5)).unwrap();
+        assert_eq!(mesh.no_vertices(), 8);
+
+        let features = feature_edges(&mesh, DEFAULT_ANGLE_THRESHOLD);
+        assert_eq!(features.sharp_edges.len(), 12);
+        assert_eq!(features.boundary_edges.len(), 0);
+        assert_eq!(features.non_manifold_edges.len(), 0);
+        let polylines = features.polylines();
+        assert_eq!(polylines.len(), 12);
+        assert!(polylines.iter().all(|polyline| polyline.len() == 2));
+    }
+
+    #[test]
+    fn test_polylines()
+    {
+        // Each edge of the cube is split in two, so the sharp edges form 12 polylines between the corners
+        let mesh = cube(2);
+        let features = feature_edges(&mesh, DEFAULT_ANGLE_THRESHOLD);
+        assert_eq!(features.no_edges(), 24);
+        let polylines = features.polylines();
+        assert_eq!(polylines.len(), 12);
+        for polyline in polylines.iter() {
+            let (p0, p1, p2) = (mesh.vertex_position(polyline[0]), mesh.vertex_position(polyline[1]), mesh.vertex_position(polyline[2]));
+            assert_eq!(polyline.len(), 3);
+            assert!((p0 + p2 - 2.0 * p1).magnitude() < 1.0e-9);
+            assert!((p0.distance(p2) - 1.0).abs() < 1.0e-9);
+        }
+
+        // The boundary of a box without a lid is a closed loop, which does not end at the corners of the square
+        let positions = mesh.positions_buffer();
+        let indices: Vec<u32> = mesh.indices_buffer().chunks(3).filter(|triangle| triangle.iter().any(|index| positions[3 * *index as usize + 2] < 1.0))
+            .flat_map(|triangle| triangle.to_vec()).collect();
+        let open = self::mesh(positions, indices);
+        let features = feature_edges(&open, std::f64::consts::PI);
+        assert_eq!((features.sharp_edges.len(), features.boundary_edges.len()), (0, 8));
+        let polylines = features.polylines();
+        assert_eq!(polylines.len(), 1);
+        assert_eq!(polylines[0].len(), 9);
+        assert_eq!(polylines[0][0], polylines[0][8]);
+    }
+
+    #[test]
+    fn test_feature_angle()
+    {
+        // The sharp edges of the cube are kept, so smoothing only moves the vertices inside the sides, which are already flat
+        let mut mesh = cube(4);
+        let positions: Vec<Vec3> = mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)).collect();
+        crate::smoothing::smooth(&mut mesh, &crate::smoothing::SmoothingOptions {feature_angle: Some(DEFAULT_ANGLE_THRESHOLD), ..Default::default()});
+        for (vertex_id, p) in mesh.vertex_iter().zip(positions.iter()) {
+            assert!(mesh.vertex_position(vertex_id).distance(*p) < 1.0e-9);
+        }
+
+        let mut remeshed = cube(4);
+        crate::remeshing::remesh(&mut remeshed, &crate::remeshing::RemeshingOptions {target_edge_length: crate::welding::Tolerance::Absolute(0.2),
+            feature_angle: Some(DEFAULT_ANGLE_THRESHOLD), ..Default::default()}).unwrap();
+        assert!((volume(&remeshed) - 1.0).abs() < 1.0e-9);
+        assert_eq!(feature_edges(&remeshed, DEFAULT_ANGLE_THRESHOLD).polylines().len(), 12);
+
+        let mut rounded = cube(4);
+        crate::remeshing::remesh(&mut rounded, &crate::remeshing::RemeshingOptions {target_edge_length: crate::welding::Tolerance::Absolute(0.2),
+            ..Default::default()}).unwrap();
+        assert!(volume(&rounded) < 1.0 - 1.0e-3);
+    }
+}
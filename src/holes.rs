@@ -0,0 +1,467 @@
+//! Detection and filling of holes in open meshes.
+//!
+//! Small holes are filled with a flat triangulation which minimizes the area. Larger holes are in addition refined,
+//! so the new faces have the same size as the faces around the hole, and faired, ie. the new vertices are placed so the bi-Laplacians with cotangent weights
+//! vanish, using the two rings of vertices around the hole as boundary conditions. This gives a smooth filling which meets the surrounding surface
+//! without a kink and continues its curvature, so for example a hole in a sphere is filled with an approximately spherical cap.
+
+use tri_mesh::prelude::*;
+use crate::editable_mesh::EditableMesh;
+use crate::indexed_mesh::IndexedMesh;
+use std::collections::{HashMap, HashSet};
+
+/// Holes with more boundary edges are triangulated by a fan from the center instead of by the minimum area triangulation, which takes cubic time.
+const MAX_MINIMUM_AREA_HOLE_SIZE: usize = 64;
+const MAX_REFINEMENT_PASSES: usize = 32;
+const NO_FAIRING_PASSES: usize = 3;
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// Options for the filling of holes, see [fill_holes](fn.fill_holes.html).
+///
+#[derive(Debug, Clone)]
+pub struct HoleFillingOptions {
+    /// Holes with more boundary edges than this are not filled.
+    pub max_hole_size: usize,
+    /// Holes with more boundary edges than this are refined and faired, smaller holes are only filled with a flat triangulation.
+    pub fair_threshold: usize,
+    /// The maximum number of iterations used to solve for the positions of the vertices in a faired hole.
+    pub no_fairing_iterations: usize
+}
+
+impl Default for HoleFillingOptions {
+    fn default() -> Self {
+        HoleFillingOptions {max_hole_size: 500, fair_threshold: 10, no_fairing_iterations: 200}
+    }
+}
+
+///
+/// The changes made to a mesh by [fill_holes](fn.fill_holes.html).
+///
+#[derive(Debug, Clone, Default)]
+pub struct HoleFillingReport {
+    pub no_holes: usize,
+    pub no_filled_holes: usize,
+    /// The number of filled holes which are also refined and faired.
+    pub no_faired_holes: usize,
+    pub no_added_faces: usize,
+    pub no_added_vertices: usize
+}
+
+///
+/// Returns the closed loops of boundary edges, ie. the holes in the mesh.
+/// Each loop is ordered so that a face with three consecutive vertices of the loop has the same orientation as the faces next to the hole.
+///
+pub fn boundary_loops(mesh: &Mesh) -> Vec<Vec<VertexID>>
+{
+    let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+    boundary_loops_indexed(&IndexedMesh::new(mesh)).iter()
+        .map(|hole| hole.iter().map(|index| vertex_ids[*index]).collect())
+        .collect()
+}
+
+///
+/// Fills the holes in the mesh with at most [max_hole_size](struct.HoleFillingOptions.html#structfield.max_hole_size) boundary edges.
+/// Holes with more than [fair_threshold](struct.HoleFillingOptions.html#structfield.fair_threshold) boundary edges are refined to match
+/// the length of the boundary edges and the new vertices are placed so the filling is smooth, ie. so the bi-Laplacians at the new vertices vanish.
+/// The mesh is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn fill_holes(mesh: &mut Mesh, options: &HoleFillingOptions) -> Result<HoleFillingReport, Error>
+{
+    let mut indexed = IndexedMesh::new(mesh);
+    let report = fill_holes_indexed(&mut indexed, options);
+    if report.no_filled_holes > 0 {
+        *mesh = indexed.to_mesh()?;
+    }
+    Ok(report)
+}
+
+pub(crate) fn fill_holes_indexed(mesh: &mut IndexedMesh, options: &HoleFillingOptions) -> HoleFillingReport
+{
+    let holes = boundary_loops_indexed(mesh);
+    let mut report = HoleFillingReport {no_holes: holes.len(), ..Default::default()};
+    let no_triangles = mesh.triangles.len();
+    let no_vertices = mesh.positions.len();
+    let mut faired_vertices = Vec::new();
+    for hole in holes {
+        if hole.len() < 3 || hole.len() > options.max_hole_size {
+            continue;
+        }
+        let first_triangle = mesh.triangles.len();
+        let first_vertex = mesh.positions.len();
+        triangulate(mesh, &hole);
+        if hole.len() > options.fair_threshold {
+            let target_length = (0..hole.len()).map(|i| mesh.positions[hole[i]].distance(mesh.positions[hole[(i + 1) % hole.len()]])).sum::<f64>()
+                / hole.len() as f64;
+            refine(mesh, &hole, first_triangle, target_length);
+            faired_vertices.extend(first_vertex..mesh.positions.len());
+            report.no_faired_holes += 1;
+        }
+        report.no_filled_holes += 1;
+    }
+    fair(mesh, &faired_vertices, options.no_fairing_iterations);
+    report.no_added_faces = mesh.triangles.len() - no_triangles;
+    report.no_added_vertices = mesh.positions.len() - no_vertices;
+    report
+}
+
+///
+/// Returns the closed loops of boundary edges, ie. edges with only one triangle, traversed in the opposite direction of the triangles.
+///
+pub(crate) fn boundary_loops_indexed(mesh: &IndexedMesh) -> Vec<Vec<usize>>
+{
+    let mut next: HashMap<usize, Vec<usize>> = HashMap::new();
+    for ((a, b), triangles) in mesh.edge_triangles().iter() {
+        if triangles.len() == 1 {
+            let (from, to) = if triangles[0].1 { (*b, *a) } else { (*a, *b) };
+            next.entry(from).or_insert(Vec::new()).push(to);
+        }
+    }
+
+    let mut holes = Vec::new();
+    let mut starts: Vec<usize> = next.keys().cloned().collect();
+    starts.sort();
+    for start in starts {
+        while next.get(&start).map(|to| to.len() > 0).unwrap_or(false) {
+            let mut hole = Vec::new();
+            let mut current = start;
+            let mut is_closed = false;
+            while let Some(to) = next.get_mut(&current).and_then(|to| to.pop()) {
+                hole.push(current);
+                if to == start {
+                    is_closed = true;
+                    break;
+                }
+                current = to;
+            }
+            if is_closed {
+                holes.push(hole);
+            }
+        }
+    }
+    holes
+}
+
+/// Adds triangles which fill the hole. Uses the triangulation with minimum area or, for large holes, a fan from a new vertex at the center.
+fn triangulate(mesh: &mut IndexedMesh, hole: &Vec<usize>)
+{
+    let n = hole.len();
+    if n > MAX_MINIMUM_AREA_HOLE_SIZE {
+        let center = mesh.positions.len();
+        mesh.positions.push(hole.iter().fold(vec3(0.0, 0.0, 0.0), |sum, index| sum + mesh.positions[*index]) / n as f64);
+        for i in 0..n {
+            mesh.triangles.push([hole[i], hole[(i + 1) % n], center]);
+        }
+        return;
+    }
+
+    let area = |i: usize, m: usize, k: usize| {
+        let (p0, p1, p2) = (mesh.positions[hole[i]], mesh.positions[hole[m]], mesh.positions[hole[k]]);
+        0.5 * (p1 - p0).cross(p2 - p0).magnitude()
+    };
+    let mut weights = vec![vec![0.0; n]; n];
+    let mut splits = vec![vec![0; n]; n];
+    for distance in 2..n {
+        for i in 0..n - distance {
+            let k = i + distance;
+            let (m, weight) = (i + 1..k).map(|m| (m, weights[i][m] + weights[m][k] + area(i, m, k)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap()).unwrap();
+            weights[i][k] = weight;
+            splits[i][k] = m;
+        }
+    }
+
+    let mut stack = vec![(0, n - 1)];
+    while let Some((i, k)) = stack.pop() {
+        if k - i < 2 {
+            continue;
+        }
+        let m = splits[i][k];
+        mesh.triangles.push([hole[i], hole[m], hole[k]]);
+        stack.push((i, m));
+        stack.push((m, k));
+    }
+}
+
+/// Splits the longest edges of the triangles filling the hole, which are not on the boundary of the hole, until they are shorter than 4/3 of the target length
+/// and flips edges to improve the shape of the triangles.
+fn refine(mesh: &mut IndexedMesh, hole: &Vec<usize>, first_triangle: usize, target_length: f64)
+{
+    let boundary: HashSet<(usize, usize)> = (0..hole.len()).map(|i| {
+        let (a, b) = (hole[i], hole[(i + 1) % hole.len()]);
+        (a.min(b), a.max(b))
+    }).collect();
+    let max_length = 4.0 / 3.0 * target_length;
+
+    for _ in 0..MAX_REFINEMENT_PASSES {
+        let edges = fill_edges(mesh, first_triangle);
+        let mut candidates: Vec<(f64, (usize, usize), Vec<usize>)> = edges.into_iter()
+            .filter(|(edge, triangles)| triangles.len() == 2 && !boundary.contains(edge))
+            .map(|((a, b), triangles)| (mesh.positions[a].distance(mesh.positions[b]), (a, b), triangles))
+            .filter(|(length, _, _)| *length > max_length)
+            .collect();
+        if candidates.len() == 0 {
+            break;
+        }
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        let mut is_touched = HashSet::new();
+        for (_, (a, b), triangles) in candidates {
+            if triangles.iter().any(|triangle| is_touched.contains(triangle)) {
+                continue;
+            }
+            let middle = mesh.positions.len();
+            mesh.positions.push(0.5 * (mesh.positions[a] + mesh.positions[b]));
+            for triangle in triangles {
+                let indices = mesh.triangles[triangle];
+                let i = (0..3).find(|i| {
+                    let (x, y) = (indices[*i], indices[(*i + 1) % 3]);
+                    (x == a && y == b) || (x == b && y == a)
+                }).unwrap();
+                let (x, y, z) = (indices[i], indices[(i + 1) % 3], indices[(i + 2) % 3]);
+                mesh.triangles[triangle] = [x, middle, z];
+                is_touched.insert(triangle);
+                is_touched.insert(mesh.triangles.len());
+                mesh.triangles.push([middle, y, z]);
+            }
+        }
+        relax(mesh, &boundary, first_triangle);
+    }
+}
+
+/// Flips the edges of the triangles filling the hole, which are not on the boundary of the hole, when the sum of the opposite angles exceeds π.
+fn relax(mesh: &mut IndexedMesh, boundary: &HashSet<(usize, usize)>, first_triangle: usize)
+{
+    let mut existing_edges: HashSet<(usize, usize)> = mesh.edge_triangles().keys().cloned().collect();
+    let edges = fill_edges(mesh, first_triangle);
+    let mut is_touched = HashSet::new();
+    for ((a, b), triangles) in edges {
+        if triangles.len() != 2 || boundary.contains(&(a, b)) || triangles.iter().any(|triangle| is_touched.contains(triangle)) {
+            continue;
+        }
+        let opposite = |triangle: usize| {
+            let indices = mesh.triangles[triangle];
+            let i = (0..3).find(|i| indices[*i] != a && indices[*i] != b).unwrap();
+            (indices[i], indices[(i + 1) % 3])
+        };
+        let (c, c_next) = opposite(triangles[0]);
+        let (d, _) = opposite(triangles[1]);
+        if c == d || existing_edges.contains(&(c.min(d), c.max(d))) {
+            continue;
+        }
+        let angle = |p: usize| (mesh.positions[a] - mesh.positions[p]).angle(mesh.positions[b] - mesh.positions[p]).0;
+        if angle(c) + angle(d) <= std::f64::consts::PI {
+            continue;
+        }
+        // The first triangle is (c, x, y) where x is the vertex after c, so the edge is traversed from x to y in the first triangle
+        let (x, y) = (c_next, if c_next == a { b } else { a });
+        mesh.triangles[triangles[0]] = [c, x, d];
+        mesh.triangles[triangles[1]] = [d, y, c];
+        existing_edges.remove(&(a, b));
+        existing_edges.insert((c.min(d), c.max(d)));
+        is_touched.insert(triangles[0]);
+        is_touched.insert(triangles[1]);
+    }
+}
+
+/// Returns the triangles, starting from the given triangle, next to each edge of those triangles.
+fn fill_edges(mesh: &IndexedMesh, first_triangle: usize) -> HashMap<(usize, usize), Vec<usize>>
+{
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for triangle in first_triangle..mesh.triangles.len() {
+        let indices = mesh.triangles[triangle];
+        for i in 0..3 {
+            let (a, b) = (indices[i], indices[(i + 1) % 3]);
+            edges.entry((a.min(b), a.max(b))).or_insert(Vec::new()).push(triangle);
+        }
+    }
+    edges
+}
+
+/// Moves the given vertices so the sum of the squared bi-Laplacians at them is minimized, ie. zero, while the other vertices are fixed.
+/// The bi-Laplacian at a vertex depends on the vertices up to two edges away, so the two rings of fixed vertices around a hole give the boundary conditions,
+/// which makes the filling meet the surrounding surface with the same tangent plane and continue its curvature.
+/// The Laplacian uses cotangent weights, which depend on the shape of the triangles, so they are computed again from the faired positions a few times.
+fn fair(mesh: &mut IndexedMesh, vertices: &Vec<usize>, max_no_iterations: usize)
+{
+    if vertices.len() == 0 {
+        return;
+    }
+    let columns: HashMap<usize, usize> = vertices.iter().enumerate().map(|(column, vertex)| (*vertex, column)).collect();
+    for _ in 0..NO_FAIRING_PASSES {
+        let editable = EditableMesh::new(mesh, &HashSet::new(), false);
+        let laplacians: HashMap<usize, (Vec<(usize, f64)>, f64)> = vertices.iter()
+            .flat_map(|vertex| editable.neighbours(*vertex).into_iter().chain(std::iter::once(*vertex)))
+            .collect::<HashSet<usize>>().into_iter()
+            .map(|vertex| (vertex, cotangent_laplacian(&editable, vertex)))
+            .collect();
+
+        // The Laplacian at each unknown vertex and its neighbours as a linear function of the unknown positions plus a constant from the fixed positions
+        let linear_laplacians: HashMap<usize, (HashMap<usize, f64>, Vec3)> = laplacians.iter().map(|(vertex, (laplacian, _))| {
+            let mut coefficients = HashMap::new();
+            let mut constant = vec3(0.0, 0.0, 0.0);
+            for (neighbour, coefficient) in laplacian.iter() {
+                match columns.get(neighbour) {
+                    Some(column) => *coefficients.entry(*column).or_insert(0.0) += coefficient,
+                    None => constant += mesh.positions[*neighbour] * *coefficient
+                }
+            }
+            (*vertex, (coefficients, constant))
+        }).collect();
+
+        // The bi-Laplacian at each unknown vertex, ie. the Laplacian of the Laplacians, multiplied by the area of the vertex to make the matrix symmetric
+        let rows: Vec<(Vec<(usize, f64)>, Vec3)> = vertices.iter().map(|vertex| {
+            let (laplacian, area) = &laplacians[vertex];
+            let mut coefficients = HashMap::new();
+            let mut constant = vec3(0.0, 0.0, 0.0);
+            for (neighbour, coefficient) in laplacian.iter() {
+                let (neighbour_coefficients, neighbour_constant) = &linear_laplacians[neighbour];
+                for (column, value) in neighbour_coefficients.iter() {
+                    *coefficients.entry(*column).or_insert(0.0) += area * coefficient * value;
+                }
+                constant += neighbour_constant * (area * coefficient);
+            }
+            let mut coefficients: Vec<(usize, f64)> = coefficients.into_iter().collect();
+            coefficients.sort_by_key(|(column, _)| *column);
+            (coefficients, constant)
+        }).collect();
+
+        for coordinate in 0..3 {
+            let b: Vec<f64> = rows.iter().map(|(_, constant)| -constant[coordinate]).collect();
+            let x: Vec<f64> = vertices.iter().map(|vertex| mesh.positions[*vertex][coordinate]).collect();
+            let x = conjugate_gradient(&rows, &b, x, max_no_iterations);
+            for (vertex, value) in vertices.iter().zip(x.iter()) {
+                mesh.positions[*vertex][coordinate] = *value;
+            }
+        }
+    }
+}
+
+/// Returns the Laplacian at the vertex as a list of vertices and coefficients, including the vertex itself, together with the area of the vertex,
+/// which is a third of the area of the triangles around it. The coefficient of a neighbour is its cotangent weight divided by twice the area.
+fn cotangent_laplacian(mesh: &EditableMesh, vertex: usize) -> (Vec<(usize, f64)>, f64)
+{
+    let area = mesh.vertex_triangles[vertex].iter().map(|triangle| {
+        let (p0, p1, p2) = mesh.triangle_positions(*triangle);
+        (p1 - p0).cross(p2 - p0).magnitude() / 6.0
+    }).sum::<f64>().max(std::f64::MIN_POSITIVE);
+    let mut neighbours: Vec<usize> = mesh.neighbours(vertex).into_iter().collect();
+    neighbours.sort();
+    let mut laplacian: Vec<(usize, f64)> = neighbours.into_iter().map(|neighbour| (neighbour, mesh.cotangent_weight(vertex, neighbour) / (2.0 * area))).collect();
+    let sum: f64 = laplacian.iter().map(|(_, coefficient)| coefficient).sum();
+    laplacian.push((vertex, -sum));
+    (laplacian, area)
+}
+
+/// Solves `Ax = b` where the rows of the sparse, symmetric and positive definite matrix `A` are given as lists of column indices and coefficients,
+/// using the conjugate gradient method.
+fn conjugate_gradient(rows: &Vec<(Vec<(usize, f64)>, Vec3)>, b: &Vec<f64>, mut x: Vec<f64>, max_no_iterations: usize) -> Vec<f64>
+{
+    let multiply = |v: &Vec<f64>| -> Vec<f64> {
+        rows.iter().map(|(coefficients, _)| coefficients.iter().map(|(column, coefficient)| coefficient * v[*column]).sum()).collect()
+    };
+    let dot = |a: &Vec<f64>, b: &Vec<f64>| -> f64 { a.iter().zip(b.iter()).map(|(a, b)| a * b).sum() };
+
+    let mut r: Vec<f64> = multiply(&x).iter().zip(b.iter()).map(|(ax, b)| b - ax).collect();
+    let mut p = r.clone();
+    let mut gamma = dot(&r, &r);
+    let tolerance = 1.0e-24 * gamma.max(dot(b, b)).max(std::f64::MIN_POSITIVE);
+    for _ in 0..max_no_iterations {
+        if gamma <= tolerance {
+            break;
+        }
+        let q = multiply(&p);
+        let alpha = gamma / dot(&p, &q);
+        for i in 0..x.len() { x[i] += alpha * p[i]; }
+        for i in 0..r.len() { r[i] -= alpha * q[i]; }
+        let new_gamma = dot(&r, &r);
+        let beta = new_gamma / gamma;
+        gamma = new_gamma;
+        for i in 0..p.len() { p[i] = r[i] + beta * p[i]; }
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::validation::validate;
+
+    /// Returns a sphere with radius 1 and `n` rings where the faces with all corners above the given height are removed.
+    fn open_sphere(n: usize, height: f64) -> Mesh
+    {
+        let sphere = sphere(1.0, n);
+        let indices = sphere.indices_buffer();
+        let positions = sphere.positions_buffer();
+        let z = |index: u32| positions[3 * index as usize + 2];
+        let kept = indices.chunks(3).filter(|triangle| triangle.iter().any(|index| z(*index) < height)).flat_map(|triangle| triangle.to_vec()).collect();
+        mesh(positions, kept)
+    }
+
+    #[test]
+    fn test_boundary_loops()
+    {
+        let cube = cube(2);
+        let mut mesh = mesh(cube.positions_buffer(), cube.indices_buffer()[6..].to_vec());
+        let loops = boundary_loops(&mesh);
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].len(), 4);
+
+        // The removed faces are on the side x = 0 and a face through three consecutive vertices of the loop has the same orientation as them
+        let (v0, v1, v2) = (loops[0][0], loops[0][1], loops[0][2]);
+        let normal = (mesh.vertex_position(v1) - mesh.vertex_position(v0)).cross(mesh.vertex_position(v2) - mesh.vertex_position(v1));
+        let report = fill_holes(&mut mesh, &HoleFillingOptions::default()).unwrap();
+        assert_eq!((report.no_holes, report.no_filled_holes, report.no_faired_holes, report.no_added_faces, report.no_added_vertices), (1, 1, 0, 2, 0));
+        assert!(normal.x < 0.0 && normal.y.abs() < 1.0e-12 && normal.z.abs() < 1.0e-12);
+        assert!(validate(&mesh).is_valid());
+        assert!((volume(&mesh) - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn test_flat_filling()
+    {
+        // The hole at the top of the sphere has 16 boundary edges
+        let mut mesh = open_sphere(8, 0.8);
+        let options = HoleFillingOptions {fair_threshold: 16, ..Default::default()};
+        let report = fill_holes(&mut mesh, &options).unwrap();
+        assert_eq!((report.no_filled_holes, report.no_faired_holes, report.no_added_faces, report.no_added_vertices), (1, 0, 14, 0));
+        assert!(validate(&mesh).is_valid());
+    }
+
+    #[test]
+    fn test_faired_filling()
+    {
+        // The hole is a cap of height 1 - cos(3π/16) ≈ 0.17 and the filling continues the curvature of the sphere, so it is much closer to the sphere than a flat filling
+        let mut mesh = open_sphere(16, 0.8);
+        let original: Vec<Vec3> = mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)).collect();
+        let report = fill_holes(&mut mesh, &HoleFillingOptions::default()).unwrap();
+        assert_eq!((report.no_filled_holes, report.no_faired_holes), (1, 1));
+        assert!(report.no_added_vertices > 0);
+        assert!(validate(&mesh).is_valid());
+
+        let added: Vec<Vec3> = mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)).filter(|p| !original.contains(p)).collect();
+        assert_eq!(added.len(), report.no_added_vertices);
+        let max_error = added.iter().map(|p| (p.magnitude() - 1.0).abs()).fold(0.0, f64::max);
+        assert!(max_error < 0.02, "{}", max_error);
+    }
+
+    #[test]
+    fn test_max_hole_size()
+    {
+        let mut mesh = open_sphere(8, 0.8);
+        let report = fill_holes(&mut mesh, &HoleFillingOptions {max_hole_size: 15, ..Default::default()}).unwrap();
+        assert_eq!((report.no_holes, report.no_filled_holes), (1, 0));
+        assert!(!validate(&mesh).is_closed());
+    }
+}
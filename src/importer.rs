@@ -0,0 +1,761 @@
+use tri_mesh::prelude::*;
+use tri_mesh::MeshBuilder;
+use crate::exporter::{Unit, GpmAttribute, GpmAttributeLocation, GpmAttributeKind};
+use crate::compression::{self, Compression};
+use std::collections::HashMap;
+use std::io::Read;
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    Zip(zip::result::ZipError),
+    MeshBuilder(tri_mesh::mesh_builder::Error),
+    Parse {message: String},
+    FileTypeNotSupported {message: String},
+    ExtensionNotSpecified {message: String},
+    FileNameNotSpecified {message: String}
+}
+
+impl From<std::io::Error> for Error {
+    fn from(other: std::io::Error) -> Self {
+        Error::IO(other)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(other: zip::result::ZipError) -> Self {
+        Error::Zip(other)
+    }
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// Loads the meshes in the file at the given path. The file type is deduced from the extension.
+/// An OBJ file gives one mesh for each object or group, see [parse_obj](fn.parse_obj.html), and a 3MF file gives one mesh for each build item.
+/// The unit of a 3MF file is discarded, use [load_3mf](fn.load_3mf.html) to get it.
+///
+pub fn load(path: &str) -> Result<Vec<Mesh>, Error>
+{
+    let path_split: Vec<&str> = path.split('/').collect();
+    if path_split.len() == 0
+    {
+        return Err(Error::FileNameNotSpecified {message: format!("Filename is not specified!")})
+    }
+    let filename = path_split.last().unwrap();
+    let splitted: Vec<&str> = filename.split('.').collect();
+    let compression = Compression::from_extension(splitted.last().unwrap());
+    let no_extensions = if compression == Compression::None { 1 } else { 2 };
+    if splitted.len() <= no_extensions
+    {
+        return Err(Error::ExtensionNotSpecified {message: format!("Extension for file {} is not specified!", splitted[0])})
+    }
+    let extension = splitted[splitted.len() - no_extensions];
+
+    if extension == "obj" {
+        let objects = parse_obj(&read_text(path, compression)?)?;
+        Ok(objects.into_iter().map(|object| object.mesh).collect())
+    }
+    else if extension == "3mf" {
+        let (meshes, _) = parse_3mf(&read_data(path, compression)?)?;
+        Ok(meshes)
+    }
+    else if extension == "gpm" {
+        let (mesh, _) = parse_gpm(&read_data(path, compression)?)?;
+        Ok(vec![mesh])
+    }
+    else { Err(Error::FileTypeNotSupported {message: format!("Extension {} of file {} is not supported!", extension, splitted[0])}) }
+}
+
+///
+/// Loads the 3MF file at the given path and returns one mesh for each build item together with the unit of the coordinates, see [parse_3mf](fn.parse_3mf.html).
+/// The file is decompressed if the path has a compression extension, for example `model.3mf.gz`.
+///
+pub fn load_3mf(path: &str) -> Result<(Vec<Mesh>, Unit), Error>
+{
+    let compression = Compression::from_extension(path.split('.').last().unwrap());
+    parse_3mf(&read_data(path, compression)?)
+}
+
+///
+/// A mesh together with the name of the object or group it was loaded from.
+///
+#[derive(Debug, Clone)]
+pub struct NamedMesh {
+    pub name: String,
+    pub mesh: Mesh
+}
+
+///
+/// Loads the OBJ file at the given path and returns one named mesh for each object or group, see [parse_obj](fn.parse_obj.html).
+/// The file is decompressed if the path has a compression extension, for example `model.obj.gz`.
+///
+pub fn load_obj(path: &str) -> Result<Vec<NamedMesh>, Error>
+{
+    let compression = Compression::from_extension(path.split('.').last().unwrap());
+    parse_obj(&read_text(path, compression)?)
+}
+
+/// Reads the text in the file through a decompressing stream, see [compression::read](../compression/fn.read.html).
+fn read_text(path: &str, compression: Compression) -> Result<String, Error>
+{
+    let mut source = String::new();
+    compression::read(path, compression)?.read_to_string(&mut source).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidData => parse_error(format!("The file {} is not valid UTF-8!", path)),
+        _ => Error::IO(e)
+    })?;
+    Ok(source)
+}
+
+/// Reads the binary data in the file through a decompressing stream. The data is read into memory,
+/// since a 3MF file is a ZIP archive which is read in random order and a GPM file must be checked against the checksum at its end before it is parsed.
+fn read_data(path: &str, compression: Compression) -> Result<Vec<u8>, Error>
+{
+    let mut data = Vec::new();
+    compression::read(path, compression)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+///
+/// Parses the content of an OBJ file and returns one named mesh for each object (`o`).
+/// If an object contains groups (`g`), one mesh is returned for each group instead, named `object/group`,
+/// and the faces which are not in any group are returned in a mesh with the name of the object.
+/// Each mesh only contains the vertices used by its faces. Points and lines are ignored.
+///
+pub fn parse_obj(source: &str) -> Result<Vec<NamedMesh>, Error>
+{
+    let obj_set = wavefront_obj::obj::parse(source)
+        .map_err(|e| parse_error(format!("Invalid OBJ at line {}: {}", e.line_number, e.message)))?;
+
+    let mut meshes = Vec::new();
+    for object in obj_set.objects.iter() {
+        let mut part_names = Vec::new();
+        let mut parts: HashMap<String, Vec<usize>> = HashMap::new();
+        for geometry in object.geometry.iter() {
+            for shape in geometry.shapes.iter() {
+                if let wavefront_obj::obj::Primitive::Triangle(a, b, c) = shape.primitive {
+                    let name = if shape.groups.len() == 0 || shape.groups == ["default"] { object.name.clone() }
+                        else if object.name.len() == 0 { shape.groups.join(" ") }
+                        else { format!("{}/{}", object.name, shape.groups.join(" ")) };
+                    if !parts.contains_key(&name) {
+                        part_names.push(name.clone());
+                    }
+                    parts.entry(name).or_insert(Vec::new()).extend(&[a.0, b.0, c.0]);
+                }
+            }
+        }
+
+        for name in part_names {
+            let mut mapping = HashMap::new();
+            let mut positions = Vec::new();
+            let mut indices = Vec::new();
+            for vertex_index in parts[&name].iter() {
+                let vertex = object.vertices.get(*vertex_index).ok_or_else(
+                    || parse_error(format!("Face in object {} refers to vertex {} which does not exist!", object.name, vertex_index)))?;
+                let index = *mapping.entry(*vertex_index).or_insert_with(|| {
+                    positions.extend(&[vertex.x, vertex.y, vertex.z]);
+                    (positions.len() / 3 - 1) as u32
+                });
+                indices.push(index);
+            }
+            meshes.push(NamedMesh {name, mesh: MeshBuilder::new().with_positions(positions).with_indices(indices).build()?});
+        }
+    }
+    Ok(meshes)
+}
+
+///
+/// Parses the content of a file in the native binary GPM format and returns the mesh and the stored attributes,
+/// see [parse_as_gpm](../exporter/fn.parse_as_gpm.html).
+/// Returns an error if the magic number, the version or the checksum is wrong or if the content is truncated.
+/// Files of version 1, which do not store the kind of the attributes, are also supported; all attributes are then real.
+///
+pub fn parse_gpm(data: &[u8]) -> Result<(Mesh, Vec<GpmAttribute>), Error>
+{
+    if data.len() < 24 || &data[0..4] != crate::exporter::GPM_MAGIC
+    {
+        return Err(parse_error(format!("The data is not in the GPM format!")));
+    }
+    let (content, checksum) = data.split_at(data.len() - 4);
+    if crc32fast::hash(content) != u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]])
+    {
+        return Err(parse_error(format!("The checksum of the GPM data is wrong, the data is corrupted!")));
+    }
+
+    let mut reader = BinaryReader {data: content, position: 4};
+    let version = reader.u32()?;
+    if version == 0 || version > crate::exporter::GPM_VERSION
+    {
+        return Err(parse_error(format!("Version {} of the GPM format is not supported!", version)));
+    }
+    let no_vertices = reader.u32()? as usize;
+    let no_faces = reader.u32()? as usize;
+    let no_attributes = reader.u32()? as usize;
+
+    let positions = reader.f64s(no_vertices * 3)?;
+    let mut indices = Vec::with_capacity(no_faces * 3);
+    for _ in 0..no_faces * 3 {
+        let index = reader.u32()?;
+        if index as usize >= no_vertices
+        {
+            return Err(parse_error(format!("Index {} in GPM data refers to a vertex which does not exist!", index)));
+        }
+        indices.push(index);
+    }
+
+    let mut attributes = Vec::with_capacity(no_attributes);
+    for _ in 0..no_attributes {
+        let name_length = reader.u32()? as usize;
+        let name = String::from_utf8(reader.bytes(name_length)?.to_vec())
+            .map_err(|_| parse_error(format!("The name of an attribute in GPM data is not valid UTF-8!")))?;
+        let (location, no_elements) = match reader.bytes(1)?[0] {
+            0 => (GpmAttributeLocation::Vertex, no_vertices),
+            1 => (GpmAttributeLocation::Face, no_faces),
+            l => return Err(parse_error(format!("Attribute {} in GPM data has unknown location {}!", name, l)))
+        };
+        let kind = if version == 1 { GpmAttributeKind::Real } else {
+            match reader.bytes(1)?[0] {
+                0 => GpmAttributeKind::Real,
+                1 => GpmAttributeKind::Integer,
+                2 => GpmAttributeKind::Color,
+                k => return Err(parse_error(format!("Attribute {} in GPM data has unknown kind {}!", name, k)))
+            }
+        };
+        let no_components = reader.u32()? as usize;
+        let values = reader.f64s(no_elements * no_components)?;
+        attributes.push(GpmAttribute {name, location, kind, no_components, values});
+    }
+
+    if reader.position != content.len()
+    {
+        return Err(parse_error(format!("The GPM data contains unexpected trailing bytes!")));
+    }
+
+    let mesh = MeshBuilder::new().with_positions(positions).with_indices(indices).build()?;
+    Ok((mesh, attributes))
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> BinaryReader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], Error>
+    {
+        if self.data.len() - self.position < length
+        {
+            return Err(parse_error(format!("The GPM data is truncated!")));
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error>
+    {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f64s(&mut self, count: usize) -> Result<Vec<f64>, Error>
+    {
+        let bytes = self.bytes(count.checked_mul(8).ok_or_else(|| parse_error(format!("The GPM data is truncated!")))?)?;
+        Ok(bytes.chunks(8).map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])).collect())
+    }
+}
+
+///
+/// Parses the content of a 3MF file and returns one mesh for each build item together with the unit of the coordinates.
+/// The transformation of the build item and of any components are applied to the positions.
+/// If the file contains no build items, one mesh is returned for each object instead.
+///
+pub fn parse_3mf(data: &[u8]) -> Result<(Vec<Mesh>, Unit), Error>
+{
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))?;
+
+    let mut model_path = "3D/3dmodel.model".to_string();
+    if let Ok(mut file) = archive.by_name("_rels/.rels")
+    {
+        let mut source = String::new();
+        file.read_to_string(&mut source)?;
+        for tag in xml_tags(&source)? {
+            if tag.name == "Relationship" && tag.attribute("Type") == Some(crate::exporter::THREEMF_MODEL_RELATIONSHIP_TYPE)
+            {
+                if let Some(target) = tag.attribute("Target") {
+                    model_path = target.trim_start_matches('/').to_string();
+                }
+            }
+        }
+    }
+
+    let mut source = String::new();
+    archive.by_name(&model_path)?.read_to_string(&mut source)?;
+    parse_3mf_model(&source)
+}
+
+type Transform = [f64; 12];
+
+const IDENTITY: Transform = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0];
+
+#[derive(Default)]
+struct ThreemfObject {
+    positions: Vec<f64>,
+    indices: Vec<u32>,
+    components: Vec<(u32, Transform)>
+}
+
+fn parse_3mf_model(source: &str) -> Result<(Vec<Mesh>, Unit), Error>
+{
+    let mut unit = Unit::default();
+    let mut objects: HashMap<u32, ThreemfObject> = HashMap::new();
+    let mut object_ids = Vec::new();
+    let mut items = Vec::new();
+    let mut current_object = None;
+
+    for tag in xml_tags(source)? {
+        if tag.is_end {
+            if tag.name == "object" { current_object = None; }
+            continue;
+        }
+        match tag.name {
+            "model" => {
+                if let Some(name) = tag.attribute("unit") {
+                    unit = Unit::from_name(name).ok_or_else(|| parse_error(format!("Unit {} is not supported!", name)))?;
+                }
+            },
+            "object" => {
+                let id = tag.parse_attribute("id")?;
+                objects.insert(id, ThreemfObject::default());
+                object_ids.push(id);
+                current_object = if tag.is_empty { None } else { Some(id) };
+            },
+            "vertex" => {
+                let object = current_object.and_then(|id| objects.get_mut(&id)).ok_or_else(|| parse_error(format!("Vertex outside of an object!")))?;
+                object.positions.push(tag.parse_attribute("x")?);
+                object.positions.push(tag.parse_attribute("y")?);
+                object.positions.push(tag.parse_attribute("z")?);
+            },
+            "triangle" => {
+                let object = current_object.and_then(|id| objects.get_mut(&id)).ok_or_else(|| parse_error(format!("Triangle outside of an object!")))?;
+                object.indices.push(tag.parse_attribute("v1")?);
+                object.indices.push(tag.parse_attribute("v2")?);
+                object.indices.push(tag.parse_attribute("v3")?);
+            },
+            "component" => {
+                let object = current_object.and_then(|id| objects.get_mut(&id)).ok_or_else(|| parse_error(format!("Component outside of an object!")))?;
+                object.components.push((tag.parse_attribute("objectid")?, tag.transform()?));
+            },
+            "item" => {
+                items.push((tag.parse_attribute("objectid")?, tag.transform()?));
+            },
+            _ => {}
+        }
+    }
+
+    if items.len() == 0
+    {
+        items = object_ids.iter().map(|id| (*id, IDENTITY)).collect();
+    }
+
+    let mut meshes = Vec::new();
+    for (object_id, transform) in items {
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        append_object(&objects, object_id, &transform, 0, &mut positions, &mut indices)?;
+        if indices.len() > 0
+        {
+            meshes.push(MeshBuilder::new().with_positions(positions).with_indices(indices).build()?);
+        }
+    }
+    Ok((meshes, unit))
+}
+
+fn append_object(objects: &HashMap<u32, ThreemfObject>, object_id: u32, transform: &Transform, depth: usize,
+                 positions: &mut Vec<f64>, indices: &mut Vec<u32>) -> Result<(), Error>
+{
+    if depth > 32
+    {
+        return Err(parse_error(format!("Components of object {} are nested too deep or recursive!", object_id)));
+    }
+    let object = objects.get(&object_id).ok_or_else(|| parse_error(format!("Object {} is not defined!", object_id)))?;
+
+    let start = (positions.len() / 3) as u32;
+    let no_vertices = (object.positions.len() / 3) as u32;
+    for p in object.positions.chunks(3) {
+        positions.push(p[0] * transform[0] + p[1] * transform[3] + p[2] * transform[6] + transform[9]);
+        positions.push(p[0] * transform[1] + p[1] * transform[4] + p[2] * transform[7] + transform[10]);
+        positions.push(p[0] * transform[2] + p[1] * transform[5] + p[2] * transform[8] + transform[11]);
+    }
+    for index in object.indices.iter() {
+        if *index >= no_vertices
+        {
+            return Err(parse_error(format!("Triangle in object {} refers to vertex {} which does not exist!", object_id, index)));
+        }
+        indices.push(start + index);
+    }
+
+    for (component_id, component_transform) in object.components.iter() {
+        append_object(objects, *component_id, &compose(component_transform, transform), depth + 1, positions, indices)?;
+    }
+    Ok(())
+}
+
+/// Returns the transform which applies `first` and then `second` (3MF uses row vectors, so this is `first * second`).
+fn compose(first: &Transform, second: &Transform) -> Transform
+{
+    let mut result = [0.0; 12];
+    for row in 0..4 {
+        for col in 0..3 {
+            let mut value = if row == 3 { second[9 + col] } else { 0.0 };
+            for k in 0..3 {
+                value += first[row * 3 + k] * second[k * 3 + col];
+            }
+            result[row * 3 + col] = value;
+        }
+    }
+    result
+}
+
+fn parse_error(message: String) -> Error
+{
+    Error::Parse {message}
+}
+
+struct XmlTag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    is_end: bool,
+    is_empty: bool
+}
+
+impl<'a> XmlTag<'a> {
+    fn attribute(&self, name: &str) -> Option<&str>
+    {
+        self.attributes.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn parse_attribute<T: std::str::FromStr>(&self, name: &str) -> Result<T, Error>
+    {
+        let value = self.attribute(name).ok_or_else(|| parse_error(format!("Attribute {} is missing on {}!", name, self.name)))?;
+        value.trim().parse::<T>().map_err(|_| parse_error(format!("Attribute {} on {} has invalid value {}!", name, self.name, value)))
+    }
+
+    fn transform(&self) -> Result<Transform, Error>
+    {
+        match self.attribute("transform") {
+            Some(value) => {
+                let numbers = value.split_whitespace().map(|v| v.parse::<f64>()).collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| parse_error(format!("Invalid transform {} on {}!", value, self.name)))?;
+                if numbers.len() != 12
+                {
+                    return Err(parse_error(format!("Invalid transform {} on {}!", value, self.name)));
+                }
+                let mut transform = IDENTITY;
+                transform.copy_from_slice(&numbers);
+                Ok(transform)
+            },
+            None => Ok(IDENTITY)
+        }
+    }
+}
+
+/// Splits the source into tags, ignoring text content, comments, processing instructions and namespace prefixes.
+fn xml_tags(source: &str) -> Result<Vec<XmlTag<'_>>, Error>
+{
+    let mut tags = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let terminator = if rest.starts_with("<!--") { "-->" }
+            else if rest.starts_with("<![CDATA[") { "]]>" }
+            else if rest.starts_with("<?") { "?>" }
+            else { ">" };
+        let end = if terminator == ">" { tag_end(rest) } else { rest.find(terminator) }
+            .ok_or_else(|| parse_error(format!("Unterminated tag in XML!")))?;
+        let content = &rest[1..end];
+        rest = &rest[end + terminator.len()..];
+
+        if content.starts_with('!') || content.starts_with('?') { continue; }
+
+        let is_end = content.starts_with('/');
+        let is_empty = content.ends_with('/');
+        let content = content.trim_start_matches('/').trim_end_matches('/').trim();
+        let name_end = content.find(char::is_whitespace).unwrap_or(content.len());
+        tags.push(XmlTag {name: local_name(&content[..name_end]), attributes: xml_attributes(&content[name_end..])?, is_end, is_empty});
+    }
+    Ok(tags)
+}
+
+/// Returns the position of the `>` which ends the tag at the start of the source, skipping the `>` characters in quoted attribute values.
+fn tag_end(source: &str) -> Option<usize>
+{
+    let mut quote = None;
+    for (index, c) in source.char_indices() {
+        match (quote, c) {
+            (None, '>') => return Some(index),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the source into attributes, where the entity and character references in the values are replaced by the characters they refer to.
+fn xml_attributes(source: &str) -> Result<Vec<(&str, String)>, Error>
+{
+    let mut attributes = Vec::new();
+    let mut rest = source.trim_start();
+    while rest.len() > 0 {
+        let equal = rest.find('=').ok_or_else(|| parse_error(format!("Invalid attributes {} in XML!", source)))?;
+        let key = rest[..equal].trim();
+        let value = rest[equal + 1..].trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| parse_error(format!("Invalid attributes {} in XML!", source)))?;
+        let end = value[1..].find(quote).ok_or_else(|| parse_error(format!("Invalid attributes {} in XML!", source)))? + 1;
+        attributes.push((local_name(key), xml_unescape(&value[1..end])?));
+        rest = value[end + 1..].trim_start();
+    }
+    Ok(attributes)
+}
+
+fn xml_unescape(value: &str) -> Result<String, Error>
+{
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(';').ok_or_else(|| parse_error(format!("Unterminated reference in XML value {}!", value)))?;
+        let reference = &rest[1..end];
+        let character = match reference {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if reference.starts_with("#x") => u32::from_str_radix(&reference[2..], 16).ok().and_then(std::char::from_u32),
+            _ if reference.starts_with('#') => reference[1..].parse::<u32>().ok().and_then(std::char::from_u32),
+            _ => None
+        };
+        result.push(character.ok_or_else(|| parse_error(format!("Unknown reference &{}; in XML value {}!", reference, value)))?);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn local_name(name: &str) -> &str
+{
+    name.rsplit(':').next().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporter;
+    use crate::test_utils::*;
+
+    fn temp_path(filename: &str) -> String
+    {
+        std::env::temp_dir().join(format!("geo-proc-{}-{}", std::process::id(), filename)).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parse_obj_objects_and_groups()
+    {
+        let source = "o first\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 1 3 4\n\
+            o second\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 5 6 8\ng side\nf 6 7 8\nf 5 8 7\nl 5 6\n";
+        let meshes = parse_obj(source).unwrap();
+
+        let names: Vec<&str> = meshes.iter().map(|named| named.name.as_str()).collect();
+        assert_eq!(names, ["first", "second", "second/side"]);
+        let no_faces: Vec<usize> = meshes.iter().map(|named| named.mesh.no_faces()).collect();
+        assert_eq!(no_faces, [2, 1, 2]);
+        let no_vertices: Vec<usize> = meshes.iter().map(|named| named.mesh.no_vertices()).collect();
+        assert_eq!(no_vertices, [4, 3, 4]);
+
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn test_load_obj_gives_one_mesh_per_object()
+    {
+        let path = temp_path("objects.obj");
+        std::fs::write(&path, "o a\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\no b\nv 0 0 1\nv 1 0 1\nv 0 1 1\nf 4 5 6\n").unwrap();
+        let meshes = load(&path).unwrap();
+        let objects = load_obj(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(objects[1].name, "b");
+        assert_eq!(sorted_triangles(&meshes[1]), sorted_triangles(&objects[1].mesh));
+        assert_eq!(meshes[1].positions_buffer().iter().filter(|value| **value == 1.0).count(), 5);
+    }
+
+    #[test]
+    fn test_gpm_round_trip()
+    {
+        let sphere = sphere(1.0, 4);
+        let mut attributes = crate::attributes::Attributes::new();
+        for vertex_id in sphere.vertex_iter() {
+            let p = sphere.vertex_position(vertex_id);
+            attributes.vertex_layer_mut::<f64>("height").insert(vertex_id, p.z);
+            attributes.vertex_layer_mut::<Vec3>("normal").insert(vertex_id, p);
+        }
+        for (i, face_id) in sphere.face_iter().enumerate() {
+            attributes.face_layer_mut::<i32>("marker").insert(face_id, i as i32 % 3);
+        }
+        let path = temp_path("round-trip.gpm");
+        crate::exporter::save_with_attributes(&sphere, &attributes, &path).unwrap();
+        let (mesh, gpm_attributes) = parse_gpm(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mesh.positions_buffer(), sphere.positions_buffer());
+        assert_eq!(mesh.indices_buffer(), sphere.indices_buffer());
+        assert_eq!(gpm_attributes, attributes.gpm_attributes(&sphere));
+
+        let loaded = crate::attributes::Attributes::from_gpm_attributes(&mesh, &gpm_attributes);
+        for (vertex_id, loaded_id) in sphere.vertex_iter().zip(mesh.vertex_iter()) {
+            assert_eq!(loaded.vertex_layer::<f64>("height").unwrap()[&loaded_id], attributes.vertex_layer::<f64>("height").unwrap()[&vertex_id]);
+            assert_eq!(loaded.vertex_layer::<Vec3>("normal").unwrap()[&loaded_id], attributes.vertex_layer::<Vec3>("normal").unwrap()[&vertex_id]);
+        }
+        for (face_id, loaded_id) in sphere.face_iter().zip(mesh.face_iter()) {
+            assert_eq!(loaded.face_layer::<i32>("marker").unwrap()[&loaded_id], attributes.face_layer::<i32>("marker").unwrap()[&face_id]);
+        }
+    }
+
+    #[test]
+    fn test_gpm_invalid_data()
+    {
+        let cube = cube(1);
+        let attribute = GpmAttribute {name: "area".to_string(), location: GpmAttributeLocation::Face, kind: GpmAttributeKind::Real,
+            no_components: 1, values: vec![0.5; cube.no_faces()]};
+        let data = crate::exporter::parse_as_gpm(&cube, &[attribute.clone()]);
+        assert_eq!(parse_gpm(&data).unwrap().1, vec![attribute]);
+
+        let mut corrupted = data.clone();
+        corrupted[40] ^= 1;
+        assert!(parse_gpm(&corrupted).is_err());
+        assert!(parse_gpm(&data[..data.len() - 1]).is_err());
+        assert!(parse_gpm(b"not a GPM file at all, just some text").is_err());
+
+        // A newer version is rejected even with a valid checksum
+        let mut newer = data[..data.len() - 4].to_vec();
+        newer[4..8].copy_from_slice(&(crate::exporter::GPM_VERSION + 1).to_le_bytes());
+        let checksum = crc32fast::hash(&newer);
+        newer.extend_from_slice(&checksum.to_le_bytes());
+        assert!(parse_gpm(&newer).is_err());
+    }
+
+    #[test]
+    fn test_gpm_version_1()
+    {
+        let mut data = Vec::new();
+        data.extend_from_slice(crate::exporter::GPM_MAGIC);
+        for value in [1u32, 3, 1, 1].iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [0.0f64, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0u32, 1, 2].iter() {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(b"area");
+        data.push(1);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0.5f64.to_le_bytes());
+        let checksum = crc32fast::hash(&data);
+        data.extend_from_slice(&checksum.to_le_bytes());
+
+        let (mesh, attributes) = parse_gpm(&data).unwrap();
+        assert_eq!(mesh.no_faces(), 1);
+        assert_eq!(attributes, vec![GpmAttribute {name: "area".to_string(), location: GpmAttributeLocation::Face, kind: GpmAttributeKind::Real,
+            no_components: 1, values: vec![0.5]}]);
+    }
+
+    #[test]
+    fn test_3mf_round_trip()
+    {
+        let cube = cube(2);
+        let sphere = sphere(1.0, 4);
+        let path = temp_path("round-trip.3mf");
+        exporter::save_3mf(&[&cube, &sphere], Unit::Inch, &path).unwrap();
+        let (meshes, unit) = load_3mf(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(unit, Unit::Inch);
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(sorted_triangles(&meshes[0]), sorted_triangles(&cube));
+        assert_eq!(sorted_triangles(&meshes[1]), sorted_triangles(&sphere));
+    }
+
+    #[test]
+    fn test_3mf_escaped_attributes()
+    {
+        let model = format!("<?xml version=\"1.0\"?>
+<model unit=\"&#x6D;illimeter\" xmlns=\"{}\">
+ <resources>
+  <object id=\"1\" name=\"&lt;a &amp; b&gt;\" type=\"model\">
+   <mesh>
+    <vertices>
+     <vertex x=\"&#48;\" y=\"0\" z=\"0\"/>
+     <vertex x=\"1\" y=\"0\" z=\"0\"/>
+     <vertex x=\"0\" y=\"1\" z=\"0\"/>
+    </vertices>
+    <triangles>
+     <triangle v1=\"0\" v2=\"1\" v3=\"2\"/>
+    </triangles>
+   </mesh>
+  </object>
+ </resources>
+ <build>
+  <item objectid=\"1\"/>
+ </build>
+</model>", exporter::THREEMF_CORE_NAMESPACE);
+        let (meshes, unit) = parse_3mf_model(&model).unwrap();
+        assert_eq!(unit, Unit::Millimeter);
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].no_faces(), 1);
+
+        assert_eq!(xml_unescape("&quot;a&apos; &lt;&#x26;&#38;&gt;").unwrap(), "\"a' <&&>");
+        assert!(xml_unescape("&unknown;").is_err());
+        assert!(xml_unescape("a & b").is_err());
+    }
+
+    #[test]
+    fn test_3mf_relationships()
+    {
+        let data = exporter::parse_as_3mf(&[&cube(1)], Unit::Meter).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+        let mut source = String::new();
+        archive.by_name("_rels/.rels").unwrap().read_to_string(&mut source).unwrap();
+        let tags = xml_tags(&source).unwrap();
+        let relationship = tags.iter().find(|tag| tag.name == "Relationship").unwrap();
+        assert_eq!(relationship.attribute("Type"), Some(exporter::THREEMF_MODEL_RELATIONSHIP_TYPE));
+        assert_eq!(relationship.attribute("Target"), Some("/3D/3dmodel.model"));
+    }
+
+    #[test]
+    fn test_xml_tags_with_quoted_greater_than()
+    {
+        let tags = xml_tags("<a name=\"x > y\" other='>'><b value=\"/>\"/></a>").unwrap();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags[0].name, "a");
+        assert_eq!(tags[0].attribute("name"), Some("x > y"));
+        assert_eq!(tags[0].attribute("other"), Some(">"));
+        assert!(!tags[0].is_empty);
+        assert_eq!(tags[1].name, "b");
+        assert_eq!(tags[1].attribute("value"), Some("/>"));
+        assert!(tags[1].is_empty);
+        assert!(tags[2].is_end);
+        assert!(xml_tags("<a name=\"x >").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! An indexed triangle list which, unlike the half-edge mesh, can represent non-manifold and inconsistently oriented meshes.
+//! Used by the operations which change the connectivity of a mesh globally, the result is converted back into a mesh when done.
+
+use tri_mesh::prelude::*;
+use tri_mesh::MeshBuilder;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub(crate) struct IndexedMesh {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>
+}
+
+impl IndexedMesh {
+    ///
+    /// Creates an indexed mesh where vertex `i` and triangle `i` corresponds to the `i`'th vertex and face of the vertex and face iterators.
+    ///
+    pub fn new(mesh: &Mesh) -> Self
+    {
+        let indices = vertex_indices(mesh);
+        let positions = mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)).collect();
+        let triangles = mesh.face_iter().map(|face_id| {
+            let (v0, v1, v2) = mesh.face_vertices(face_id);
+            [indices[&v0], indices[&v1], indices[&v2]]
+        }).collect();
+        IndexedMesh {positions, triangles}
+    }
+
+    ///
+    /// Builds a mesh from the triangles. Vertices which are not part of any triangle are left out.
+    ///
+    pub fn to_mesh(&self) -> Result<Mesh, tri_mesh::mesh_builder::Error>
+    {
+        let mut compact = self.clone();
+        compact.remove_unused_vertices();
+        let positions = compact.positions.iter().flat_map(|p| vec![p.x, p.y, p.z]).collect();
+        let indices = compact.triangles.iter().flat_map(|triangle| triangle.iter().map(|i| *i as u32)).collect();
+        MeshBuilder::new().with_positions(positions).with_indices(indices).build()
+    }
+
+    ///
+    /// Builds a mesh from the triangles, see [to_mesh](#method.to_mesh), and returns the triangle each face of the mesh is built from.
+    /// The faces are matched with the triangles by their corner positions, since the mesh does not necessarily iterate the faces in the order of the triangles.
+    ///
+    pub fn to_mesh_with_triangles(&self) -> Result<(Mesh, HashMap<FaceID, usize>), tri_mesh::mesh_builder::Error>
+    {
+        let mesh = self.to_mesh()?;
+        let key = |p0: Vec3, p1: Vec3, p2: Vec3| {
+            let mut corners = [p0, p1, p2].iter().map(|p| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]).collect::<Vec<[u64; 3]>>();
+            corners.sort();
+            corners
+        };
+        let mut triangles: HashMap<Vec<[u64; 3]>, Vec<usize>> = HashMap::new();
+        for triangle in (0..self.triangles.len()).rev() {
+            let (p0, p1, p2) = self.triangle_positions(triangle);
+            triangles.entry(key(p0, p1, p2)).or_insert(Vec::new()).push(triangle);
+        }
+        let faces = mesh.face_iter().filter_map(|face_id| {
+            let (p0, p1, p2) = mesh.face_positions(face_id);
+            triangles.get_mut(&key(p0, p1, p2)).and_then(|candidates| candidates.pop()).map(|triangle| (face_id, triangle))
+        }).collect();
+        Ok((mesh, faces))
+    }
+
+    ///
+    /// Appends the vertices and triangles of the other mesh, so the triangle with index `i` in the other mesh gets the index `self.triangles.len() + i`.
+    ///
+    pub fn append(&mut self, other: &IndexedMesh)
+    {
+        let offset = self.positions.len();
+        self.positions.extend(other.positions.iter());
+        self.triangles.extend(other.triangles.iter().map(|[i0, i1, i2]| [i0 + offset, i1 + offset, i2 + offset]));
+    }
+
+    ///
+    /// Removes the vertices which are not part of any triangle and returns the number of removed vertices.
+    ///
+    pub fn remove_unused_vertices(&mut self) -> usize
+    {
+        let mut mapping = vec![None; self.positions.len()];
+        let mut positions = Vec::new();
+        for triangle in self.triangles.iter_mut() {
+            for index in triangle.iter_mut() {
+                if mapping[*index].is_none() {
+                    mapping[*index] = Some(positions.len());
+                    positions.push(self.positions[*index]);
+                }
+                *index = mapping[*index].unwrap();
+            }
+        }
+        let no_removed = self.positions.len() - positions.len();
+        self.positions = positions;
+        no_removed
+    }
+
+    ///
+    /// Merges each vertex into the first vertex within the given distance using a uniform grid and returns the number of merged vertices.
+    /// The merged vertices are left unused.
+    ///
+    pub fn merge_close_vertices(&mut self, distance: f64) -> usize
+    {
+        let cell_size = distance.max(1.0e-12 * self.diagonal()).max(std::f64::MIN_POSITIVE);
+        let cell = |p: &Vec3| ((p.x / cell_size).floor() as i64, (p.y / cell_size).floor() as i64, (p.z / cell_size).floor() as i64);
+        let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut mapping = Vec::with_capacity(self.positions.len());
+        let mut no_merged = 0;
+        for (index, p) in self.positions.iter().enumerate() {
+            let (x, y, z) = cell(p);
+            let mut representative = None;
+            'search: for i in x-1..x+2 {
+                for j in y-1..y+2 {
+                    for k in z-1..z+2 {
+                        if let Some(candidates) = grid.get(&(i, j, k)) {
+                            if let Some(candidate) = candidates.iter().find(|candidate| self.positions[**candidate].distance(*p) <= distance) {
+                                representative = Some(*candidate);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            match representative {
+                Some(candidate) => {
+                    mapping.push(candidate);
+                    no_merged += 1;
+                },
+                None => {
+                    grid.entry((x, y, z)).or_insert(Vec::new()).push(index);
+                    mapping.push(index);
+                }
+            }
+        }
+        for triangle in self.triangles.iter_mut() {
+            for index in triangle.iter_mut() {
+                *index = mapping[*index];
+            }
+        }
+        no_merged
+    }
+
+    pub fn triangle_positions(&self, triangle: usize) -> (Vec3, Vec3, Vec3)
+    {
+        let [i0, i1, i2] = self.triangles[triangle];
+        (self.positions[i0], self.positions[i1], self.positions[i2])
+    }
+
+    pub fn triangle_area(&self, triangle: usize) -> f64
+    {
+        let (p0, p1, p2) = self.triangle_positions(triangle);
+        0.5 * (p1 - p0).cross(p2 - p0).magnitude()
+    }
+
+    ///
+    /// Returns the length of the diagonal of the axis aligned bounding box of the vertices.
+    ///
+    pub fn diagonal(&self) -> f64
+    {
+        crate::ray_casting::diagonal(self.positions.iter().cloned())
+    }
+
+    ///
+    /// Returns the triangles next to each edge together with whether the triangle traverses the edge from the smallest to the largest index.
+    /// The edges are given by their end vertices, the smallest index first.
+    ///
+    pub fn edge_triangles(&self) -> HashMap<(usize, usize), Vec<(usize, bool)>>
+    {
+        let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+        for (triangle, [i0, i1, i2]) in self.triangles.iter().enumerate() {
+            for (a, b) in [(*i0, *i1), (*i1, *i2), (*i2, *i0)].iter() {
+                if a != b {
+                    edges.entry(((*a).min(*b), (*a).max(*b))).or_insert(Vec::new()).push((triangle, a < b));
+                }
+            }
+        }
+        edges
+    }
+
+    ///
+    /// Returns the triangles which each vertex is part of.
+    ///
+    pub fn vertex_triangles(&self) -> Vec<Vec<usize>>
+    {
+        let mut result = vec![Vec::new(); self.positions.len()];
+        for (triangle, indices) in self.triangles.iter().enumerate() {
+            for index in indices.iter() {
+                if !result[*index].contains(&triangle) {
+                    result[*index].push(triangle);
+                }
+            }
+        }
+        result
+    }
+
+    ///
+    /// Returns the connected components, ie. groups of triangles connected through manifold edges (edges with exactly two triangles).
+    ///
+    pub fn components(&self) -> Vec<Vec<usize>>
+    {
+        self.components_where(&|_, _| true)
+    }
+
+    ///
+    /// Returns the connected components, where two triangles sharing a manifold edge are only connected if the given function returns true.
+    ///
+    pub fn components_where(&self, is_connected: &dyn Fn(usize, usize) -> bool) -> Vec<Vec<usize>>
+    {
+        let edges = self.edge_triangles();
+        let mut component_of = vec![None; self.triangles.len()];
+        let mut components = Vec::new();
+        for start in 0..self.triangles.len() {
+            if component_of[start].is_some() {
+                continue;
+            }
+            let mut component = Vec::new();
+            let mut stack = vec![start];
+            component_of[start] = Some(components.len());
+            while let Some(triangle) = stack.pop() {
+                component.push(triangle);
+                for neighbour in self.manifold_neighbours(triangle, &edges) {
+                    if component_of[neighbour].is_none() && is_connected(triangle, neighbour) {
+                        component_of[neighbour] = Some(components.len());
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+
+    ///
+    /// Returns the triangles sharing a manifold edge with the given triangle.
+    ///
+    pub fn manifold_neighbours(&self, triangle: usize, edges: &HashMap<(usize, usize), Vec<(usize, bool)>>) -> Vec<usize>
+    {
+        let [i0, i1, i2] = self.triangles[triangle];
+        let mut result = Vec::new();
+        for (a, b) in [(i0, i1), (i1, i2), (i2, i0)].iter() {
+            if let Some(neighbours) = edges.get(&((*a).min(*b), (*a).max(*b))) {
+                if neighbours.len() == 2 {
+                    let neighbour = if neighbours[0].0 == triangle { neighbours[1].0 } else { neighbours[0].0 };
+                    if neighbour != triangle {
+                        result.push(neighbour);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+///
+/// Returns the index of each vertex in an indexed mesh created from the mesh, ie. the position of the vertex in the vertex iterator.
+///
+pub(crate) fn vertex_indices(mesh: &Mesh) -> HashMap<VertexID, usize>
+{
+    mesh.vertex_iter().enumerate().map(|(index, vertex_id)| (vertex_id, index)).collect()
+}
+
+///
+/// Returns the given edges of the mesh as edges of an indexed mesh created from the mesh, the smallest index first.
+///
+pub(crate) fn edge_indices(mesh: &Mesh, edges: &[(VertexID, VertexID)]) -> HashSet<(usize, usize)>
+{
+    let indices = vertex_indices(mesh);
+    edges.iter().filter_map(|(vertex_id0, vertex_id1)| {
+        let (i0, i1) = (*indices.get(vertex_id0)?, *indices.get(vertex_id1)?);
+        Some((i0.min(i1), i0.max(i1)))
+    }).collect()
+}
@@ -1,7 +1,30 @@
 //! Geometry processing algorithms working on [tri-mesh](https://github.com/asny/tri-mesh) triangle mesh data structure.
 //!
 
+pub mod attributes;
+pub mod compression;
+pub mod curvature;
+mod editable_mesh;
 pub mod exporter;
+pub mod features;
+pub mod holes;
+pub mod importer;
+mod indexed_mesh;
+pub mod orientation;
+pub mod ray_casting;
+pub mod remeshing;
+pub mod repair;
+pub mod self_intersection;
+pub mod simplification;
+pub mod smoothing;
+pub mod stitching;
+pub mod subdivision;
+#[cfg(test)]
+mod test_utils;
+pub mod validation;
+pub mod view_factors;
+pub mod visibility;
+pub mod welding;
 
 pub use tri_mesh::prelude as prelude;
 pub use tri_mesh;
@@ -0,0 +1,207 @@
+//! Consistent orientation of the faces of a mesh with the normals pointing outwards.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::IndexedMesh;
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// The changes made to a mesh by [orient](fn.orient.html).
+///
+#[derive(Debug, Clone, Default)]
+pub struct OrientationReport {
+    /// The number of connected components, ie. groups of faces connected through manifold edges.
+    pub no_components: usize,
+    /// The number of components which are flipped entirely to make the normals point outwards.
+    pub no_flipped_components: usize,
+    pub no_flipped_faces: usize,
+    /// The number of edges which are still oriented inconsistently, which happens if a component is not orientable, for example a Möbius strip.
+    pub no_inconsistent_edges: usize
+}
+
+///
+/// Flips the faces of the mesh so the orientation is consistent, ie. so neighbouring faces traverse their common edge in opposite directions,
+/// and so the normals point outwards. The orientation of the first face of each connected component is propagated to the rest of the component,
+/// then the components with a negative signed volume are flipped.
+/// The signed volume is computed relative to the center of the component, so it is only meaningful for closed or almost closed components.
+/// If no faces are flipped, the mesh is not changed, otherwise it is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn orient(mesh: &mut Mesh) -> Result<OrientationReport, Error>
+{
+    let mut indexed = IndexedMesh::new(mesh);
+    let report = orient_indexed(&mut indexed);
+    if report.no_flipped_faces > 0 {
+        *mesh = indexed.to_mesh()?;
+    }
+    Ok(report)
+}
+
+///
+/// Flips the triangles so the orientation is consistent across the manifold edges of each connected component
+/// and flips the components with a negative signed volume, so the normals point outwards.
+///
+pub(crate) fn orient_indexed(mesh: &mut IndexedMesh) -> OrientationReport
+{
+    let mut report = OrientationReport::default();
+    let edges = mesh.edge_triangles();
+    let mut is_flipped = vec![false; mesh.triangles.len()];
+    let mut is_visited = vec![false; mesh.triangles.len()];
+    for start in 0..mesh.triangles.len() {
+        if is_visited[start] {
+            continue;
+        }
+        is_visited[start] = true;
+        let mut stack = vec![start];
+        while let Some(triangle) = stack.pop() {
+            let [i0, i1, i2] = mesh.triangles[triangle];
+            for (a, b) in [(i0, i1), (i1, i2), (i2, i0)].iter() {
+                let neighbours = match edges.get(&((*a).min(*b), (*a).max(*b))) {
+                    Some(neighbours) if neighbours.len() == 2 => neighbours,
+                    _ => continue
+                };
+                let (neighbour, neighbour_direction) = if neighbours[0].0 == triangle { neighbours[1] } else { neighbours[0] };
+                if !is_visited[neighbour] {
+                    is_visited[neighbour] = true;
+                    is_flipped[neighbour] = neighbour_direction == ((a < b) != is_flipped[triangle]);
+                    stack.push(neighbour);
+                }
+            }
+        }
+    }
+    flip(mesh, &is_flipped);
+
+    let volume_tolerance = 1.0e-12 * mesh.diagonal().powi(3);
+    let components = mesh.components();
+    report.no_components = components.len();
+    for component in components {
+        let center = component.iter().fold(vec3(0.0, 0.0, 0.0), |sum, triangle| {
+            let (p0, p1, p2) = mesh.triangle_positions(*triangle);
+            sum + (p0 + p1 + p2) / 3.0
+        }) / component.len() as f64;
+        let volume: f64 = component.iter().map(|triangle| {
+            let (p0, p1, p2) = mesh.triangle_positions(*triangle);
+            (p0 - center).dot((p1 - center).cross(p2 - center)) / 6.0
+        }).sum();
+        if volume < -volume_tolerance {
+            for triangle in component {
+                is_flipped[triangle] = !is_flipped[triangle];
+                mesh.triangles[triangle].swap(1, 2);
+            }
+            report.no_flipped_components += 1;
+        }
+    }
+
+    report.no_flipped_faces = is_flipped.iter().filter(|is_flipped| **is_flipped).count();
+    report.no_inconsistent_edges = mesh.edge_triangles().values()
+        .filter(|triangles| triangles.len() == 2 && triangles[0].1 == triangles[1].1).count();
+    report
+}
+
+fn flip(mesh: &mut IndexedMesh, is_flipped: &Vec<bool>)
+{
+    for (triangle, is_flipped) in mesh.triangles.iter_mut().zip(is_flipped.iter()) {
+        if *is_flipped {
+            triangle.swap(1, 2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Returns the mesh as an indexed mesh where the given triangles are reversed.
+    fn reversed(mesh: &Mesh, triangles: &[usize]) -> IndexedMesh
+    {
+        let mut indexed = IndexedMesh::new(mesh);
+        for triangle in triangles {
+            indexed.triangles[*triangle].swap(1, 2);
+        }
+        indexed
+    }
+
+    #[test]
+    fn test_orient_partially_reversed()
+    {
+        let cube = cube(2);
+        for (triangles, no_flipped_components) in [(vec![1, 4, 7], 0), (vec![0, 5, 6, 11], 1)].iter() {
+            let mut indexed = reversed(&cube, triangles);
+            let report = orient_indexed(&mut indexed);
+            assert_eq!((report.no_components, report.no_flipped_components, report.no_flipped_faces, report.no_inconsistent_edges),
+                (1, *no_flipped_components, triangles.len(), 0));
+            assert_eq!(sorted_triangles(&indexed.to_mesh().unwrap()), sorted_triangles(&cube));
+        }
+    }
+
+    #[test]
+    fn test_orient_inside_out()
+    {
+        let cube = cube(2);
+        let inside_out = reversed(&cube, &(0..cube.no_faces()).collect::<Vec<usize>>());
+        let mut mesh = inside_out.to_mesh().unwrap();
+        assert!((volume(&mesh) + 1.0).abs() < 1.0e-9);
+
+        let report = orient(&mut mesh).unwrap();
+        assert_eq!((report.no_components, report.no_flipped_components, report.no_flipped_faces, report.no_inconsistent_edges), (1, 1, cube.no_faces(), 0));
+        assert!((volume(&mesh) - 1.0).abs() < 1.0e-9);
+        assert_eq!(sorted_triangles(&mesh), sorted_triangles(&cube));
+
+        // A mesh which is already oriented is not changed
+        let report = orient(&mut mesh).unwrap();
+        assert_eq!((report.no_flipped_components, report.no_flipped_faces), (0, 0));
+    }
+
+    #[test]
+    fn test_orient_components()
+    {
+        // A partially reversed cube and an inside-out sphere, which are fixed independently of each other
+        let cube = cube(2);
+        let sphere = translated(&sphere(0.5, 6), vec3(3.0, 0.0, 0.0));
+        let mut indexed = reversed(&cube, &[2, 3, 8]);
+        indexed.append(&reversed(&sphere, &(0..sphere.no_faces()).collect::<Vec<usize>>()));
+        let report = orient_indexed(&mut indexed);
+        assert_eq!((report.no_components, report.no_flipped_components, report.no_flipped_faces, report.no_inconsistent_edges),
+            (2, 1, 3 + sphere.no_faces(), 0));
+
+        let mut expected = IndexedMesh::new(&cube);
+        expected.append(&IndexedMesh::new(&sphere));
+        assert_eq!(sorted_triangles(&indexed.to_mesh().unwrap()), sorted_triangles(&expected.to_mesh().unwrap()));
+    }
+
+    #[test]
+    fn test_orient_unorientable()
+    {
+        // A Möbius strip, where the last segment is joined to the first segment with a half twist
+        let n = 8;
+        let mut positions = Vec::new();
+        for i in 0..n {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+            let radial = vec3(angle.cos(), angle.sin(), 0.0);
+            let width = 0.2 * (radial * (0.5 * angle).cos() + vec3(0.0, 0.0, 1.0) * (0.5 * angle).sin());
+            positions.push(radial - width);
+            positions.push(radial + width);
+        }
+        let mut triangles = Vec::new();
+        for i in 0..n {
+            let (a, b) = (2 * i, 2 * i + 1);
+            let (c, d) = if i + 1 < n { (2 * i + 2, 2 * i + 3) } else { (1, 0) };
+            triangles.push([a, c, b]);
+            triangles.push([b, c, d]);
+        }
+        let mut strip = IndexedMesh {positions, triangles};
+
+        let report = orient_indexed(&mut strip);
+        assert_eq!((report.no_components, report.no_inconsistent_edges), (1, 1));
+        assert_eq!(strip.triangles.len(), 2 * n);
+    }
+}
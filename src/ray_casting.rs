@@ -0,0 +1,643 @@
+//! Accelerated ray casting against the faces of a mesh using a bounding volume hierarchy.
+
+use tri_mesh::prelude::*;
+
+const LEAF_SIZE: usize = 4;
+
+///
+/// The tolerance, relative to the diagonal of the bounding box of the occluding mesh, used when testing whether the view between two points is blocked.
+/// Hits closer than the tolerance to the end points are ignored, so a point on the surface does not block its own view.
+///
+pub(crate) const RELATIVE_EPSILON: f64 = 1.0e-8;
+
+///
+/// A bounding volume hierarchy over the faces of a mesh which answers ray and line piece queries
+/// in logarithmic instead of linear time in the number of faces.
+/// The ray caster is a snapshot of the mesh, so it must be rebuilt if the mesh changes.
+/// The faces are identified by their face ID, unless the ray caster is built from a list of triangles with other identifiers.
+///
+pub struct RayCaster<T = FaceID> {
+    /// The triangles, each with its identifier, its corners and its index in the list the ray caster is built from.
+    triangles: Vec<(T, Vec3, Vec3, Vec3, usize)>,
+    nodes: Vec<Node>
+}
+
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    /// The children of an inner node or the range of triangles in a leaf.
+    content: NodeContent
+}
+
+enum NodeContent {
+    Inner(usize, usize),
+    Leaf(usize, usize)
+}
+
+/// What to do after a hit when traversing the hierarchy with a ray.
+enum Visit {
+    /// Visit all hits.
+    All,
+    /// Only visit hits nearer than this hit.
+    Nearer,
+    /// Stop the traversal.
+    Stop
+}
+
+impl RayCaster<FaceID> {
+    pub fn new(mesh: &Mesh) -> Self
+    {
+        RayCaster::from_triangles(mesh.face_iter().map(|face_id| {
+            let (p0, p1, p2) = mesh.face_positions(face_id);
+            (face_id, p0, p1, p2)
+        }).collect())
+    }
+}
+
+impl<T: Copy> RayCaster<T> {
+    ///
+    /// Creates a ray caster from a list of triangles, each given by an identifier and its three corners.
+    ///
+    pub(crate) fn from_triangles(triangles: Vec<(T, Vec3, Vec3, Vec3)>) -> Self
+    {
+        let triangles = triangles.into_iter().enumerate().map(|(index, (id, p0, p1, p2))| (id, p0, p1, p2, index)).collect();
+        let mut ray_caster = RayCaster {triangles, nodes: Vec::new()};
+        if ray_caster.triangles.len() > 0 {
+            let no_triangles = ray_caster.triangles.len();
+            ray_caster.build(0, no_triangles);
+        }
+        ray_caster
+    }
+
+    fn build(&mut self, start: usize, end: usize) -> usize
+    {
+        let (min, max) = bounds(self.triangles[start..end].iter().flat_map(|(_, p0, p1, p2, _)| vec![*p0, *p1, *p2]));
+        let index = self.nodes.len();
+        self.nodes.push(Node {min, max, content: NodeContent::Leaf(start, end)});
+        if end - start <= LEAF_SIZE {
+            return index;
+        }
+
+        let (centroid_min, centroid_max) = bounds(self.triangles[start..end].iter().map(|(_, p0, p1, p2, _)| (p0 + p1 + p2) / 3.0));
+        let extent = centroid_max - centroid_min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+        self.triangles[start..end].sort_by(|a, b| (a.1[axis] + a.2[axis] + a.3[axis]).partial_cmp(&(b.1[axis] + b.2[axis] + b.3[axis])).unwrap());
+
+        let middle = (start + end) / 2;
+        let left = self.build(start, middle);
+        let right = self.build(middle, end);
+        self.nodes[index].content = NodeContent::Inner(left, right);
+        index
+    }
+
+    ///
+    /// Returns the length of the diagonal of the axis aligned bounding box of the mesh.
+    ///
+    pub fn diagonal(&self) -> f64
+    {
+        self.nodes.first().map(|node| (node.max - node.min).magnitude()).unwrap_or(0.0)
+    }
+
+    ///
+    /// Returns the tolerance to use with [blocks_view](#method.blocks_view) for this mesh, see [RELATIVE_EPSILON](constant.RELATIVE_EPSILON.html).
+    ///
+    pub(crate) fn epsilon(&self) -> f64
+    {
+        RELATIVE_EPSILON * self.diagonal()
+    }
+
+    ///
+    /// Returns the nearest face hit by the ray together with the distance along the ray (in units of the direction length),
+    /// ignoring hits closer than `min_distance` or further away than `max_distance`.
+    ///
+    pub fn first_hit(&self, origin: &Vec3, direction: &Vec3, min_distance: f64, max_distance: f64) -> Option<(T, f64)>
+    {
+        let mut result = None;
+        let mut max_distance = max_distance;
+        self.traverse(origin, direction, min_distance, &mut max_distance, &mut |face_id, t| {
+            result = Some((face_id, t));
+            Visit::Nearer
+        });
+        result
+    }
+
+    ///
+    /// Returns all faces hit by the ray together with the distance along the ray, sorted by the distance.
+    ///
+    pub fn hits(&self, origin: &Vec3, direction: &Vec3, min_distance: f64, max_distance: f64) -> Vec<(T, f64)>
+    {
+        let mut result = Vec::new();
+        let mut max = max_distance;
+        self.traverse(origin, direction, min_distance, &mut max, &mut |face_id, t| {
+            result.push((face_id, t));
+            Visit::All
+        });
+        result.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        result
+    }
+
+    ///
+    /// Returns whether any face blocks the view between the two points. Hits within `epsilon` of the end points are ignored,
+    /// so a point on the surface of the mesh does not block its own view. The traversal stops at the first hit found.
+    ///
+    pub fn blocks_view(&self, point0: &Vec3, point1: &Vec3, epsilon: f64) -> bool
+    {
+        let direction = point1 - point0;
+        let length = direction.magnitude();
+        if length <= 2.0 * epsilon {
+            return false;
+        }
+        let mut blocked = false;
+        let mut max_distance = 1.0 - epsilon / length;
+        self.traverse(point0, &direction, epsilon / length, &mut max_distance, &mut |_, _| {
+            blocked = true;
+            Visit::Stop
+        });
+        blocked
+    }
+
+    ///
+    /// Returns whether the point is inside the mesh, which must be closed, by counting the number of faces crossed by three rays (majority vote).
+    ///
+    pub fn is_inside(&self, point: &Vec3) -> bool
+    {
+        let no_inside = ray_directions().iter().filter(|direction| self.hits(point, direction, 0.0, std::f64::MAX).len() % 2 == 1).count();
+        no_inside >= 2
+    }
+
+    ///
+    /// Returns the face nearest to the point together with the closest point on that face, or `None` if the mesh has no faces.
+    ///
+    pub fn nearest_face(&self, point: &Vec3) -> Option<(T, Vec3)>
+    {
+        if self.nodes.len() == 0 {
+            return None;
+        }
+        let mut result = None;
+        let mut best_distance2 = std::f64::MAX;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if box_distance2(point, &node.min, &node.max) > best_distance2 {
+                continue;
+            }
+            match node.content {
+                NodeContent::Inner(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                },
+                NodeContent::Leaf(start, end) => {
+                    for (face_id, p0, p1, p2, _) in self.triangles[start..end].iter() {
+                        let closest = closest_point_on_triangle(point, &(*p0, *p1, *p2));
+                        let distance2 = closest.distance2(*point);
+                        if distance2 < best_distance2 {
+                            best_distance2 = distance2;
+                            result = Some((*face_id, closest));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    ///
+    /// Returns the pairs of faces which intersect each other, see [triangles_intersect](fn.triangles_intersect.html).
+    /// Each pair is given with the face which comes first in the mesh first and the pairs are sorted in the order of the faces in the mesh.
+    ///
+    pub fn intersecting_faces(&self) -> Vec<(T, T)>
+    {
+        let mut result = Vec::new();
+        for (face_id, p0, p1, p2, index) in self.triangles.iter() {
+            let triangle = (*p0, *p1, *p2);
+            let (min, max) = bounds(vec![*p0, *p1, *p2].into_iter());
+            self.visit_overlapping(&min, &max, &mut |other| {
+                let (other_face_id, q0, q1, q2, other_index) = self.triangles[other];
+                if other_index > *index && triangles_intersect(&triangle, &(q0, q1, q2)) {
+                    result.push(((*index, other_index), (*face_id, other_face_id)));
+                }
+            });
+        }
+        result.sort_by_key(|(indices, _)| *indices);
+        result.into_iter().map(|(_, pair)| pair).collect()
+    }
+
+    ///
+    /// Returns the triangles whose bounding box overlaps the given box.
+    ///
+    pub(crate) fn overlapping(&self, min: &Vec3, max: &Vec3) -> Vec<T>
+    {
+        let mut result = Vec::new();
+        self.visit_overlapping(min, max, &mut |index| result.push(self.triangles[index].0));
+        result
+    }
+
+    /// Visits the indices of the triangles whose bounding box overlaps the given box.
+    fn visit_overlapping(&self, min: &Vec3, max: &Vec3, callback: &mut dyn FnMut(usize))
+    {
+        if self.nodes.len() == 0 {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if (0..3).any(|i| node.max[i] < min[i] || node.min[i] > max[i]) {
+                continue;
+            }
+            match node.content {
+                NodeContent::Inner(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                },
+                NodeContent::Leaf(start, end) => {
+                    for triangle_index in start..end {
+                        callback(triangle_index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Visits the faces hit by the ray within the distance range, continuing as told by the callback after each hit.
+    fn traverse(&self, origin: &Vec3, direction: &Vec3, min_distance: f64, max_distance: &mut f64, callback: &mut dyn FnMut(T, f64) -> Visit)
+    {
+        if self.nodes.len() == 0 {
+            return;
+        }
+        let inverse_direction = vec3(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !ray_hits_box(origin, &inverse_direction, &node.min, &node.max, min_distance, *max_distance) {
+                continue;
+            }
+            match node.content {
+                NodeContent::Inner(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                },
+                NodeContent::Leaf(start, end) => {
+                    for (face_id, p0, p1, p2, _) in self.triangles[start..end].iter() {
+                        if let Some(t) = ray_triangle_intersection(origin, direction, &(*p0, *p1, *p2)) {
+                            if t >= min_distance && t <= *max_distance {
+                                match callback(*face_id, t) {
+                                    Visit::All => {},
+                                    Visit::Nearer => *max_distance = t,
+                                    Visit::Stop => return
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+///
+/// Returns three directions which are far from each other and from the coordinate axes and diagonals,
+/// so rays in at least two of the directions avoid hitting edges and vertices of axis aligned meshes exactly.
+///
+pub(crate) fn ray_directions() -> [Vec3; 3]
+{
+    [vec3(0.5773, 0.5774, 0.5775), vec3(-0.7072, 0.0012, 0.7070), vec3(0.0013, -0.8944, -0.4472)]
+}
+
+///
+/// Returns the length of the diagonal of the axis aligned bounding box of the points, or 0 if there are no points.
+///
+pub(crate) fn diagonal(points: impl Iterator<Item = Vec3>) -> f64
+{
+    let (min, max) = bounds(points);
+    if min.x > max.x { 0.0 } else { (max - min).magnitude() }
+}
+
+fn bounds(points: impl Iterator<Item = Vec3>) -> (Vec3, Vec3)
+{
+    let mut min = vec3(std::f64::MAX, std::f64::MAX, std::f64::MAX);
+    let mut max = vec3(std::f64::MIN, std::f64::MIN, std::f64::MIN);
+    for p in points {
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+    (min, max)
+}
+
+fn ray_hits_box(origin: &Vec3, inverse_direction: &Vec3, min: &Vec3, max: &Vec3, min_distance: f64, max_distance: f64) -> bool
+{
+    let mut t_min = min_distance;
+    let mut t_max = max_distance;
+    for i in 0..3 {
+        let mut t0 = (min[i] - origin[i]) * inverse_direction[i];
+        let mut t1 = (max[i] - origin[i]) * inverse_direction[i];
+        if t0.is_nan() || t1.is_nan() {
+            // The ray is parallel to and in the plane of the slab
+            continue;
+        }
+        if t0 > t1 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        t_min = t_min.max(t0);
+        t_max = t_max.min(t1);
+        if t_min > t_max {
+            return false;
+        }
+    }
+    true
+}
+
+///
+/// Returns whether the two triangles intersect away from the corners they share, if any.
+/// Triangles which are not coplanar intersect if an edge of one of the triangles crosses the other triangle at a point which is not a shared corner,
+/// so triangles sharing an edge never intersect. Coplanar triangles intersect if their interiors overlap.
+/// Triangles with the same three corners are duplicates, not intersecting.
+///
+pub(crate) fn triangles_intersect(triangle0: &(Vec3, Vec3, Vec3), triangle1: &(Vec3, Vec3, Vec3)) -> bool
+{
+    let vertices0 = [triangle0.0, triangle0.1, triangle0.2];
+    let vertices1 = [triangle1.0, triangle1.1, triangle1.2];
+    let shared: Vec<Vec3> = vertices0.iter().filter(|p| vertices1.contains(p)).cloned().collect();
+    if shared.len() == 3 {
+        return false;
+    }
+    let size = vertices0.iter().chain(vertices1.iter()).map(|p| p.distance(vertices0[0])).fold(0.0, f64::max);
+    let tolerance = 1.0e-9 * size;
+
+    if are_coplanar(triangle0, triangle1) {
+        let normal = (triangle0.1 - triangle0.0).cross(triangle0.2 - triangle0.0);
+        return coplanar_triangles_overlap(&vertices0, &vertices1, &normal, tolerance);
+    }
+    if shared.len() == 2 {
+        return false;
+    }
+
+    let edge_crosses = |vertices: &[Vec3; 3], triangle: &(Vec3, Vec3, Vec3)| (0..3).any(|i| {
+        let p0 = vertices[i];
+        let p1 = vertices[(i + 1) % 3];
+        match ray_triangle_intersection(&p0, &(p1 - p0), triangle) {
+            Some(t) if t <= 1.0 => {
+                let point = p0 + (p1 - p0) * t;
+                shared.iter().all(|corner| corner.distance(point) > tolerance)
+            },
+            _ => false
+        }
+    });
+    edge_crosses(&vertices0, triangle1) || edge_crosses(&vertices1, triangle0)
+}
+
+///
+/// Returns whether the corners of the second triangle are in the plane of the first triangle, which must not be degenerate,
+/// within a tolerance relative to the size of the triangles.
+///
+pub(crate) fn are_coplanar(triangle0: &(Vec3, Vec3, Vec3), triangle1: &(Vec3, Vec3, Vec3)) -> bool
+{
+    let normal = (triangle0.1 - triangle0.0).cross(triangle0.2 - triangle0.0);
+    if normal.magnitude2() == 0.0 {
+        return false;
+    }
+    let normal = normal.normalize();
+    let corners = [triangle0.0, triangle0.1, triangle0.2, triangle1.0, triangle1.1, triangle1.2];
+    let tolerance = 1.0e-9 * corners.iter().map(|p| p.distance(triangle0.0)).fold(0.0, f64::max);
+    corners[3..].iter().all(|p| normal.dot(p - triangle0.0).abs() <= tolerance)
+}
+
+///
+/// Returns whether the interiors of the two triangles in the plane with the given normal overlap, ie. whether two edges cross
+/// or a corner of one triangle is strictly inside the other triangle.
+///
+fn coplanar_triangles_overlap(vertices0: &[Vec3; 3], vertices1: &[Vec3; 3], normal: &Vec3, tolerance: f64) -> bool
+{
+    let axes = if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() { (1, 2) }
+        else if normal.y.abs() >= normal.z.abs() { (2, 0) } else { (0, 1) };
+    let project = |vertices: &[Vec3; 3]| {
+        let mut points: Vec<(f64, f64)> = vertices.iter().map(|p| (p[axes.0], p[axes.1])).collect();
+        // Orient the triangles counterclockwise in the projection plane
+        if orientation(points[0], points[1], points[2]) < 0.0 {
+            points.swap(1, 2);
+        }
+        points
+    };
+    let (points0, points1) = (project(vertices0), project(vertices1));
+    // The tolerance is scaled by a length to compare it to the doubled areas given by the orientation
+    let scale = vertices0.iter().chain(vertices1.iter()).map(|p| p.distance(vertices0[0])).fold(0.0, f64::max);
+    let area_tolerance = tolerance * scale;
+
+    let is_strictly_inside = |point: (f64, f64), triangle: &Vec<(f64, f64)>| (0..3).all(|i| orientation(triangle[i], triangle[(i + 1) % 3], point) > area_tolerance);
+    if points0.iter().any(|point| is_strictly_inside(*point, &points1)) || points1.iter().any(|point| is_strictly_inside(*point, &points0)) {
+        return true;
+    }
+    for i in 0..3 {
+        let (a, b) = (points0[i], points0[(i + 1) % 3]);
+        for j in 0..3 {
+            let (c, d) = (points1[j], points1[(j + 1) % 3]);
+            let (o0, o1) = (orientation(a, b, c), orientation(a, b, d));
+            let (o2, o3) = (orientation(c, d, a), orientation(c, d, b));
+            if ((o0 > area_tolerance && o1 < -area_tolerance) || (o0 < -area_tolerance && o1 > area_tolerance))
+                && ((o2 > area_tolerance && o3 < -area_tolerance) || (o2 < -area_tolerance && o3 > area_tolerance)) {
+                return true;
+            }
+        }
+    }
+    // Triangles with the same corners in the projection plane, but not the same corners in 3D, overlap completely
+    points0.iter().all(|p| points1.iter().any(|q| (p.0 - q.0).abs() <= tolerance && (p.1 - q.1).abs() <= tolerance))
+}
+
+/// Returns twice the signed area of the triangle in the plane, which is positive if the corners are counterclockwise.
+fn orientation(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64
+{
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+fn box_distance2(point: &Vec3, min: &Vec3, max: &Vec3) -> f64
+{
+    let mut distance2 = 0.0;
+    for i in 0..3 {
+        let d = (min[i] - point[i]).max(point[i] - max[i]).max(0.0);
+        distance2 += d * d;
+    }
+    distance2
+}
+
+///
+/// Returns the point on the triangle closest to the given point.
+///
+pub(crate) fn closest_point_on_triangle(point: &Vec3, triangle: &(Vec3, Vec3, Vec3)) -> Vec3
+{
+    let (a, b, c) = *triangle;
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 { return a; }
+
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 { return b; }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 { return c; }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
+
+///
+/// Returns the distance along the ray (in units of the direction length) to the intersection with the triangle,
+/// using the Möller–Trumbore algorithm. Only intersections in front of the origin are returned.
+/// The ray is parallel to the triangle if the determinant is small relative to the lengths of the edges and the direction,
+/// so the test does not depend on the scale of the mesh.
+///
+pub(crate) fn ray_triangle_intersection(origin: &Vec3, direction: &Vec3, triangle: &(Vec3, Vec3, Vec3)) -> Option<f64>
+{
+    let (p0, p1, p2) = triangle;
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let h = direction.cross(edge2);
+    let a = edge1.dot(h);
+    if a.abs() <= 1.0e-12 * edge1.magnitude() * edge2.magnitude() * direction.magnitude() { return None; }
+    let f = 1.0 / a;
+    let s = origin - p0;
+    let u = f * s.dot(h);
+    if u < 0.0 || u > 1.0 { return None; }
+    let q = s.cross(edge1);
+    let v = f * direction.dot(q);
+    if v < 0.0 || u + v > 1.0 { return None; }
+    let t = f * edge2.dot(q);
+    if t > 0.0 { Some(t) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn triangle(scale: f64) -> (Vec3, Vec3, Vec3)
+    {
+        (vec3(0.0, 0.0, 0.0), vec3(scale, 0.0, 0.0), vec3(0.0, scale, 0.0))
+    }
+
+    #[test]
+    fn test_ray_triangle_intersection()
+    {
+        let down = vec3(0.0, 0.0, -1.0);
+        assert_eq!(ray_triangle_intersection(&vec3(0.25, 0.25, 2.0), &down, &triangle(1.0)), Some(2.0));
+        assert_eq!(ray_triangle_intersection(&vec3(0.25, 0.25, 2.0), &(down * 4.0), &triangle(1.0)), Some(0.5));
+
+        // Misses beside the triangle and behind the origin
+        assert_eq!(ray_triangle_intersection(&vec3(0.75, 0.75, 2.0), &down, &triangle(1.0)), None);
+        assert_eq!(ray_triangle_intersection(&vec3(0.25, 0.25, -2.0), &down, &triangle(1.0)), None);
+
+        // A ray through an edge or a corner grazes the triangle
+        assert_eq!(ray_triangle_intersection(&vec3(0.5, 0.0, 1.0), &down, &triangle(1.0)), Some(1.0));
+        assert_eq!(ray_triangle_intersection(&vec3(0.0, 0.0, 1.0), &down, &triangle(1.0)), Some(1.0));
+
+        // Rays parallel or nearly parallel to the triangle, in its plane or above it, never hit
+        assert_eq!(ray_triangle_intersection(&vec3(-1.0, 0.25, 0.0), &vec3(1.0, 0.0, 0.0), &triangle(1.0)), None);
+        assert_eq!(ray_triangle_intersection(&vec3(-1.0, 0.25, 0.1), &vec3(1.0, 0.0, 0.0), &triangle(1.0)), None);
+        assert_eq!(ray_triangle_intersection(&vec3(-1.0e6, 0.25e6, -1.0e-6), &vec3(1.0e6, 0.0, 1.0e-12), &triangle(1.0e6)), None);
+    }
+
+    #[test]
+    fn test_ray_triangle_intersection_scale()
+    {
+        // The parallel test is relative, so tiny and huge triangles are hit like the unit triangle
+        for scale in [1.0e-6, 1.0e-3, 1.0e3, 1.0e6].iter() {
+            let origin = vec3(0.25, 0.25, 2.0) * *scale;
+            let t = ray_triangle_intersection(&origin, &vec3(0.0, 0.0, -*scale), &triangle(*scale));
+            assert!(t.map(|t| (t - 2.0).abs() < 1.0e-9).unwrap_or(false), "{:?} at scale {}", t, scale);
+        }
+    }
+
+    #[test]
+    fn test_hits()
+    {
+        let cube = cube(3);
+        let ray_caster = RayCaster::new(&cube);
+        let direction = ray_directions()[0];
+        let hits = ray_caster.hits(&vec3(0.5, 0.5, 0.5), &direction, 0.0, std::f64::MAX);
+        assert_eq!(hits.len(), 1);
+        let (face_id, t) = ray_caster.first_hit(&vec3(-1.0, -1.0, -1.0), &direction, 0.0, std::f64::MAX).unwrap();
+        assert_eq!(hits_of_brute_force(&cube, &vec3(-1.0, -1.0, -1.0), &direction)[0], (face_id, t));
+        assert_eq!(ray_caster.hits(&vec3(-1.0, -1.0, -1.0), &direction, 0.0, std::f64::MAX).len(), 2);
+        assert_eq!(ray_caster.hits(&vec3(-1.0, -1.0, -1.0), &direction, 0.0, t).len(), 1);
+        assert!(ray_caster.hits(&vec3(-1.0, -1.0, -1.0), &(-direction), 0.0, std::f64::MAX).is_empty());
+    }
+
+    /// Returns the hits of the ray with all faces of the mesh, sorted by the distance.
+    fn hits_of_brute_force(mesh: &Mesh, origin: &Vec3, direction: &Vec3) -> Vec<(FaceID, f64)>
+    {
+        let mut hits: Vec<(FaceID, f64)> = mesh.face_iter()
+            .filter_map(|face_id| ray_triangle_intersection(origin, direction, &mesh.face_positions(face_id)).map(|t| (face_id, t)))
+            .collect();
+        hits.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        hits
+    }
+
+    #[test]
+    fn test_nearest_face()
+    {
+        let sphere = sphere(1.0, 8);
+        let ray_caster = RayCaster::new(&sphere);
+        let points = [vec3(0.0, 0.0, 0.0), vec3(0.3, 0.2, 1.5), vec3(-2.0, 0.1, 0.4), vec3(0.1, -0.5, -0.5), vec3(5.0, 5.0, 5.0)];
+        for point in points.iter() {
+            let (face_id, closest) = ray_caster.nearest_face(point).unwrap();
+            let distance = |face_id: FaceID| closest_point_on_triangle(point, &sphere.face_positions(face_id)).distance(*point);
+            let nearest = sphere.face_iter().map(|face_id| distance(face_id)).fold(std::f64::MAX, f64::min);
+            assert!((distance(face_id) - nearest).abs() < 1.0e-12, "{} != {} at {:?}", distance(face_id), nearest, point);
+            assert!((closest.distance(*point) - nearest).abs() < 1.0e-12);
+        }
+        assert!(RayCaster::<FaceID>::from_triangles(Vec::new()).nearest_face(&vec3(0.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_is_inside()
+    {
+        let ray_caster = RayCaster::new(&sphere(1.0, 8));
+        assert!(ray_caster.is_inside(&vec3(0.0, 0.0, 0.0)));
+        assert!(ray_caster.is_inside(&vec3(0.5, -0.3, 0.2)));
+        assert!(!ray_caster.is_inside(&vec3(1.5, 0.0, 0.0)));
+        assert!(!ray_caster.is_inside(&vec3(0.0, 0.0, -2.0)));
+
+        // Points on the axis of a cube send rays through its edges and corners
+        let ray_caster = RayCaster::new(&cube(1));
+        assert!(ray_caster.is_inside(&vec3(0.5, 0.5, 0.5)));
+        assert!(!ray_caster.is_inside(&vec3(1.5, 1.5, 1.5)));
+        assert!(!ray_caster.is_inside(&vec3(-0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_blocks_view()
+    {
+        let room = cube(2);
+        let ray_caster = RayCaster::new(&room);
+        let epsilon = ray_caster.epsilon();
+        assert!(!ray_caster.blocks_view(&vec3(0.2, 0.3, 0.4), &vec3(0.8, 0.7, 0.6), epsilon));
+        assert!(ray_caster.blocks_view(&vec3(0.5, 0.5, 0.5), &vec3(2.0, 0.5, 0.5), epsilon));
+        assert!(ray_caster.blocks_view(&vec3(-1.0, 0.5, 0.5), &vec3(2.0, 0.5, 0.5), epsilon));
+
+        // End points on the surface do not block their own view
+        assert!(!ray_caster.blocks_view(&vec3(0.0, 0.5, 0.5), &vec3(1.0, 0.25, 0.75), epsilon));
+        assert!(!ray_caster.blocks_view(&vec3(0.5, 0.5, 0.0), &vec3(0.5, 0.5, 0.0), epsilon));
+    }
+}
@@ -0,0 +1,295 @@
+//! Isotropic remeshing, ie. replacing the faces of a mesh by well shaped faces with edges of (almost) the same length,
+//! following Botsch and Kobbelt; edges are repeatedly split, collapsed and flipped and the vertices are relaxed in the tangent plane.
+
+use tri_mesh::prelude::*;
+use crate::editable_mesh::EditableMesh;
+use crate::features::preserved_edge_indices;
+use crate::indexed_mesh::IndexedMesh;
+use crate::ray_casting::RayCaster;
+use crate::welding::Tolerance;
+
+const MAX_SPLIT_PASSES: usize = 32;
+/// With adaptive sizing, edges are never shorter than this fraction of the target edge length.
+const MIN_EDGE_LENGTH_FRACTION: f64 = 0.1;
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// Options for the remeshing of a mesh, see [remesh](fn.remesh.html).
+///
+#[derive(Debug, Clone)]
+pub struct RemeshingOptions {
+    pub target_edge_length: Tolerance,
+    /// If given, the edges are shortened where the surface is curved, so the distance between the faces and the curved surface is at most this tolerance.
+    pub max_curvature_error: Option<Tolerance>,
+    pub no_iterations: usize,
+    /// Whether to preserve the boundary edges. The vertices on the boundary only move along the boundary, and the corners of the boundary are kept.
+    pub preserve_boundaries: bool,
+    /// Edges, given by their end vertices, which are preserved in the same way as the boundary, for example intersection curves
+    /// or the sharp edges found by [feature_edges](../features/fn.feature_edges.html).
+    pub feature_edges: Vec<(VertexID, VertexID)>,
+    /// If given, the sharp edges where the angle in radians between the face normals is larger than this threshold, see [feature_edges](../features/fn.feature_edges.html),
+    /// are preserved as well, for example with the threshold [DEFAULT_ANGLE_THRESHOLD](../features/constant.DEFAULT_ANGLE_THRESHOLD.html).
+    pub feature_angle: Option<f64>
+}
+
+impl Default for RemeshingOptions {
+    fn default() -> Self {
+        RemeshingOptions {target_edge_length: Tolerance::Relative(0.02), max_curvature_error: None, no_iterations: 5,
+            preserve_boundaries: true, feature_edges: Vec::new(), feature_angle: None}
+    }
+}
+
+///
+/// The changes made to a mesh by [remesh](fn.remesh.html).
+///
+#[derive(Debug, Clone, Default)]
+pub struct RemeshingReport {
+    pub no_split_edges: usize,
+    pub no_collapsed_edges: usize,
+    pub no_flipped_edges: usize
+}
+
+///
+/// Remeshes the mesh so the edges get the target length. In each iteration, edges longer than 4/3 of the target length are split,
+/// edges shorter than 4/5 of the target length are collapsed, edges are flipped to give the vertices six neighbours (four on the boundary),
+/// and the vertices are moved towards the center of their neighbours in the tangent plane and projected back onto the original surface.
+/// Vertices on preserved or non-manifold edges are not relaxed.
+/// The mesh is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn remesh(mesh: &mut Mesh, options: &RemeshingOptions) -> Result<RemeshingReport, Error>
+{
+    let indexed = IndexedMesh::new(mesh);
+    let feature_edges = preserved_edge_indices(mesh, &options.feature_edges, options.feature_angle);
+    let diagonal = indexed.diagonal();
+    let target_edge_length = options.target_edge_length.distance(diagonal);
+    let ray_caster = RayCaster::from_triangles((0..indexed.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = indexed.triangle_positions(triangle);
+        (triangle, p0, p1, p2)
+    }).collect());
+
+    let mut remesher = Remesher {mesh: EditableMesh::new(&indexed, &feature_edges, options.preserve_boundaries), sizes: Vec::new(), report: RemeshingReport::default()};
+    remesher.sizes = match options.max_curvature_error {
+        Some(tolerance) => curvature_sizes(&remesher.mesh, target_edge_length, tolerance.distance(diagonal)),
+        None => vec![target_edge_length; indexed.positions.len()]
+    };
+    for _ in 0..options.no_iterations {
+        remesher.split_long_edges();
+        remesher.collapse_short_edges();
+        remesher.flip_edges();
+        remesher.relax(&ray_caster);
+    }
+
+    *mesh = remesher.mesh.to_indexed().to_mesh()?;
+    Ok(remesher.report)
+}
+
+struct Remesher {
+    mesh: EditableMesh,
+    /// The target edge length at each vertex.
+    sizes: Vec<f64>,
+    report: RemeshingReport
+}
+
+impl Remesher {
+    fn target_length(&self, i0: usize, i1: usize) -> f64
+    {
+        0.5 * (self.sizes[i0] + self.sizes[i1])
+    }
+
+    fn length(&self, i0: usize, i1: usize) -> f64
+    {
+        self.mesh.positions[i0].distance(self.mesh.positions[i1])
+    }
+
+    fn split_long_edges(&mut self)
+    {
+        for _ in 0..MAX_SPLIT_PASSES {
+            let mut no_split = 0;
+            for (i0, i1) in self.mesh.edges() {
+                if self.length(i0, i1) > 4.0 / 3.0 * self.target_length(i0, i1) {
+                    let position = 0.5 * (self.mesh.positions[i0] + self.mesh.positions[i1]);
+                    self.mesh.split_edge(i0, i1, position);
+                    self.sizes.push(self.target_length(i0, i1));
+                    no_split += 1;
+                }
+            }
+            self.report.no_split_edges += no_split;
+            if no_split == 0 {
+                break;
+            }
+        }
+    }
+
+    fn collapse_short_edges(&mut self)
+    {
+        let mut edges = self.mesh.edges();
+        edges.sort_by(|(a0, b0), (a1, b1)| self.length(*a0, *b0).partial_cmp(&self.length(*a1, *b1)).unwrap());
+        for (i0, i1) in edges {
+            if self.mesh.edge_triangles(i0, i1).len() == 0 || self.length(i0, i1) >= 4.0 / 5.0 * self.target_length(i0, i1) {
+                continue;
+            }
+            let is_free = self.mesh.is_free(i0) && self.mesh.is_free(i1);
+            for (removed, kept) in self.mesh.collapse_directions(i0, i1) {
+                let position = if is_free { 0.5 * (self.mesh.positions[i0] + self.mesh.positions[i1]) } else { self.mesh.positions[kept] };
+                // Avoid creating new long edges, which would be split again
+                let creates_long_edge = self.mesh.neighbours(removed).union(&self.mesh.neighbours(kept))
+                    .filter(|neighbour| **neighbour != removed && **neighbour != kept)
+                    .any(|neighbour| position.distance(self.mesh.positions[*neighbour]) > 4.0 / 3.0 * self.target_length(*neighbour, kept));
+                if !creates_long_edge && self.mesh.is_collapsible(removed, kept, &position) {
+                    self.mesh.collapse(removed, kept, position);
+                    self.sizes[kept] = self.sizes[kept].min(self.sizes[removed]);
+                    self.report.no_collapsed_edges += 1;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn flip_edges(&mut self)
+    {
+        let target_valence = |mesh: &EditableMesh, vertex: usize| if mesh.is_on_boundary(vertex) { 4 } else { 6 };
+        for (i0, i1) in self.mesh.edges() {
+            if let Some((i2, i3)) = self.mesh.flip_vertices(i0, i1) {
+                let deviation = |vertex: usize, change: i64| {
+                    (self.mesh.neighbours(vertex).len() as i64 + change - target_valence(&self.mesh, vertex)).abs()
+                };
+                let before = deviation(i0, 0) + deviation(i1, 0) + deviation(i2, 0) + deviation(i3, 0);
+                let after = deviation(i0, -1) + deviation(i1, -1) + deviation(i2, 1) + deviation(i3, 1);
+                if after < before && self.mesh.flip_edge(i0, i1) {
+                    self.report.no_flipped_edges += 1;
+                }
+            }
+        }
+    }
+
+    /// Moves the free vertices, which are not on the boundary, towards the center of their neighbours in the tangent plane and projects them onto the original surface.
+    fn relax(&mut self, ray_caster: &RayCaster<usize>)
+    {
+        let positions: Vec<Option<Vec3>> = (0..self.mesh.positions.len()).map(|vertex| {
+            if self.mesh.vertex_triangles[vertex].len() == 0 || !self.mesh.is_free(vertex) || self.mesh.is_on_boundary(vertex) {
+                return None;
+            }
+            let neighbours = self.mesh.neighbours(vertex);
+            let mut center = vec3(0.0, 0.0, 0.0);
+            for neighbour in neighbours.iter() {
+                center += self.mesh.positions[*neighbour];
+            }
+            let p = self.mesh.positions[vertex];
+            let displacement = center / neighbours.len() as f64 - p;
+            let normal = self.mesh.vertex_normal(vertex);
+            let relaxed = p + displacement - normal * normal.dot(displacement);
+            Some(ray_caster.nearest_face(&relaxed).map(|(_, point)| point).unwrap_or(relaxed))
+        }).collect();
+        for (vertex, position) in positions.into_iter().enumerate() {
+            if let Some(position) = position {
+                self.mesh.positions[vertex] = position;
+            }
+        }
+    }
+}
+
+/// Returns the target edge length at each vertex, so the distance between an edge and a circle with the largest curvature at the vertex is at most the error.
+/// The curvature is estimated from the circles through each neighbour which touches the tangent plane at the vertex.
+fn curvature_sizes(mesh: &EditableMesh, target_edge_length: f64, error: f64) -> Vec<f64>
+{
+    (0..mesh.positions.len()).map(|vertex| {
+        let normal = mesh.vertex_normal(vertex);
+        let p = mesh.positions[vertex];
+        let curvature = mesh.neighbours(vertex).iter().map(|neighbour| {
+            let d = mesh.positions[*neighbour] - p;
+            2.0 * normal.dot(d).abs() / d.magnitude2().max(std::f64::MIN_POSITIVE)
+        }).fold(0.0, f64::max);
+        // The edge length of equilateral triangles whose distance to a sphere with the curvature is the error
+        let squared_length = 6.0 * error / curvature.max(std::f64::MIN_POSITIVE) - 3.0 * error * error;
+        let length = if squared_length > 0.0 { squared_length.sqrt() } else { 0.0 };
+        length.max(MIN_EDGE_LENGTH_FRACTION * target_edge_length).min(target_edge_length)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Returns the lengths of the edges of the faces, where the inner edges appear twice.
+    fn edge_lengths(mesh: &Mesh) -> Vec<f64>
+    {
+        mesh.face_iter().flat_map(|face_id| {
+            let (p0, p1, p2) = mesh.face_positions(face_id);
+            vec![p0.distance(p1), p1.distance(p2), p2.distance(p0)]
+        }).collect()
+    }
+
+    #[test]
+    fn test_remesh_sphere()
+    {
+        let target = 0.2;
+        let mut mesh = sphere(1.0, 16);
+        let report = remesh(&mut mesh, &RemeshingOptions {target_edge_length: Tolerance::Absolute(target), ..Default::default()}).unwrap();
+        assert!(report.no_collapsed_edges > 0);
+        assert!(crate::validation::validate(&mesh).is_valid());
+
+        let lengths = edge_lengths(&mesh);
+        // The last relaxation may stretch a few edges slightly beyond the split length
+        let mean = lengths.iter().sum::<f64>() / lengths.len() as f64;
+        assert!((mean - target).abs() < 0.1 * target);
+        assert!(lengths.iter().all(|length| *length > 0.5 * target && *length < 1.5 * target));
+        // The vertices are projected onto the original faces, which are inside the sphere
+        for vertex_id in mesh.vertex_iter() {
+            let distance = mesh.vertex_position(vertex_id).magnitude();
+            assert!(distance <= 1.0 + 1.0e-9 && distance > 0.98);
+        }
+        for face_id in mesh.face_iter() {
+            let (p0, p1, p2) = mesh.face_positions(face_id);
+            assert!((p1 - p0).cross(p2 - p0).dot(p0 + p1 + p2) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_curvature_sizes()
+    {
+        // The target edge length on a unit sphere with a curvature error of 0.002 is about 0.11
+        let options = RemeshingOptions {target_edge_length: Tolerance::Absolute(0.4), ..Default::default()};
+        let mut mesh = sphere(1.0, 16);
+        remesh(&mut mesh, &options).unwrap();
+        let mut refined = sphere(1.0, 16);
+        remesh(&mut refined, &RemeshingOptions {max_curvature_error: Some(Tolerance::Absolute(0.002)), ..options}).unwrap();
+
+        assert!(refined.no_faces() > 8 * mesh.no_faces());
+        assert!(edge_lengths(&refined).iter().all(|length| *length < 1.5 * 0.12));
+    }
+
+    #[test]
+    fn test_preserve_boundaries()
+    {
+        // A box without a lid, whose boundary is the square at the top
+        let cube = cube(4);
+        let positions = cube.positions_buffer();
+        let indices: Vec<u32> = cube.indices_buffer().chunks(3).filter(|triangle| triangle.iter().any(|index| positions[3 * *index as usize + 2] < 1.0))
+            .flat_map(|triangle| triangle.to_vec()).collect();
+        let mut mesh = self::mesh(positions, indices);
+        remesh(&mut mesh, &RemeshingOptions {target_edge_length: Tolerance::Absolute(0.15), ..Default::default()}).unwrap();
+
+        let is_on_square = |p: &Vec3| p.z > 1.0 - 1.0e-9 && (p.x.abs() < 1.0e-9 || p.y.abs() < 1.0e-9 || (p.x - 1.0).abs() < 1.0e-9 || (p.y - 1.0).abs() < 1.0e-9);
+        let positions: Vec<Vec3> = mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)).collect();
+        for corner in [vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 1.0), vec3(1.0, 1.0, 1.0), vec3(0.0, 1.0, 1.0)].iter() {
+            assert!(positions.iter().any(|p| p.distance(*corner) < 1.0e-9));
+        }
+        for p in positions.iter().filter(|p| p.z > 1.0 - 1.0e-9) {
+            assert!(is_on_square(p));
+        }
+        let boundary_loops = crate::holes::boundary_loops(&mesh);
+        assert_eq!(boundary_loops.len(), 1);
+        assert!(boundary_loops[0].iter().all(|vertex_id| is_on_square(&mesh.vertex_position(*vertex_id))));
+    }
+}
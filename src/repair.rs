@@ -0,0 +1,382 @@
+//! Automatic repair of the most common defects in a mesh, see [validate](../validation/fn.validate.html) for detecting them.
+
+use tri_mesh::prelude::*;
+use crate::holes::{boundary_loops_indexed, fill_holes_indexed, HoleFillingOptions};
+use crate::indexed_mesh::IndexedMesh;
+use crate::orientation::orient_indexed;
+use crate::welding::Tolerance;
+use crate::validation::DEGENERATE_AREA_TOLERANCE;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// Options for the repair of a mesh, see [repair](fn.repair.html).
+///
+#[derive(Debug, Clone)]
+pub struct RepairOptions {
+    /// Vertices closer than this tolerance are merged, see [weld](../welding/fn.weld.html).
+    pub merge_tolerance: Tolerance,
+    /// Whether to remove the faces with two identical vertices or (almost) zero area.
+    pub remove_degenerate_faces: bool,
+    /// Whether to remove the faces with the same vertices as another face.
+    pub remove_duplicate_faces: bool,
+    /// Whether to split the vertices and edges where several surfaces meet, so the mesh becomes a two-manifold.
+    pub fix_non_manifold: bool,
+    /// Whether to make the orientation of the faces consistent and the normals point outwards, see [orient](../orientation/fn.orient.html).
+    pub fix_orientation: bool,
+    /// Holes with at most this number of boundary edges are filled, 0 means that no holes are filled. The holes with more boundary edges than
+    /// the default [fair_threshold](../holes/struct.HoleFillingOptions.html#structfield.fair_threshold) are refined and faired,
+    /// the smaller holes are filled with a flat triangulation. Use [fill_holes](../holes/fn.fill_holes.html) for more control.
+    pub max_hole_size: usize
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        RepairOptions {merge_tolerance: Tolerance::Relative(1.0e-6), remove_degenerate_faces: true, remove_duplicate_faces: true,
+            fix_non_manifold: true, fix_orientation: true, max_hole_size: 10}
+    }
+}
+
+///
+/// The changes made to a mesh by [repair](fn.repair.html). The mesh is rebuilt, so the changed vertices and faces are given by their positions
+/// and a face is given by the positions of its three corners.
+///
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub removed_isolated_vertices: Vec<Vec3>,
+    /// The vertices which are merged into another vertex within the merge tolerance.
+    pub merged_vertices: Vec<Vec3>,
+    /// The edges of degenerate faces which are collapsed to make the faces disappear, given by their end points before collapsing.
+    pub collapsed_edges: Vec<(Vec3, Vec3)>,
+    /// The faces which are removed because two of their vertices are identical, either in the input or after merging vertices or collapsing edges.
+    pub removed_degenerate_faces: Vec<(Vec3, Vec3, Vec3)>,
+    pub removed_duplicate_faces: Vec<(Vec3, Vec3, Vec3)>,
+    /// The vertices which are split when splitting non-manifold vertices and edges, once for each added copy.
+    pub split_vertices: Vec<Vec3>,
+    /// The faces which are flipped, with the corners in the new order.
+    pub flipped_faces: Vec<(Vec3, Vec3, Vec3)>,
+    /// The boundary loops of the filled holes.
+    pub filled_holes: Vec<Vec<Vec3>>,
+    /// The faces added when filling holes.
+    pub added_faces: Vec<(Vec3, Vec3, Vec3)>
+}
+
+impl RepairReport {
+    ///
+    /// Returns whether the repair did not change anything.
+    ///
+    pub fn is_unchanged(&self) -> bool
+    {
+        self.removed_isolated_vertices.len() == 0 && self.merged_vertices.len() == 0 && self.collapsed_edges.len() == 0
+            && self.removed_degenerate_faces.len() == 0 && self.removed_duplicate_faces.len() == 0 && self.split_vertices.len() == 0
+            && self.flipped_faces.len() == 0 && self.filled_holes.len() == 0 && self.added_faces.len() == 0
+    }
+}
+
+///
+/// Repairs the mesh by applying the steps enabled in the options in the following order; removes isolated vertices, merges near-duplicate vertices,
+/// removes degenerate and duplicate faces, splits non-manifold vertices and edges, makes the orientation consistent and fills small holes.
+/// Returns a report of everything that is changed.
+/// The mesh is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn repair(mesh: &mut Mesh, options: &RepairOptions) -> Result<RepairReport, Error>
+{
+    let mut indexed = IndexedMesh::new(mesh);
+    let mut report = RepairReport::default();
+
+    report.removed_isolated_vertices = unused_vertices(&indexed).iter().map(|vertex| indexed.positions[*vertex]).collect();
+    indexed.remove_unused_vertices();
+    let diagonal = indexed.diagonal();
+    // All vertices are used before merging, so the unused vertices after merging are the merged vertices
+    indexed.merge_close_vertices(options.merge_tolerance.distance(diagonal));
+    report.merged_vertices = unused_vertices(&indexed).iter().map(|vertex| indexed.positions[*vertex]).collect();
+    if options.remove_degenerate_faces {
+        let (removed, collapsed) = remove_degenerate_triangles(&mut indexed, DEGENERATE_AREA_TOLERANCE * diagonal * diagonal);
+        report.removed_degenerate_faces = removed;
+        report.collapsed_edges = collapsed;
+    }
+    if options.remove_duplicate_faces {
+        report.removed_duplicate_faces = remove_duplicate_triangles(&mut indexed);
+    }
+    if options.fix_non_manifold {
+        report.split_vertices = split_non_manifold_vertices(&mut indexed);
+    }
+    if options.fix_orientation {
+        let triangles = indexed.triangles.clone();
+        orient_indexed(&mut indexed);
+        report.flipped_faces = (0..triangles.len()).filter(|triangle| triangles[*triangle] != indexed.triangles[*triangle])
+            .map(|triangle| indexed.triangle_positions(triangle)).collect();
+    }
+    if options.max_hole_size > 0 {
+        let holes = boundary_loops_indexed(&indexed);
+        let no_triangles = indexed.triangles.len();
+        fill_holes_indexed(&mut indexed, &HoleFillingOptions {max_hole_size: options.max_hole_size, ..Default::default()});
+        // A hole is filled if its boundary edges are no longer on the boundary
+        let edges = indexed.edge_triangles();
+        report.filled_holes = holes.iter().filter(|hole| edges[&(hole[0].min(hole[1]), hole[0].max(hole[1]))].len() > 1)
+            .map(|hole| hole.iter().map(|vertex| indexed.positions[*vertex]).collect()).collect();
+        report.added_faces = (no_triangles..indexed.triangles.len()).map(|triangle| indexed.triangle_positions(triangle)).collect();
+    }
+
+    *mesh = indexed.to_mesh()?;
+    Ok(report)
+}
+
+/// Returns the vertices which are not part of any triangle.
+fn unused_vertices(mesh: &IndexedMesh) -> Vec<usize>
+{
+    let used: HashSet<usize> = mesh.triangles.iter().flat_map(|triangle| triangle.iter().cloned()).collect();
+    (0..mesh.positions.len()).filter(|vertex| !used.contains(vertex)).collect()
+}
+
+/// Returns the positions of the corners of the triangle given by its vertex indices.
+fn corner_positions(mesh: &IndexedMesh, [i0, i1, i2]: &[usize; 3]) -> (Vec3, Vec3, Vec3)
+{
+    (mesh.positions[*i0], mesh.positions[*i1], mesh.positions[*i2])
+}
+
+/// Removes the triangles with two identical vertices and collapses the shortest edge of the triangles with an area below the tolerance
+/// until no degenerate triangles are left. Returns the removed triangles and the collapsed edges.
+fn remove_degenerate_triangles(mesh: &mut IndexedMesh, area_tolerance: f64) -> (Vec<(Vec3, Vec3, Vec3)>, Vec<(Vec3, Vec3)>)
+{
+    let mut removed = Vec::new();
+    let mut collapsed = Vec::new();
+    loop {
+        removed.extend(mesh.triangles.iter().filter(|[i0, i1, i2]| i0 == i1 || i1 == i2 || i2 == i0).map(|triangle| corner_positions(mesh, triangle)));
+        mesh.triangles.retain(|[i0, i1, i2]| i0 != i1 && i1 != i2 && i2 != i0);
+
+        let mut mapping: Vec<usize> = (0..mesh.positions.len()).collect();
+        let mut is_locked = vec![false; mesh.positions.len()];
+        for triangle in 0..mesh.triangles.len() {
+            if mesh.triangle_area(triangle) > area_tolerance {
+                continue;
+            }
+            let [i0, i1, i2] = mesh.triangles[triangle];
+            let (a, b) = [(i0, i1), (i1, i2), (i2, i0)].iter()
+                .min_by(|(a0, b0), (a1, b1)| mesh.positions[*a0].distance2(mesh.positions[*b0])
+                    .partial_cmp(&mesh.positions[*a1].distance2(mesh.positions[*b1])).unwrap())
+                .cloned().unwrap();
+            if !is_locked[a] && !is_locked[b] {
+                is_locked[a] = true;
+                is_locked[b] = true;
+                mapping[b] = a;
+                collapsed.push((mesh.positions[a], mesh.positions[b]));
+            }
+        }
+        if mapping.iter().enumerate().all(|(index, mapped)| index == *mapped) {
+            break;
+        }
+        for triangle in mesh.triangles.iter_mut() {
+            for index in triangle.iter_mut() {
+                *index = mapping[*index];
+            }
+        }
+    }
+    (removed, collapsed)
+}
+
+/// Removes the triangles with the same vertices as an earlier triangle, regardless of orientation, and returns the removed triangles.
+fn remove_duplicate_triangles(mesh: &mut IndexedMesh) -> Vec<(Vec3, Vec3, Vec3)>
+{
+    let mut keys = HashSet::new();
+    let mut removed = Vec::new();
+    let mut triangles = Vec::with_capacity(mesh.triangles.len());
+    for triangle in mesh.triangles.iter() {
+        let mut key = *triangle;
+        key.sort();
+        if keys.insert(key) {
+            triangles.push(*triangle);
+        }
+        else {
+            removed.push(corner_positions(mesh, triangle));
+        }
+    }
+    mesh.triangles = triangles;
+    removed
+}
+
+/// Gives each fan of triangles around a vertex its own copy of the vertex, where fans are connected through manifold edges only,
+/// so both non-manifold vertices and non-manifold edges are split. Returns the position of the split vertex for each added vertex.
+fn split_non_manifold_vertices(mesh: &mut IndexedMesh) -> Vec<Vec3>
+{
+    let edges = mesh.edge_triangles();
+    let vertex_triangles = mesh.vertex_triangles();
+    let mut split = Vec::new();
+    for (vertex, triangles) in vertex_triangles.iter().enumerate() {
+        let mut fan_of: HashMap<usize, usize> = HashMap::new();
+        let mut no_fans = 0;
+        for start in triangles.iter() {
+            if fan_of.contains_key(start) {
+                continue;
+            }
+            let mut stack = vec![*start];
+            fan_of.insert(*start, no_fans);
+            while let Some(triangle) = stack.pop() {
+                for neighbour in mesh.manifold_neighbours(triangle, &edges) {
+                    if triangles.contains(&neighbour) && !fan_of.contains_key(&neighbour) && shares_edge_at(mesh, triangle, neighbour, vertex) {
+                        fan_of.insert(neighbour, no_fans);
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            no_fans += 1;
+        }
+
+        let copies: Vec<usize> = (1..no_fans).map(|_| {
+            mesh.positions.push(mesh.positions[vertex]);
+            mesh.positions.len() - 1
+        }).collect();
+        for (triangle, fan) in fan_of.iter() {
+            if *fan > 0 {
+                for index in mesh.triangles[*triangle].iter_mut() {
+                    if *index == vertex {
+                        *index = copies[*fan - 1];
+                    }
+                }
+            }
+        }
+        split.extend(copies.iter().map(|copy| mesh.positions[*copy]));
+    }
+    split
+}
+
+/// Returns whether the two triangles share an edge which has the vertex as an end point.
+fn shares_edge_at(mesh: &IndexedMesh, triangle0: usize, triangle1: usize, vertex: usize) -> bool
+{
+    mesh.triangles[triangle0].iter().filter(|index| **index != vertex)
+        .any(|index| mesh.triangles[triangle1].contains(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::validation::validate;
+
+    /// Returns the positions and indices of the unit cube divided into 2 by 2 squares on each side.
+    fn cube_buffers() -> (Vec<f64>, Vec<u32>)
+    {
+        let cube = cube(2);
+        (cube.positions_buffer(), cube.indices_buffer())
+    }
+
+    #[test]
+    fn test_valid_mesh_is_unchanged()
+    {
+        let mut mesh = cube(2);
+        let report = repair(&mut mesh, &RepairOptions::default()).unwrap();
+        assert!(report.is_unchanged(), "{:?}", report);
+        assert_eq!(sorted_triangles(&mesh), sorted_triangles(&cube(2)));
+    }
+
+    #[test]
+    fn test_removed_vertices_and_faces()
+    {
+        let (mut positions, mut indices) = cube_buffers();
+        let isolated = vec3(5.0, 5.0, 5.0);
+        positions.extend(&[isolated.x, isolated.y, isolated.z]);
+        let (i0, i1, i2) = (indices[0], indices[1], indices[2]);
+        indices.extend(&[i2, i1, i0, i0, i0, i1]);
+        let mut mesh = self::mesh(positions, indices);
+        let report = repair(&mut mesh, &RepairOptions::default()).unwrap();
+
+        assert_eq!(report.removed_isolated_vertices, vec![isolated]);
+        assert_eq!(report.removed_duplicate_faces.len(), 1);
+        assert_eq!(report.removed_degenerate_faces.len(), 1);
+        let (p0, p1, _) = report.removed_degenerate_faces[0];
+        assert_eq!(p0, p1);
+        assert!(validate(&mesh).is_valid());
+        assert_eq!(sorted_triangles(&mesh), sorted_triangles(&cube(2)));
+    }
+
+    #[test]
+    fn test_merged_vertices()
+    {
+        // The first face refers to a copy of its first vertex, slightly moved
+        let (mut positions, mut indices) = cube_buffers();
+        let vertex = indices[0] as usize;
+        let copy = vec3(positions[3 * vertex] + 1.0e-9, positions[3 * vertex + 1], positions[3 * vertex + 2]);
+        indices[0] = (positions.len() / 3) as u32;
+        positions.extend(&[copy.x, copy.y, copy.z]);
+        let mut mesh = self::mesh(positions, indices);
+        let report = repair(&mut mesh, &RepairOptions::default()).unwrap();
+
+        // Which of the two vertices is merged into the other depends on the order of the vertices
+        assert_eq!(report.merged_vertices.len(), 1);
+        assert!(report.merged_vertices[0].distance(copy) <= 1.0e-9);
+        assert_eq!(mesh.no_vertices(), cube(2).no_vertices());
+        assert!(validate(&mesh).is_valid());
+    }
+
+    #[test]
+    fn test_flipped_faces()
+    {
+        let (positions, mut indices) = cube_buffers();
+        indices.swap(1, 2);
+        let mut mesh = self::mesh(positions.clone(), indices.clone());
+        let report = repair(&mut mesh, &RepairOptions::default()).unwrap();
+
+        let corner = |i: usize| vec3(positions[3 * indices[i] as usize], positions[3 * indices[i] as usize + 1], positions[3 * indices[i] as usize + 2]);
+        assert_eq!(report.flipped_faces, vec![(corner(0), corner(2), corner(1))]);
+        assert!(validate(&mesh).is_valid());
+        assert_eq!(sorted_triangles(&mesh), sorted_triangles(&cube(2)));
+    }
+
+    #[test]
+    fn test_filled_holes()
+    {
+        // Remove the two faces of a square at a corner of the cube
+        let (positions, indices) = cube_buffers();
+        let mut mesh = self::mesh(positions, indices[6..].to_vec());
+        let report = repair(&mut mesh, &RepairOptions::default()).unwrap();
+
+        assert_eq!(report.filled_holes.len(), 1);
+        assert_eq!(report.filled_holes[0].len(), 4);
+        assert_eq!(report.added_faces.len(), 2);
+        assert!(validate(&mesh).is_valid());
+        assert!((volume(&mesh) - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn test_split_vertices()
+    {
+        // Two tetrahedra touching at a vertex
+        let positions = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, -1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, -1.0];
+        let indices = vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3, 0, 5, 4, 0, 4, 6, 0, 6, 5, 4, 5, 6];
+        let mut mesh = self::mesh(positions, indices);
+        assert_eq!(validate(&mesh).non_manifold_vertices.len(), 1);
+        let report = repair(&mut mesh, &RepairOptions::default()).unwrap();
+
+        assert_eq!(report.split_vertices, vec![vec3(0.0, 0.0, 0.0)]);
+        assert_eq!(mesh.no_vertices(), 8);
+        assert!(validate(&mesh).is_valid());
+    }
+
+    #[test]
+    fn test_large_holes_are_faired()
+    {
+        // The hole at the top of the sphere has 16 boundary edges, which is more than the fair threshold
+        let sphere = sphere(1.0, 8);
+        let positions = sphere.positions_buffer();
+        let indices: Vec<u32> = sphere.indices_buffer().chunks(3).filter(|triangle| triangle.iter().any(|index| positions[3 * *index as usize + 2] < 0.8))
+            .flat_map(|triangle| triangle.to_vec()).collect();
+        let mut mesh = self::mesh(positions, indices);
+        let report = repair(&mut mesh, &RepairOptions {max_hole_size: 20, ..Default::default()}).unwrap();
+
+        assert_eq!(report.filled_holes.len(), 1);
+        assert_eq!(report.filled_holes[0].len(), 16);
+        // A flat triangulation of the hole has 14 faces, the refined filling has more
+        assert!(report.added_faces.len() > 14);
+        assert!(validate(&mesh).is_valid());
+    }
+}
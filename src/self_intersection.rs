@@ -0,0 +1,666 @@
+//! Detection and resolution of self-intersections, ie. faces of a mesh which intersect other faces of the same mesh.
+//!
+//! Self-intersecting meshes, for example scanned or CAD exported models, cannot be split reliably at their intersection with another mesh,
+//! so they should be resolved before stitching.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::IndexedMesh;
+use crate::ray_casting::{RayCaster, are_coplanar, ray_directions, ray_triangle_intersection};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error),
+    /// An intersection segment could not be inserted in the re-triangulation of a face as a chain of edges, for example because of round-off errors.
+    InsertSegment {message: String}
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// The changes made to a mesh by [resolve_self_intersections](fn.resolve_self_intersections.html).
+///
+#[derive(Debug, Clone, Default)]
+pub struct ResolveReport {
+    /// The number of pairs of faces which cross each other.
+    pub no_intersecting_pairs: usize,
+    /// The number of pairs of coplanar faces which overlap.
+    pub no_coplanar_pairs: usize,
+    /// The number of faces which are re-triangulated along the intersection curves.
+    pub no_split_faces: usize,
+    /// The number of faces, after re-triangulation, which are inside the mesh and therefore removed.
+    pub no_removed_faces: usize
+}
+
+///
+/// Returns all pairs of faces of the mesh which intersect each other, found using a bounding volume hierarchy.
+/// The pairs are sorted in the order of the faces in the mesh. Faces only meeting at the vertices or the edge they share are not reported,
+/// but coplanar faces which overlap are.
+///
+pub fn self_intersections(mesh: &Mesh) -> Vec<(FaceID, FaceID)>
+{
+    RayCaster::new(mesh).intersecting_faces()
+}
+
+///
+/// Resolves the self-intersections of the mesh, so the result is the outer hull of the mesh.
+/// The intersecting faces are re-triangulated along the intersection curves and the pieces which are inside the mesh are removed.
+/// Whether a piece is inside is decided by the winding number at a point just outside the piece, counted as the signed number of crossings
+/// of the faces along a ray from the point, where the majority of three rays in different directions decides.
+/// Coplanar faces which overlap are re-triangulated along the edges of each other, after which the overlapping pieces with the same orientation
+/// are reduced to one piece and the overlapping pieces with opposite orientations, ie. where the mesh touches itself, are removed.
+/// The mesh must be closed and the normals must point outwards, see [repair](../repair/fn.repair.html).
+/// If the mesh has no self-intersections, it is not changed, otherwise it is rebuilt, so the vertex and face IDs are not preserved.
+///
+/// # Errors
+///
+/// Returns [InsertSegment](enum.Error.html#variant.InsertSegment) if the re-triangulation of a face fails.
+///
+pub fn resolve_self_intersections(mesh: &mut Mesh) -> Result<ResolveReport, Error>
+{
+    let split = split_at_intersections(&IndexedMesh::new(mesh), &|_, _| true)?;
+    let mut report = ResolveReport {no_intersecting_pairs: split.intersecting_pairs.len(), no_coplanar_pairs: split.coplanar_pairs.len(),
+        no_split_faces: split.no_split_triangles, ..Default::default()};
+    if split.intersecting_pairs.len() == 0 && split.coplanar_pairs.len() == 0 {
+        return Ok(report);
+    }
+    let mut indexed = split.mesh;
+    let no_triangles = indexed.triangles.len();
+
+    // Remove the triangles which are inside the mesh
+    let normals: Vec<Vec3> = (0..indexed.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = indexed.triangle_positions(triangle);
+        (p1 - p0).cross(p2 - p0)
+    }).collect();
+    let ray_caster = RayCaster::from_triangles((0..indexed.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = indexed.triangle_positions(triangle);
+        (triangle, p0, p1, p2)
+    }).collect());
+    let directions = ray_directions();
+    let mut is_outside = Vec::with_capacity(indexed.triangles.len());
+    for triangle in 0..indexed.triangles.len() {
+        let normal = normals[triangle];
+        if normal.magnitude2() == 0.0 {
+            is_outside.push(false);
+            continue;
+        }
+        let (p0, p1, p2) = indexed.triangle_positions(triangle);
+        let size = p0.distance(p1).max(p1.distance(p2)).max(p2.distance(p0));
+        let point = (p0 + p1 + p2) / 3.0 + normal.normalize() * 1.0e-4 * size;
+        let no_outside = directions.iter().filter(|direction| {
+            let winding_number: i32 = ray_caster.hits(&point, direction, 0.0, std::f64::MAX).iter()
+                .map(|(hit, _)| if normals[*hit].dot(**direction) > 0.0 { 1 } else { -1 }).sum();
+            winding_number <= 0
+        }).count();
+        is_outside.push(no_outside >= 2);
+    }
+    indexed.triangles = indexed.triangles.iter().zip(is_outside.iter()).filter(|(_, is_outside)| **is_outside).map(|(triangle, _)| *triangle).collect();
+    // The winding numbers count all the overlapping coplanar pieces, so they are reduced afterwards
+    remove_overlapping_triangles(&mut indexed);
+    report.no_removed_faces = no_triangles - indexed.triangles.len();
+
+    *mesh = indexed.to_mesh()?;
+    Ok(report)
+}
+
+///
+/// The result of [split_at_intersections](fn.split_at_intersections.html).
+///
+pub(crate) struct Split {
+    /// The split mesh, where the intersection curves are chains of edges shared by the triangles on both sides of the curves.
+    pub mesh: IndexedMesh,
+    /// The triangle in the input mesh which each triangle in the split mesh is a part of.
+    pub parents: Vec<usize>,
+    /// The pairs of triangles in the input mesh which intersect in a line segment, where each triangle crosses the plane of the other,
+    /// ie. not including triangles which only touch each other. The mesh is also split where triangles touch, if there are any intersections.
+    pub intersecting_pairs: Vec<(usize, usize)>,
+    /// The pairs of coplanar triangles in the input mesh which overlap.
+    pub coplanar_pairs: Vec<(usize, usize)>,
+    /// The number of triangles in the input mesh which are re-triangulated.
+    pub no_split_triangles: usize
+}
+
+///
+/// Splits the triangles of the mesh along the line segments where they intersect each other, ignoring the pairs of triangles
+/// for which `is_split_pair` returns false. The vertices on the intersection curves are merged, so the curves connect the triangles
+/// on both sides, which means the curves are non-manifold edges when two closed meshes are split at their intersection.
+/// Coplanar triangles which overlap are split along the parts of the edges of each other which are inside them.
+///
+pub(crate) fn split_at_intersections(mesh: &IndexedMesh, is_split_pair: &dyn Fn(usize, usize) -> bool) -> Result<Split, Error>
+{
+    let mut indexed = mesh.clone();
+    let ray_caster = RayCaster::from_triangles((0..indexed.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = indexed.triangle_positions(triangle);
+        (triangle, p0, p1, p2)
+    }).collect());
+
+    // Find the intersection segments in each triangle and the intersection points on the edges, which must be inserted in both neighbouring triangles
+    let mut intersecting_pairs = Vec::new();
+    let mut coplanar_pairs = Vec::new();
+    let mut segments: HashMap<usize, Vec<(Vec3, Vec3)>> = HashMap::new();
+    let mut edge_points: HashMap<(usize, usize), Vec<Vec3>> = HashMap::new();
+    for (triangle0, triangle1) in ray_caster.intersecting_faces() {
+        if !is_split_pair(triangle0, triangle1) {
+            continue;
+        }
+        let (positions0, positions1) = (indexed.triangle_positions(triangle0), indexed.triangle_positions(triangle1));
+        if are_coplanar(&positions0, &positions1) {
+            for (triangle, positions, other) in [(triangle0, &positions0, &positions1), (triangle1, &positions1, &positions0)].iter() {
+                let corners = [other.0, other.1, other.2];
+                for edge in 0..3 {
+                    if let Some(segment) = clip_to_triangle(&corners[edge], &corners[(edge + 1) % 3], positions) {
+                        segments.entry(*triangle).or_insert(Vec::new()).push(segment);
+                    }
+                }
+            }
+            coplanar_pairs.push((triangle0, triangle1));
+            continue;
+        }
+        let points = intersection_segment(&positions0, &positions1);
+        if let Some(((p, p_edge), (q, q_edge))) = points {
+            for (point, edge) in [(p, p_edge), (q, q_edge)].iter() {
+                if let Some((is_first, edge)) = edge {
+                    let triangle = if *is_first { triangle0 } else { triangle1 };
+                    let a = indexed.triangles[triangle][*edge];
+                    let b = indexed.triangles[triangle][(*edge + 1) % 3];
+                    edge_points.entry((a.min(b), a.max(b))).or_insert(Vec::new()).push(*point);
+                }
+            }
+            segments.entry(triangle0).or_insert(Vec::new()).push((p, q));
+            segments.entry(triangle1).or_insert(Vec::new()).push((p, q));
+            // Triangles which only touch, ie. where one of them is on one side of the plane of the other, are split, but do not intersect
+            if crosses_plane(&positions0, &positions1) && crosses_plane(&positions1, &positions0) {
+                intersecting_pairs.push((triangle0, triangle1));
+            }
+        }
+    }
+    if intersecting_pairs.len() == 0 && coplanar_pairs.len() == 0 {
+        let parents = (0..indexed.triangles.len()).collect();
+        return Ok(Split {mesh: indexed, parents, intersecting_pairs, coplanar_pairs, no_split_triangles: 0});
+    }
+
+    // Re-triangulate the triangles with intersection segments or intersection points on their edges
+    let mut triangles = Vec::new();
+    let mut parents = Vec::new();
+    let mut new_triangles = Vec::new();
+    let mut new_parents = Vec::new();
+    for (triangle, indices) in indexed.triangles.iter().enumerate() {
+        let edges = [(indices[0], indices[1]), (indices[1], indices[2]), (indices[2], indices[0])];
+        let points: Vec<Vec3> = edges.iter().flat_map(|(a, b)| edge_points.get(&((*a).min(*b), (*a).max(*b))).cloned().unwrap_or(Vec::new())).collect();
+        if points.len() == 0 && !segments.contains_key(&triangle) {
+            triangles.push(*indices);
+            parents.push(triangle);
+            continue;
+        }
+
+        let mut sub_triangulation = SubTriangulation::new(indexed.triangle_positions(triangle));
+        for point in points.iter() {
+            sub_triangulation.insert_point(point);
+        }
+        for (p, q) in segments.get(&triangle).map(|segments| segments.as_slice()).unwrap_or(&[]) {
+            sub_triangulation.insert_segment(p, q)?;
+        }
+
+        let offset = indexed.positions.len();
+        indexed.positions.extend(sub_triangulation.positions[3..].iter());
+        let mapping = |index: usize| if index < 3 { indices[index] } else { offset + index - 3 };
+        new_triangles.extend(sub_triangulation.triangles.iter().map(|[i0, i1, i2]| [mapping(*i0), mapping(*i1), mapping(*i2)]));
+        new_parents.extend(sub_triangulation.triangles.iter().map(|_| triangle));
+    }
+    let no_split_triangles = indexed.triangles.len() - triangles.len();
+    let first_new_triangle = triangles.len();
+    triangles.extend(new_triangles);
+    parents.extend(new_parents);
+    indexed.triangles = triangles;
+    let tolerance = 1.0e-9 * indexed.diagonal();
+    indexed.merge_close_vertices(tolerance);
+    fix_t_junctions(&mut indexed, &mut parents, first_new_triangle, tolerance);
+
+    let is_valid: Vec<bool> = indexed.triangles.iter().map(|[i0, i1, i2]| i0 != i1 && i1 != i2 && i2 != i0).collect();
+    indexed.triangles = indexed.triangles.iter().zip(is_valid.iter()).filter(|(_, is_valid)| **is_valid).map(|(triangle, _)| *triangle).collect();
+    parents = parents.iter().zip(is_valid.iter()).filter(|(_, is_valid)| **is_valid).map(|(parent, _)| *parent).collect();
+    Ok(Split {mesh: indexed, parents, intersecting_pairs, coplanar_pairs, no_split_triangles})
+}
+
+///
+/// Removes the triangles with the same vertices as another triangle, which are the result of splitting overlapping coplanar triangles.
+/// One of the triangles is kept if more of them have one orientation than the other, otherwise they are all removed.
+///
+fn remove_overlapping_triangles(mesh: &mut IndexedMesh)
+{
+    let mut groups: HashMap<[usize; 3], Vec<usize>> = HashMap::new();
+    for (triangle, indices) in mesh.triangles.iter().enumerate() {
+        let mut key = *indices;
+        key.sort();
+        groups.entry(key).or_insert(Vec::new()).push(triangle);
+    }
+    let mut is_removed = vec![false; mesh.triangles.len()];
+    for triangles in groups.values().filter(|triangles| triangles.len() > 1) {
+        let first = mesh.triangles[triangles[0]];
+        let is_same_orientation = |indices: &[usize; 3]| (0..3).any(|i| indices[i] == first[0] && indices[(i + 1) % 3] == first[1]);
+        let balance: i32 = triangles.iter().map(|triangle| if is_same_orientation(&mesh.triangles[*triangle]) { 1 } else { -1 }).sum();
+        let kept = if balance > 0 { triangles.iter().find(|triangle| is_same_orientation(&mesh.triangles[**triangle])) }
+            else if balance < 0 { triangles.iter().find(|triangle| !is_same_orientation(&mesh.triangles[**triangle])) }
+            else { None };
+        for triangle in triangles.iter() {
+            is_removed[*triangle] = Some(triangle) != kept;
+        }
+    }
+    mesh.triangles = mesh.triangles.iter().zip(is_removed.iter()).filter(|(_, is_removed)| !**is_removed).map(|(triangle, _)| *triangle).collect();
+}
+
+///
+/// Returns the part of the line segment between the two points which is inside the triangle, where the points must be in the plane of the triangle,
+/// or `None` if the part is a point or empty.
+///
+fn clip_to_triangle(p: &Vec3, q: &Vec3, triangle: &(Vec3, Vec3, Vec3)) -> Option<(Vec3, Vec3)>
+{
+    let corners = [triangle.0, triangle.1, triangle.2];
+    let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+    let size = corners[0].distance(corners[1]).max(corners[1].distance(corners[2])).max(corners[2].distance(corners[0]));
+    let tolerance = 1.0e-9 * size;
+    let (mut t_min, mut t_max) = (0.0, 1.0);
+    for edge in 0..3 {
+        // The inward normal of the edge in the plane of the triangle
+        let a = corners[edge];
+        let inward = normal.cross(corners[(edge + 1) % 3] - a).normalize();
+        let (distance_p, distance_q) = (inward.dot(p - a), inward.dot(q - a));
+        if distance_p < -tolerance && distance_q < -tolerance {
+            return None;
+        }
+        if (distance_p - distance_q).abs() > std::f64::EPSILON {
+            let t = distance_p / (distance_p - distance_q);
+            if distance_p < -tolerance { t_min = f64::max(t_min, t); }
+            if distance_q < -tolerance { t_max = f64::min(t_max, t); }
+        }
+    }
+    if (t_max - t_min) * p.distance(*q) <= tolerance {
+        return None;
+    }
+    Some((p + (q - p) * t_min, p + (q - p) * t_max))
+}
+
+///
+/// Splits the triangles which have a vertex of the triangles, starting from the given triangle, in the interior of one of their edges.
+/// The new triangles get the parent of the triangle they are split from.
+/// The points on the intersection curves are found independently in the triangles on each side of the curve, so a point inserted on one side
+/// is not necessarily a vertex on the other side.
+///
+fn fix_t_junctions(mesh: &mut IndexedMesh, parents: &mut Vec<usize>, first_triangle: usize, tolerance: f64)
+{
+    let ray_caster = RayCaster::from_triangles((0..mesh.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = mesh.triangle_positions(triangle);
+        (triangle, p0, p1, p2)
+    }).collect());
+    let mut vertices: Vec<usize> = mesh.triangles[first_triangle..].iter().flat_map(|triangle| triangle.iter().cloned()).collect();
+    vertices.sort();
+    vertices.dedup();
+
+    let offset = vec3(tolerance, tolerance, tolerance);
+    let mut edge_vertices: HashMap<usize, [Vec<(f64, usize)>; 3]> = HashMap::new();
+    for vertex in vertices {
+        let p = mesh.positions[vertex];
+        for triangle in ray_caster.overlapping(&(p - offset), &(p + offset)) {
+            let indices = mesh.triangles[triangle];
+            if indices.contains(&vertex) {
+                continue;
+            }
+            for edge in 0..3 {
+                let a = mesh.positions[indices[edge]];
+                let b = mesh.positions[indices[(edge + 1) % 3]];
+                let length2 = a.distance2(b);
+                if length2 == 0.0 {
+                    continue;
+                }
+                let t = (p - a).dot(b - a) / length2;
+                let t_tolerance = tolerance / length2.sqrt();
+                if t > t_tolerance && t < 1.0 - t_tolerance && (a + (b - a) * t).distance(p) <= tolerance {
+                    edge_vertices.entry(triangle).or_insert([Vec::new(), Vec::new(), Vec::new()])[edge].push((t, vertex));
+                }
+            }
+        }
+    }
+
+    for (triangle, mut vertices_on_edges) in edge_vertices {
+        let indices = mesh.triangles[triangle];
+        let mut polygon = Vec::new();
+        for edge in 0..3 {
+            vertices_on_edges[edge].sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            polygon.push(indices[edge]);
+            polygon.extend(vertices_on_edges[edge].iter().map(|(_, vertex)| *vertex));
+        }
+        let edges_with_vertices: Vec<usize> = (0..3).filter(|edge| vertices_on_edges[*edge].len() > 0).collect();
+        let mut fan = Vec::new();
+        if edges_with_vertices.len() == 1 {
+            // Fan from the corner opposite the edge
+            let corner = (edges_with_vertices[0] + 2) % 3;
+            let start = polygon.iter().position(|vertex| *vertex == indices[corner]).unwrap();
+            polygon.rotate_left(start);
+            for i in 1..polygon.len() - 1 {
+                fan.push([polygon[0], polygon[i], polygon[i + 1]]);
+            }
+        }
+        else {
+            // Fan from a new vertex at the center of the triangle
+            let (p0, p1, p2) = mesh.triangle_positions(triangle);
+            let center = mesh.positions.len();
+            mesh.positions.push((p0 + p1 + p2) / 3.0);
+            for i in 0..polygon.len() {
+                fan.push([center, polygon[i], polygon[(i + 1) % polygon.len()]]);
+            }
+        }
+        mesh.triangles[triangle] = fan[0];
+        mesh.triangles.extend(fan[1..].iter());
+        let parent = parents[triangle];
+        parents.extend(fan[1..].iter().map(|_| parent));
+    }
+}
+
+/// The location of an intersection point on an edge of one of the two triangles; whether it is the first triangle and the index of the edge.
+type EdgeLocation = Option<(bool, usize)>;
+
+///
+/// Returns the end points of the line segment where the two triangles intersect, each together with the edge it is located on, if any.
+///
+fn intersection_segment(triangle0: &(Vec3, Vec3, Vec3), triangle1: &(Vec3, Vec3, Vec3)) -> Option<((Vec3, EdgeLocation), (Vec3, EdgeLocation))>
+{
+    let mut points = Vec::new();
+    for (is_first, edges_of, other) in [(true, triangle0, triangle1), (false, triangle1, triangle0)].iter() {
+        let corners = [edges_of.0, edges_of.1, edges_of.2];
+        for edge in 0..3 {
+            let p0 = corners[edge];
+            let p1 = corners[(edge + 1) % 3];
+            if let Some(t) = ray_triangle_intersection(&p0, &(p1 - p0), other) {
+                if t <= 1.0 {
+                    points.push((p0 + (p1 - p0) * t, Some((*is_first, edge))));
+                }
+            }
+        }
+    }
+
+    let mut result = None;
+    let mut max_distance2 = 0.0;
+    for i in 0..points.len() {
+        for j in i+1..points.len() {
+            let distance2 = points[i].0.distance2(points[j].0);
+            if distance2 > max_distance2 {
+                max_distance2 = distance2;
+                result = Some((points[i], points[j]));
+            }
+        }
+    }
+    result
+}
+
+///
+/// Returns whether the triangle has corners strictly on both sides of the plane of the other triangle.
+///
+fn crosses_plane(triangle: &(Vec3, Vec3, Vec3), other: &(Vec3, Vec3, Vec3)) -> bool
+{
+    let (q0, q1, q2) = other;
+    let normal = (q1 - q0).cross(q2 - q0);
+    if normal.magnitude2() == 0.0 {
+        return false;
+    }
+    let normal = normal.normalize();
+    let size = q0.distance(*q1).max(q1.distance(*q2)).max(q2.distance(*q0));
+    let tolerance = 1.0e-9 * size;
+    let distances = [normal.dot(triangle.0 - q0), normal.dot(triangle.1 - q0), normal.dot(triangle.2 - q0)];
+    distances.iter().any(|distance| *distance > tolerance) && distances.iter().any(|distance| *distance < -tolerance)
+}
+
+///
+/// A triangulation of a triangle which is refined by inserting points and line segments in the plane of the triangle.
+/// The first three vertices are the corners of the triangle and the orientation of the sub-triangles is the same as the triangle.
+///
+struct SubTriangulation {
+    axes: (usize, usize),
+    tolerance: f64,
+    positions: Vec<Vec3>,
+    triangles: Vec<[usize; 3]>
+}
+
+impl SubTriangulation {
+    fn new(triangle: (Vec3, Vec3, Vec3)) -> Self
+    {
+        let (p0, p1, p2) = triangle;
+        let normal = (p1 - p0).cross(p2 - p0);
+        let axes = if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() { (1, 2) }
+            else if normal.y.abs() >= normal.z.abs() { (2, 0) } else { (0, 1) };
+        let size = p0.distance(p1).max(p1.distance(p2)).max(p2.distance(p0));
+        SubTriangulation {axes, tolerance: 1.0e-9 * size, positions: vec![p0, p1, p2], triangles: vec![[0, 1, 2]]}
+    }
+
+    fn point2(&self, p: &Vec3) -> (f64, f64)
+    {
+        (p[self.axes.0], p[self.axes.1])
+    }
+
+    /// Returns the barycentric coordinates of the point with respect to the sub-triangle in the projection plane.
+    fn barycentric_coordinates(&self, p: &Vec3, triangle: &[usize; 3]) -> (f64, f64, f64)
+    {
+        let (x, y) = self.point2(p);
+        let (x0, y0) = self.point2(&self.positions[triangle[0]]);
+        let (x1, y1) = self.point2(&self.positions[triangle[1]]);
+        let (x2, y2) = self.point2(&self.positions[triangle[2]]);
+        let area = (x1 - x0) * (y2 - y0) - (x2 - x0) * (y1 - y0);
+        if area == 0.0 {
+            return (-1.0, -1.0, -1.0);
+        }
+        let u = ((x1 - x) * (y2 - y) - (x2 - x) * (y1 - y)) / area;
+        let v = ((x2 - x) * (y0 - y) - (x0 - x) * (y2 - y)) / area;
+        (u, v, 1.0 - u - v)
+    }
+
+    ///
+    /// Inserts the point, by splitting the sub-triangle or edge it is located on, and returns the index of the vertex at the point.
+    ///
+    fn insert_point(&mut self, p: &Vec3) -> usize
+    {
+        let (x, y) = self.point2(p);
+        if let Some(index) = self.positions.iter().position(|q| {
+            let (qx, qy) = self.point2(q);
+            (qx - x).abs() <= self.tolerance && (qy - y).abs() <= self.tolerance
+        }) {
+            return index;
+        }
+
+        let (triangle, (u, v, w)) = self.triangles.iter().enumerate()
+            .map(|(index, triangle)| (index, self.barycentric_coordinates(p, triangle)))
+            .max_by(|(_, a), (_, b)| a.0.min(a.1).min(a.2).partial_cmp(&b.0.min(b.1).min(b.2)).unwrap())
+            .unwrap();
+        let [i0, i1, i2] = self.triangles[triangle];
+        let index = self.positions.len();
+        self.positions.push(*p);
+        let epsilon = 1.0e-9;
+        if u <= epsilon { self.split_edge(i1, i2, index); }
+        else if v <= epsilon { self.split_edge(i2, i0, index); }
+        else if w <= epsilon { self.split_edge(i0, i1, index); }
+        else {
+            self.triangles[triangle] = [i0, i1, index];
+            self.triangles.push([i1, i2, index]);
+            self.triangles.push([i2, i0, index]);
+        }
+        index
+    }
+
+    /// Splits the edge between the two vertices at the given vertex, ie. splits each sub-triangle with the edge into two.
+    fn split_edge(&mut self, a: usize, b: usize, index: usize)
+    {
+        let mut new_triangles = Vec::new();
+        for triangle in self.triangles.iter_mut() {
+            for i in 0..3 {
+                let (i0, i1, i2) = (triangle[i], triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+                if (i0 == a && i1 == b) || (i0 == b && i1 == a) {
+                    *triangle = [i0, index, i2];
+                    new_triangles.push([index, i1, i2]);
+                    break;
+                }
+            }
+        }
+        self.triangles.extend(new_triangles);
+    }
+
+    ///
+    /// Inserts the line segment by inserting its end points and the points where it crosses the edges and checks that the segment
+    /// is a chain of edges afterwards, ie. that each two consecutive vertices on the segment are connected by an edge.
+    ///
+    fn insert_segment(&mut self, p: &Vec3, q: &Vec3) -> Result<(), Error>
+    {
+        let start = self.insert_point(p);
+        let end = self.insert_point(q);
+        if start == end {
+            return Ok(());
+        }
+        let (px, py) = self.point2(&self.positions[start]);
+        let (qx, qy) = self.point2(&self.positions[end]);
+
+        let mut edges = HashSet::new();
+        for triangle in self.triangles.iter() {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                if a != start && a != end && b != start && b != end {
+                    edges.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        let epsilon = 1.0e-9;
+        let mut crossings = Vec::new();
+        for (a, b) in edges {
+            let (ax, ay) = self.point2(&self.positions[a]);
+            let (bx, by) = self.point2(&self.positions[b]);
+            let denominator = (qx - px) * (by - ay) - (qy - py) * (bx - ax);
+            if denominator.abs() <= std::f64::EPSILON {
+                continue;
+            }
+            let s = ((ax - px) * (by - ay) - (ay - py) * (bx - ax)) / denominator;
+            let t = ((ax - px) * (qy - py) - (ay - py) * (qx - px)) / denominator;
+            if s > epsilon && s < 1.0 - epsilon && t > epsilon && t < 1.0 - epsilon {
+                crossings.push((s, a, b));
+            }
+        }
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let p = self.positions[start];
+        let q = self.positions[end];
+        for (s, a, b) in crossings {
+            let index = self.positions.len();
+            self.positions.push(p + (q - p) * s);
+            self.split_edge(a, b, index);
+        }
+
+        // Check that the vertices on the segment, ordered along the segment, are connected by edges
+        let length2 = (qx - px) * (qx - px) + (qy - py) * (qy - py);
+        let mut chain: Vec<(f64, usize)> = (0..self.positions.len()).filter_map(|index| {
+            let (x, y) = self.point2(&self.positions[index]);
+            let s = ((x - px) * (qx - px) + (y - py) * (qy - py)) / length2;
+            let distance = ((x - px) * (qy - py) - (y - py) * (qx - px)).abs() / length2.sqrt();
+            if s >= -epsilon && s <= 1.0 + epsilon && distance <= self.tolerance { Some((s, index)) } else { None }
+        }).collect();
+        chain.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        for pair in chain.windows(2) {
+            let (a, b) = (pair[0].1, pair[1].1);
+            let is_edge = self.triangles.iter().any(|triangle| (0..3).any(|i| (triangle[i] == a && triangle[(i + 1) % 3] == b) || (triangle[i] == b && triangle[(i + 1) % 3] == a)));
+            if !is_edge {
+                return Err(Error::InsertSegment {message: format!("The segment from {:?} to {:?} is not a chain of edges after inserting it in the triangle {:?}!",
+                    p, q, (self.positions[0], self.positions[1], self.positions[2]))});
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::validation::validate;
+
+    /// Returns a mesh with the faces of both meshes.
+    fn combined(mesh0: &Mesh, mesh1: &Mesh) -> Mesh
+    {
+        let mut indexed = IndexedMesh::new(mesh0);
+        indexed.append(&IndexedMesh::new(mesh1));
+        indexed.to_mesh().unwrap()
+    }
+
+    #[test]
+    fn test_overlapping_cubes()
+    {
+        let mut mesh = combined(&cube(2), &translated(&cube(2), vec3(0.5, 0.4, 0.3)));
+        assert!(self_intersections(&mesh).len() > 0);
+        let report = resolve_self_intersections(&mut mesh).unwrap();
+
+        assert!(report.no_intersecting_pairs > 0 && report.no_removed_faces > 0);
+        let validation = validate(&mesh);
+        assert!(validation.is_closed());
+        assert_eq!(validation.self_intersections.len(), 0);
+        assert_eq!(validation.non_manifold_edges.len(), 0);
+        assert!((volume(&mesh) - (2.0 - 0.5 * 0.6 * 0.7)).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn test_no_self_intersections()
+    {
+        let mut mesh = sphere(1.0, 8);
+        let report = resolve_self_intersections(&mut mesh).unwrap();
+        assert_eq!(report.no_intersecting_pairs + report.no_coplanar_pairs + report.no_split_faces + report.no_removed_faces, 0);
+        assert_eq!(sorted_triangles(&mesh), sorted_triangles(&sphere(1.0, 8)));
+    }
+
+    #[test]
+    fn test_coplanar_overlap()
+    {
+        // The bottom and top sides of the cubes overlap in the same planes
+        let mut mesh = combined(&cube(1), &translated(&cube(1), vec3(0.5, 0.5, 0.0)));
+        let report = resolve_self_intersections(&mut mesh).unwrap();
+
+        assert!(report.no_coplanar_pairs > 0);
+        let validation = validate(&mesh);
+        assert!(validation.is_closed());
+        assert_eq!(validation.self_intersections.len(), 0);
+        assert_eq!(validation.non_manifold_edges.len(), 0);
+        assert_eq!(validation.duplicate_faces.len(), 0);
+        assert!((volume(&mesh) - 1.75).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn test_touching_cubes()
+    {
+        // The cubes touch at the overlapping part of the sides x = 1 and x = 1, which are removed
+        let mut mesh = combined(&cube(1), &translated(&cube(1), vec3(1.0, 0.5, 0.0)));
+        let report = resolve_self_intersections(&mut mesh).unwrap();
+
+        assert!(report.no_coplanar_pairs > 0);
+        let validation = validate(&mesh);
+        assert!(validation.is_closed());
+        assert_eq!(validation.non_manifold_edges.len(), 0);
+        assert!((volume(&mesh) - 2.0).abs() < 1.0e-9);
+        assert!(mesh.face_iter().all(|face_id| {
+            let center = mesh.face_center(face_id);
+            (center.x - 1.0).abs() > 1.0e-9 || center.y < 0.5 || center.y > 1.0
+        }));
+    }
+
+    #[test]
+    fn test_insert_segment()
+    {
+        let mut sub_triangulation = SubTriangulation::new((vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)));
+        sub_triangulation.insert_segment(&vec3(0.1, 0.1, 0.0), &vec3(0.6, 0.2, 0.0)).unwrap();
+        sub_triangulation.insert_segment(&vec3(0.3, 0.0, 0.0), &vec3(0.2, 0.5, 0.0)).unwrap();
+        let area: f64 = sub_triangulation.triangles.iter().map(|[i0, i1, i2]| {
+            let (p0, p1, p2) = (sub_triangulation.positions[*i0], sub_triangulation.positions[*i1], sub_triangulation.positions[*i2]);
+            0.5 * (p1 - p0).cross(p2 - p0).z
+        }).sum();
+        assert!((area - 0.5).abs() < 1.0e-12);
+        assert!(sub_triangulation.triangles.iter().all(|[i0, i1, i2]| {
+            let (p0, p1, p2) = (sub_triangulation.positions[*i0], sub_triangulation.positions[*i1], sub_triangulation.positions[*i2]);
+            (p1 - p0).cross(p2 - p0).z > 0.0
+        }));
+    }
+}
@@ -0,0 +1,344 @@
+//! Simplification (decimation) of a mesh by collapsing edges in the order given by the quadric error metric of Garland and Heckbert.
+//!
+//! Each vertex carries a quadric which measures the sum of the squared distances to the planes of the original faces around it.
+//! The edge whose collapse gives the smallest error is collapsed first, and the new vertex is placed where the error is smallest.
+
+use tri_mesh::prelude::*;
+use crate::editable_mesh::EditableMesh;
+use crate::features::preserved_edge_indices;
+use crate::indexed_mesh::IndexedMesh;
+use crate::welding::Tolerance;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// The weight of the planes through the preserved edges relative to the planes of the faces.
+const CONSTRAINT_WEIGHT: f64 = 1000.0;
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// Options for the simplification of a mesh, see [simplify](fn.simplify.html).
+///
+#[derive(Debug, Clone)]
+pub struct SimplificationOptions {
+    /// The simplification stops when the mesh has at most this number of faces.
+    pub target_no_faces: Option<usize>,
+    /// The simplification stops when the error of the next collapse is larger than this tolerance.
+    /// The error is the square root of the quadric error, ie. of the sum of the squared distances from the new vertex to the planes of the original faces around it.
+    pub max_error: Option<Tolerance>,
+    /// Whether to preserve the boundary edges. The vertices on the boundary only move along the boundary, and the corners of the boundary are kept.
+    pub preserve_boundaries: bool,
+    /// Edges, given by their end vertices, which are preserved in the same way as the boundary, for example the sharp edges found by [feature_edges](../features/fn.feature_edges.html).
+    pub feature_edges: Vec<(VertexID, VertexID)>,
+    /// If given, the sharp edges where the angle in radians between the face normals is larger than this threshold, see [feature_edges](../features/fn.feature_edges.html),
+    /// are preserved as well, for example with the threshold [DEFAULT_ANGLE_THRESHOLD](../features/constant.DEFAULT_ANGLE_THRESHOLD.html).
+    pub feature_angle: Option<f64>
+}
+
+impl Default for SimplificationOptions {
+    fn default() -> Self {
+        SimplificationOptions {target_no_faces: None, max_error: Some(Tolerance::Relative(1.0e-3)), preserve_boundaries: true, feature_edges: Vec::new(),
+            feature_angle: None}
+    }
+}
+
+///
+/// The changes made to a mesh by [simplify](fn.simplify.html).
+///
+#[derive(Debug, Clone, Default)]
+pub struct SimplificationReport {
+    pub no_collapsed_edges: usize,
+    pub no_removed_faces: usize,
+    /// The largest error of the collapsed edges, see [SimplificationOptions](struct.SimplificationOptions.html).
+    pub max_error: f64
+}
+
+///
+/// Simplifies the mesh by collapsing edges, the edge with the smallest quadric error first, until the target number of faces
+/// or the maximum error is reached, whichever comes first. If neither is given, edges are collapsed as long as possible.
+/// A collapse is only made if it keeps the mesh a two-manifold and does not fold any faces over. Vertices on non-manifold edges are never moved.
+/// If any edges are collapsed, the mesh is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn simplify(mesh: &mut Mesh, options: &SimplificationOptions) -> Result<SimplificationReport, Error>
+{
+    let indexed = IndexedMesh::new(mesh);
+    let feature_edges = preserved_edge_indices(mesh, &options.feature_edges, options.feature_angle);
+    let max_error = options.max_error.map(|tolerance| tolerance.distance(indexed.diagonal()));
+
+    let mut decimation = Decimation::new(&indexed, &feature_edges, options.preserve_boundaries);
+    let report = decimation.run(options.target_no_faces, max_error);
+    if report.no_collapsed_edges > 0 {
+        *mesh = decimation.mesh.to_indexed().to_mesh()?;
+    }
+    Ok(report)
+}
+
+/// A symmetric 4x4 matrix measuring the sum of squared distances to a set of planes, stored as the upper 3x3 part, the last column and the corner.
+#[derive(Debug, Clone, Copy)]
+struct Quadric {
+    a: [f64; 6],
+    b: [f64; 3],
+    c: f64
+}
+
+impl Quadric {
+    fn zero() -> Self
+    {
+        Quadric {a: [0.0; 6], b: [0.0; 3], c: 0.0}
+    }
+
+    /// The quadric of the plane through the point with the given unit normal, multiplied by the weight.
+    fn plane(normal: &Vec3, point: &Vec3, weight: f64) -> Self
+    {
+        let d = -normal.dot(*point);
+        Quadric {
+            a: [weight * normal.x * normal.x, weight * normal.x * normal.y, weight * normal.x * normal.z,
+                weight * normal.y * normal.y, weight * normal.y * normal.z, weight * normal.z * normal.z],
+            b: [weight * d * normal.x, weight * d * normal.y, weight * d * normal.z],
+            c: weight * d * d
+        }
+    }
+
+    fn add(&mut self, other: &Quadric)
+    {
+        for i in 0..6 { self.a[i] += other.a[i]; }
+        for i in 0..3 { self.b[i] += other.b[i]; }
+        self.c += other.c;
+    }
+
+    fn sum(&self, other: &Quadric) -> Quadric
+    {
+        let mut result = *self;
+        result.add(other);
+        result
+    }
+
+    fn error(&self, p: &Vec3) -> f64
+    {
+        let [xx, xy, xz, yy, yz, zz] = self.a;
+        let quadratic = xx * p.x * p.x + yy * p.y * p.y + zz * p.z * p.z + 2.0 * (xy * p.x * p.y + xz * p.x * p.z + yz * p.y * p.z);
+        (quadratic + 2.0 * (self.b[0] * p.x + self.b[1] * p.y + self.b[2] * p.z) + self.c).max(0.0)
+    }
+
+    /// Returns the point with the smallest error, if it is well defined, ie. if the planes are not (almost) parallel.
+    fn minimizer(&self) -> Option<Vec3>
+    {
+        let [xx, xy, xz, yy, yz, zz] = self.a;
+        let det = xx * (yy * zz - yz * yz) - xy * (xy * zz - yz * xz) + xz * (xy * yz - yy * xz);
+        let trace = xx + yy + zz;
+        if det.abs() <= 1.0e-9 * trace * trace * trace || trace <= 0.0 {
+            return None;
+        }
+        let (r0, r1, r2) = (-self.b[0], -self.b[1], -self.b[2]);
+        let x = r0 * (yy * zz - yz * yz) - xy * (r1 * zz - yz * r2) + xz * (r1 * yz - yy * r2);
+        let y = xx * (r1 * zz - yz * r2) - r0 * (xy * zz - yz * xz) + xz * (xy * r2 - r1 * xz);
+        let z = xx * (yy * r2 - r1 * yz) - xy * (xy * r2 - r1 * xz) + r0 * (xy * yz - yy * xz);
+        Some(vec3(x / det, y / det, z / det))
+    }
+}
+
+/// A planned collapse of an edge, where the removed vertex is merged into the kept vertex which is moved to the position.
+#[derive(Debug, Clone)]
+struct Collapse {
+    error: f64,
+    removed: usize,
+    kept: usize,
+    position: Vec3,
+    versions: (usize, usize)
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.error == other.error
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // Reversed, so the binary heap returns the collapse with the smallest error first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.error.partial_cmp(&self.error).unwrap_or(Ordering::Equal)
+    }
+}
+
+struct Decimation {
+    mesh: EditableMesh,
+    quadrics: Vec<Quadric>,
+    /// Incremented each time a vertex changes, so the planned collapses of the edges at the vertex become outdated.
+    versions: Vec<usize>,
+    heap: BinaryHeap<Collapse>
+}
+
+impl Decimation {
+    fn new(indexed: &IndexedMesh, feature_edges: &HashSet<(usize, usize)>, preserve_boundaries: bool) -> Self
+    {
+        let mesh = EditableMesh::new(indexed, feature_edges, preserve_boundaries);
+        let mut quadrics = vec![Quadric::zero(); mesh.positions.len()];
+        let normals: Vec<Option<Vec3>> = (0..mesh.triangles.len()).map(|triangle| {
+            let (p0, p1, p2) = mesh.triangle_positions(triangle);
+            let normal = (p1 - p0).cross(p2 - p0);
+            if normal.magnitude2() > 0.0 { Some(normal.normalize()) } else { None }
+        }).collect();
+        for (triangle, indices) in mesh.triangles.iter().enumerate() {
+            if let Some(normal) = normals[triangle] {
+                let plane = Quadric::plane(&normal, &mesh.positions[indices[0]], 1.0);
+                for index in indices.iter() {
+                    quadrics[*index].add(&plane);
+                }
+            }
+        }
+
+        // Planes perpendicular to the faces through the preserved edges keep the vertices on the edges
+        for (i0, i1) in mesh.edges().into_iter().filter(|(i0, i1)| mesh.is_constrained(*i0, *i1)) {
+            let direction = mesh.positions[i1] - mesh.positions[i0];
+            for triangle in mesh.edge_triangles(i0, i1) {
+                if let Some(normal) = normals[triangle] {
+                    let perpendicular = direction.cross(normal);
+                    if perpendicular.magnitude2() > 0.0 {
+                        let plane = Quadric::plane(&perpendicular.normalize(), &mesh.positions[i0], CONSTRAINT_WEIGHT);
+                        quadrics[i0].add(&plane);
+                        quadrics[i1].add(&plane);
+                    }
+                }
+            }
+        }
+
+        let no_vertices = mesh.positions.len();
+        Decimation {mesh, quadrics, versions: vec![0; no_vertices], heap: BinaryHeap::new()}
+    }
+
+    fn run(&mut self, target_no_faces: Option<usize>, max_error: Option<f64>) -> SimplificationReport
+    {
+        let mut report = SimplificationReport::default();
+        for (i0, i1) in self.mesh.edges() {
+            self.plan(i0, i1);
+        }
+
+        let mut no_faces = self.mesh.no_triangles();
+        while let Some(collapse) = self.heap.pop() {
+            if target_no_faces.map(|target| no_faces <= target).unwrap_or(false) {
+                break;
+            }
+            if collapse.versions != (self.versions[collapse.removed], self.versions[collapse.kept]) {
+                continue;
+            }
+            let error = collapse.error.sqrt();
+            if max_error.map(|max_error| error > max_error).unwrap_or(false) {
+                break;
+            }
+            if !self.mesh.is_collapsible(collapse.removed, collapse.kept, &collapse.position) {
+                continue;
+            }
+            no_faces -= self.collapse(&collapse);
+            report.no_collapsed_edges += 1;
+            report.max_error = report.max_error.max(error);
+        }
+        report.no_removed_faces = self.mesh.triangles.len() - no_faces;
+        report
+    }
+
+    /// Plans the collapse of the edge between the two vertices, if the preserved edges allow it, and adds it to the heap.
+    fn plan(&mut self, i0: usize, i1: usize)
+    {
+        let quadric = self.quadrics[i0].sum(&self.quadrics[i1]);
+        let (p0, p1) = (self.mesh.positions[i0], self.mesh.positions[i1]);
+        let mut candidates: Vec<(usize, usize, Vec3)> = self.mesh.collapse_directions(i0, i1).into_iter()
+            .map(|(removed, kept)| (removed, kept, self.mesh.positions[kept])).collect();
+        if self.mesh.is_free(i0) && self.mesh.is_free(i1) {
+            candidates.push((i0, i1, 0.5 * (p0 + p1)));
+            if let Some(p) = quadric.minimizer() {
+                // Far away minimizers are caused by almost parallel planes and are unreliable
+                if p.distance2(0.5 * (p0 + p1)) <= p0.distance2(p1) {
+                    candidates.push((i0, i1, p));
+                }
+            }
+        }
+
+        let best = candidates.into_iter().map(|(removed, kept, position)| (quadric.error(&position), removed, kept, position))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        if let Some((error, removed, kept, position)) = best {
+            self.heap.push(Collapse {error, removed, kept, position, versions: (self.versions[removed], self.versions[kept])});
+        }
+    }
+
+    /// Collapses the edge and plans the collapses of the edges at the kept vertex again. Returns the number of removed faces.
+    fn collapse(&mut self, collapse: &Collapse) -> usize
+    {
+        let quadric = self.quadrics[collapse.removed];
+        self.quadrics[collapse.kept].add(&quadric);
+        let no_removed = self.mesh.collapse(collapse.removed, collapse.kept, collapse.position);
+        self.versions[collapse.removed] += 1;
+        self.versions[collapse.kept] += 1;
+        for neighbour in self.mesh.neighbours(collapse.kept) {
+            self.plan(collapse.kept, neighbour);
+        }
+        no_removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_target_no_faces()
+    {
+        let mut mesh = sphere(1.0, 16);
+        assert_eq!(mesh.no_faces(), 960);
+        let report = simplify(&mut mesh, &SimplificationOptions {target_no_faces: Some(200), max_error: None, ..Default::default()}).unwrap();
+
+        assert!(mesh.no_faces() <= 200);
+        assert!(mesh.no_faces() >= 190);
+        assert_eq!(report.no_removed_faces, 960 - mesh.no_faces());
+        assert!(crate::validation::validate(&mesh).is_valid());
+        // The sphere is convex, so no face is flipped if all the normals point away from the center
+        for face_id in mesh.face_iter() {
+            let (p0, p1, p2) = mesh.face_positions(face_id);
+            assert!((p1 - p0).cross(p2 - p0).dot(p0 + p1 + p2) > 0.0);
+        }
+        let sphere_volume = 4.0 / 3.0 * std::f64::consts::PI;
+        assert!((volume(&mesh) - sphere_volume).abs() < 0.05 * sphere_volume);
+    }
+
+    #[test]
+    fn test_max_error()
+    {
+        let mut mesh = sphere(1.0, 16);
+        let report = simplify(&mut mesh, &SimplificationOptions {max_error: Some(Tolerance::Absolute(1.0e-6)), ..Default::default()}).unwrap();
+        assert_eq!(report.no_collapsed_edges, 0);
+        assert_eq!(mesh.no_faces(), 960);
+
+        let report = simplify(&mut mesh, &SimplificationOptions {max_error: Some(Tolerance::Absolute(0.02)), ..Default::default()}).unwrap();
+        assert!(report.no_collapsed_edges > 0);
+        assert!(report.max_error <= 0.02);
+    }
+
+    #[test]
+    fn test_flat_sides()
+    {
+        // The sides of the cube are flat, so they are simplified to (almost) two triangles each without any error.
+        // The order of the collapses with equal error is arbitrary, and a vertex on an edge of the cube may be left.
+        let mut mesh = cube(4);
+        let report = simplify(&mut mesh, &SimplificationOptions::default()).unwrap();
+        assert!(mesh.no_faces() <= 16);
+        assert!(report.max_error < 1.0e-9);
+        assert!((volume(&mesh) - 1.0).abs() < 1.0e-9);
+    }
+}
@@ -0,0 +1,288 @@
+//! Smoothing of a mesh, ie. removal of noise by moving the vertices without changing the connectivity.
+
+use tri_mesh::prelude::*;
+use crate::editable_mesh::EditableMesh;
+use crate::features::preserved_edge_indices;
+use crate::indexed_mesh::IndexedMesh;
+use std::collections::HashMap;
+
+///
+/// A smoothing method.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothingMethod {
+    /// Moves each vertex towards the average of its neighbours. Shrinks the mesh and makes the faces more regular.
+    UniformLaplacian,
+    /// Moves each vertex along the cotangent weighted Laplacian, ie. along the mean curvature normal, so vertices in flat regions stay in place. Shrinks the mesh.
+    CotangentLaplacian,
+    /// Alternates a shrinking and an inflating uniform Laplacian step, so the mesh does not shrink (Taubin).
+    /// Variations with a lower frequency than the pass band, typically 0.1, are kept.
+    Taubin {pass_band: f64},
+    /// Filters the face normals with weights depending on both the distance between the faces and the difference between the normals,
+    /// and then moves the vertices so the faces get the filtered normals. Keeps sharp edges (Zheng et al.).
+    /// Normals differing by more than the normal tolerance, typically 0.3, are considered to be on different sides of a sharp edge.
+    BilateralNormal {normal_tolerance: f64, no_normal_iterations: usize}
+}
+
+///
+/// Options for the smoothing of a mesh, see [smooth](fn.smooth.html).
+///
+#[derive(Debug, Clone)]
+pub struct SmoothingOptions {
+    pub method: SmoothingMethod,
+    pub no_iterations: usize,
+    /// The fraction of the Laplacian each vertex is moved in each iteration. Not used by bilateral normal filtering.
+    pub step_size: f64,
+    /// If given, the movement of each vertex is scaled by its weight between 0 and 1 and vertices without a weight are not moved.
+    /// For example, a mask smoothing only some vertices is given by the weight 1 for those vertices.
+    pub vertex_weights: Option<HashMap<VertexID, f64>>,
+    /// Whether to keep the vertices on the boundary in place.
+    pub preserve_boundaries: bool,
+    /// Edges, given by their end vertices, whose vertices are kept in place, for example the edges found by [feature_edges](../features/fn.feature_edges.html).
+    pub feature_edges: Vec<(VertexID, VertexID)>,
+    /// If given, the vertices of the sharp edges where the angle in radians between the face normals is larger than this threshold, see [feature_edges](../features/fn.feature_edges.html),
+    /// are kept in place as well, for example with the threshold [DEFAULT_ANGLE_THRESHOLD](../features/constant.DEFAULT_ANGLE_THRESHOLD.html).
+    pub feature_angle: Option<f64>
+}
+
+impl Default for SmoothingOptions {
+    fn default() -> Self {
+        SmoothingOptions {method: SmoothingMethod::Taubin {pass_band: 0.1}, no_iterations: 10, step_size: 0.5, vertex_weights: None,
+            preserve_boundaries: true, feature_edges: Vec::new(), feature_angle: None}
+    }
+}
+
+///
+/// Smooths the mesh using the method given in the options. Only the positions of the vertices change, so the vertex and face IDs are preserved.
+/// Vertices on non-manifold edges are kept in place.
+///
+pub fn smooth(mesh: &mut Mesh, options: &SmoothingOptions)
+{
+    let feature_edges = preserved_edge_indices(mesh, &options.feature_edges, options.feature_angle);
+    let mut editable = EditableMesh::new(&IndexedMesh::new(mesh), &feature_edges, options.preserve_boundaries);
+    let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+    let weights: Vec<f64> = vertex_ids.iter().enumerate().map(|(index, vertex_id)| {
+        if !editable.is_free(index) || editable.vertex_triangles[index].len() == 0 {
+            return 0.0;
+        }
+        match options.vertex_weights {
+            Some(ref vertex_weights) => vertex_weights.get(vertex_id).map(|weight| weight.max(0.0).min(1.0)).unwrap_or(0.0),
+            None => 1.0
+        }
+    }).collect();
+
+    match options.method {
+        SmoothingMethod::UniformLaplacian => {
+            for _ in 0..options.no_iterations {
+                laplacian_step(&mut editable, &weights, options.step_size, false);
+            }
+        },
+        SmoothingMethod::CotangentLaplacian => {
+            for _ in 0..options.no_iterations {
+                laplacian_step(&mut editable, &weights, options.step_size, true);
+            }
+        },
+        SmoothingMethod::Taubin {pass_band} => {
+            let inflation = 1.0 / (pass_band - 1.0 / options.step_size);
+            for _ in 0..options.no_iterations {
+                laplacian_step(&mut editable, &weights, options.step_size, false);
+                laplacian_step(&mut editable, &weights, inflation, false);
+            }
+        },
+        SmoothingMethod::BilateralNormal {normal_tolerance, no_normal_iterations} => {
+            let normals = filtered_normals(&editable, normal_tolerance, no_normal_iterations);
+            for _ in 0..options.no_iterations {
+                fit_to_normals(&mut editable, &weights, &normals);
+            }
+        }
+    }
+
+    for (index, vertex_id) in vertex_ids.iter().enumerate() {
+        if weights[index] > 0.0 {
+            mesh.move_vertex_to(*vertex_id, editable.positions[index]);
+        }
+    }
+}
+
+/// Moves each vertex the step size times its weight along the uniform or cotangent weighted Laplacian, normalized by the sum of the weights.
+fn laplacian_step(mesh: &mut EditableMesh, weights: &[f64], step_size: f64, cotangent: bool)
+{
+    let positions: Vec<Vec3> = (0..mesh.positions.len()).map(|vertex| {
+        let p = mesh.positions[vertex];
+        if weights[vertex] == 0.0 {
+            return p;
+        }
+        let mut sum = vec3(0.0, 0.0, 0.0);
+        let mut total_weight = 0.0;
+        for neighbour in mesh.neighbours(vertex) {
+            let weight = if cotangent { mesh.cotangent_weight(vertex, neighbour) } else { 1.0 };
+            sum += weight * (mesh.positions[neighbour] - p);
+            total_weight += weight;
+        }
+        if total_weight <= std::f64::EPSILON {
+            return p;
+        }
+        p + step_size * weights[vertex] * sum / total_weight
+    }).collect();
+    mesh.positions = positions;
+}
+
+/// Returns the face normals after bilateral filtering over the faces sharing a vertex, where the spatial scale is the average distance between the centers of neighbouring faces.
+fn filtered_normals(mesh: &EditableMesh, normal_tolerance: f64, no_iterations: usize) -> Vec<Vec3>
+{
+    let no_triangles = mesh.triangles.len();
+    let mut normals = Vec::with_capacity(no_triangles);
+    let mut areas = Vec::with_capacity(no_triangles);
+    let mut centers = Vec::with_capacity(no_triangles);
+    for triangle in 0..no_triangles {
+        let (p0, p1, p2) = mesh.triangle_positions(triangle);
+        let normal = (p1 - p0).cross(p2 - p0);
+        areas.push(0.5 * normal.magnitude());
+        normals.push(if normal.magnitude2() > 0.0 { normal.normalize() } else { normal });
+        centers.push((p0 + p1 + p2) / 3.0);
+    }
+    let neighbourhoods: Vec<Vec<usize>> = (0..no_triangles).map(|triangle| {
+        let mut neighbourhood: Vec<usize> = mesh.triangles[triangle].iter().flat_map(|index| mesh.vertex_triangles[*index].iter().cloned()).collect();
+        neighbourhood.sort();
+        neighbourhood.dedup();
+        neighbourhood
+    }).collect();
+
+    let mut total_distance = 0.0;
+    let mut no_distances = 0;
+    for (triangle, neighbourhood) in neighbourhoods.iter().enumerate() {
+        for neighbour in neighbourhood.iter().filter(|neighbour| **neighbour != triangle) {
+            total_distance += centers[triangle].distance(centers[*neighbour]);
+            no_distances += 1;
+        }
+    }
+    if no_distances == 0 {
+        return normals;
+    }
+    let distance_tolerance = total_distance / no_distances as f64;
+
+    for _ in 0..no_iterations {
+        normals = (0..no_triangles).map(|triangle| {
+            let mut sum = vec3(0.0, 0.0, 0.0);
+            for neighbour in neighbourhoods[triangle].iter() {
+                let distance = centers[triangle].distance(centers[*neighbour]) / distance_tolerance;
+                let difference = (normals[triangle] - normals[*neighbour]).magnitude() / normal_tolerance;
+                sum += areas[*neighbour] * (-0.5 * distance * distance).exp() * (-0.5 * difference * difference).exp() * normals[*neighbour];
+            }
+            if sum.magnitude2() > 0.0 { sum.normalize() } else { normals[triangle] }
+        }).collect();
+    }
+    normals
+}
+
+/// Moves each vertex, scaled by its weight, so the faces around it are closer to the planes through their centers with the given normals.
+fn fit_to_normals(mesh: &mut EditableMesh, weights: &[f64], normals: &[Vec3])
+{
+    let centers: Vec<Vec3> = (0..mesh.triangles.len()).map(|triangle| {
+        let (p0, p1, p2) = mesh.triangle_positions(triangle);
+        (p0 + p1 + p2) / 3.0
+    }).collect();
+    let positions: Vec<Vec3> = (0..mesh.positions.len()).map(|vertex| {
+        let p = mesh.positions[vertex];
+        if weights[vertex] == 0.0 {
+            return p;
+        }
+        let triangles = &mesh.vertex_triangles[vertex];
+        let mut displacement = vec3(0.0, 0.0, 0.0);
+        for triangle in triangles.iter() {
+            let normal = normals[*triangle];
+            displacement += normal * normal.dot(centers[*triangle] - p);
+        }
+        p + weights[vertex] * displacement / triangles.len() as f64
+    }).collect();
+    mesh.positions = positions;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Returns a unit sphere where each vertex is moved along the normal by a deterministic noise of at most the amplitude.
+    fn noisy_sphere(amplitude: f64) -> Mesh
+    {
+        let mut mesh = sphere(1.0, 16);
+        let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+        for (index, vertex_id) in vertex_ids.into_iter().enumerate() {
+            let p = mesh.vertex_position(vertex_id);
+            let noise = ((index as f64 * 12.9898).sin() * 43758.5453).fract();
+            mesh.move_vertex_to(vertex_id, p * (1.0 + amplitude * noise));
+        }
+        mesh
+    }
+
+    /// Returns the average angle between the normals of neighbouring faces.
+    fn roughness(mesh: &Mesh) -> f64
+    {
+        let indexed = IndexedMesh::new(mesh);
+        let normal = |triangle: usize| {
+            let (p0, p1, p2) = indexed.triangle_positions(triangle);
+            (p1 - p0).cross(p2 - p0).normalize()
+        };
+        let angles: Vec<f64> = indexed.edge_triangles().values().map(|triangles| normal(triangles[0].0).dot(normal(triangles[1].0)).min(1.0).acos()).collect();
+        angles.iter().sum::<f64>() / angles.len() as f64
+    }
+
+    #[test]
+    fn test_taubin_keeps_volume()
+    {
+        let mut mesh = noisy_sphere(0.05);
+        let (noisy_volume, noisy_roughness) = (volume(&mesh), roughness(&mesh));
+        smooth(&mut mesh, &SmoothingOptions::default());
+        assert!((volume(&mesh) - noisy_volume).abs() < 0.02 * noisy_volume);
+        assert!(roughness(&mesh) < 0.5 * noisy_roughness);
+    }
+
+    #[test]
+    fn test_laplacian_shrinks()
+    {
+        for method in [SmoothingMethod::UniformLaplacian, SmoothingMethod::CotangentLaplacian].iter() {
+            let mut mesh = noisy_sphere(0.05);
+            let (noisy_volume, noisy_roughness) = (volume(&mesh), roughness(&mesh));
+            smooth(&mut mesh, &SmoothingOptions {method: *method, ..Default::default()});
+            assert!(volume(&mesh) < 0.95 * noisy_volume);
+            assert!(roughness(&mesh) < 0.5 * noisy_roughness);
+        }
+    }
+
+    #[test]
+    fn test_bilateral_normal_keeps_sharp_edges()
+    {
+        // Returns the average deviation of the face normals from the closest axis
+        let normal_deviation = |mesh: &Mesh| mesh.face_iter().map(|face_id| {
+            let normal = mesh.face_normal(face_id);
+            1.0 - normal.x.abs().max(normal.y.abs()).max(normal.z.abs())
+        }).sum::<f64>() / mesh.no_faces() as f64;
+
+        let mut mesh = cube(4);
+        let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+        for (index, vertex_id) in vertex_ids.into_iter().enumerate() {
+            let noise = |seed: f64| ((index as f64 * 12.9898 + seed).sin() * 43758.5453).fract();
+            let p = mesh.vertex_position(vertex_id);
+            mesh.move_vertex_to(vertex_id, p + 0.01 * vec3(noise(0.0), noise(1.0), noise(2.0)));
+        }
+        let noisy_deviation = normal_deviation(&mesh);
+        smooth(&mut mesh, &SmoothingOptions {method: SmoothingMethod::BilateralNormal {normal_tolerance: 0.3, no_normal_iterations: 5}, ..Default::default()});
+        assert!(normal_deviation(&mesh) < 0.25 * noisy_deviation);
+        assert!((volume(&mesh) - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_vertex_weights()
+    {
+        let mut mesh = noisy_sphere(0.05);
+        let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+        let positions: Vec<Vec3> = vertex_ids.iter().map(|vertex_id| mesh.vertex_position(*vertex_id)).collect();
+        // Only the vertices in the upper half are smoothed
+        let vertex_weights = vertex_ids.iter().zip(positions.iter()).filter(|(_, p)| p.z > 0.0).map(|(vertex_id, _)| (*vertex_id, 1.0)).collect();
+        smooth(&mut mesh, &SmoothingOptions {vertex_weights: Some(vertex_weights), ..Default::default()});
+        for (vertex_id, p) in vertex_ids.iter().zip(positions.iter()) {
+            assert_eq!(mesh.vertex_position(*vertex_id) == *p, p.z <= 0.0);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+//! Stitching of one or more source meshes, for example fires, into a model mesh.
+//!
+//! The model and the sources are split at their intersections, the parts of the sources which are outside the model and the parts of the model
+//! which are visible from the centre of a source are selected and the selected parts are joined along the intersection curves into one mesh.
+//! The sources may overlap each other, in which case the parts of a source inside another source are removed.
+//! Each face is labelled with the mesh it originates from before splitting and the label is carried through the stitching.
+
+use tri_mesh::prelude::*;
+use crate::attributes::Attributes;
+use crate::indexed_mesh::IndexedMesh;
+use crate::ray_casting::RayCaster;
+use crate::self_intersection::split_at_intersections;
+use std::collections::HashMap;
+
+const NO_INSIDE_SAMPLES: usize = 9;
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error),
+    SourceInsideModel {message: String},
+    SourceOutsideModel {message: String},
+    ModelInsideSource {message: String},
+    NoMeshesToMerge {message: String},
+    EmptyModel {message: String},
+    InvalidPenetrationDepth {message: String},
+    SelfIntersection(crate::self_intersection::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+impl From<crate::self_intersection::Error> for Error {
+    fn from(other: crate::self_intersection::Error) -> Self {
+        Error::SelfIntersection(other)
+    }
+}
+
+///
+/// The mesh a face in the result of a stitching originates from.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Origin {
+    Model,
+    /// The source with the given index in the list of sources.
+    Source(usize)
+}
+
+impl Origin {
+    ///
+    /// The name of the origin, for example used as the name of a patch when exporting.
+    ///
+    pub fn name(&self) -> String
+    {
+        match self {
+            Origin::Model => "model".to_string(),
+            Origin::Source(index) => format!("source{}", index)
+        }
+    }
+
+    ///
+    /// The tag of the origin in the face tag layer returned by [Stitched::attributes](struct.Stitched.html#method.attributes),
+    /// 0 for the model and `i + 1` for the source with index `i`.
+    ///
+    pub fn tag(&self) -> i32
+    {
+        match self {
+            Origin::Model => 0,
+            Origin::Source(index) => *index as i32 + 1
+        }
+    }
+
+    ///
+    /// Returns the origin with the given tag, see [tag](#method.tag). Negative tags are treated as the model.
+    ///
+    pub fn from_tag(tag: i32) -> Origin
+    {
+        if tag <= 0 { Origin::Model } else { Origin::Source(tag as usize - 1) }
+    }
+}
+
+///
+/// The result of a stitching, ie. the stitched mesh and the origin of each of its faces.
+///
+#[derive(Debug, Clone)]
+pub struct Stitched {
+    pub mesh: Mesh,
+    origins: HashMap<FaceID, Origin>
+}
+
+impl Stitched {
+    ///
+    /// Returns the origin of the given face in the stitched mesh.
+    ///
+    pub fn origin(&self, face_id: FaceID) -> Origin
+    {
+        self.origins[&face_id]
+    }
+
+    ///
+    /// Returns the faces in the stitched mesh which originates from the given origin.
+    ///
+    pub fn faces_from(&self, origin: Origin) -> Vec<FaceID>
+    {
+        self.mesh.face_iter().filter(|face_id| self.origins[face_id] == origin).collect()
+    }
+
+    ///
+    /// Returns the origin of each face as a face tag layer named `origin`, see [Origin::tag](enum.Origin.html#method.tag),
+    /// which can for example be saved using [save_with_attributes](../exporter/fn.save_with_attributes.html).
+    ///
+    pub fn attributes(&self) -> Attributes
+    {
+        let mut attributes = Attributes::new();
+        let layer = attributes.face_layer_mut::<i32>("origin");
+        for face_id in self.mesh.face_iter() {
+            layer.insert(face_id, self.origins[&face_id].tag());
+        }
+        attributes
+    }
+}
+
+///
+/// Stitches the source mesh into the model mesh. The source center is the point used to determine which parts to keep;
+/// the parts of the source which are not hidden behind the model and the parts of the model which are visible from the source center.
+/// The faces of the source gets the origin `Source(0)`.
+///
+/// # Errors
+///
+/// If no intersection segment is found between the faces of the source and the faces of the model, ie. they do not intersect
+/// (touching, for example along an edge or at coplanar faces, is not an intersection), the error describes how they are placed relative to each other:
+/// [SourceInsideModel](enum.Error.html#variant.SourceInsideModel) if the source is completely enclosed by the model,
+/// [ModelInsideSource](enum.Error.html#variant.ModelInsideSource) if the model is completely enclosed by the source and
+/// [SourceOutsideModel](enum.Error.html#variant.SourceOutsideModel) otherwise.
+/// If they intersect, but no parts are selected, [NoMeshesToMerge](enum.Error.html#variant.NoMeshesToMerge) is returned
+/// and if the selected parts cannot be built into a mesh, [MeshBuilder](enum.Error.html#variant.MeshBuilder) is returned.
+/// If the faces cannot be split along the intersection curves, [SelfIntersection](enum.Error.html#variant.SelfIntersection) is returned.
+///
+pub fn stitch(model: &Mesh, source: &Mesh, source_center: &Vec3) -> Result<Stitched, Error>
+{
+    stitch_sources(model, &[(source, *source_center)])
+}
+
+///
+/// Stitches several sources, each given by a mesh and a center, into the model mesh in one pass.
+/// The parts of each source inside another source are removed, so the sources are united, and the remaining parts of a source
+/// are selected using the center of that source as in [stitch](fn.stitch.html). The parts of the model are selected if they are visible
+/// from any of the centers, where the view is blocked by the united sources.
+/// The faces of the source with index `i` gets the origin `Source(i)`.
+///
+/// # Errors
+///
+/// Each source must intersect the model, a source which only intersects other sources would otherwise be kept as a floating component.
+/// If no intersection segment is found between the faces of a source and the faces of the model, the error is returned
+/// as in [stitch](fn.stitch.html) for the first such source and the message contains the index of the source.
+/// The other errors are as in [stitch](fn.stitch.html).
+///
+pub fn stitch_sources(model: &Mesh, sources: &[(&Mesh, Vec3)]) -> Result<Stitched, Error>
+{
+    if sources.len() == 0
+    {
+        return Err(Error::NoMeshesToMerge {message: format!("No sources are given!")});
+    }
+
+    // Split the model and the sources at their intersections, keeping track of the origin of each triangle
+    let mut combined = IndexedMesh::new(model);
+    let mut labels = vec![Origin::Model; combined.triangles.len()];
+    for (index, (source, _)) in sources.iter().enumerate() {
+        let indexed = IndexedMesh::new(source);
+        labels.extend(indexed.triangles.iter().map(|_| Origin::Source(index)));
+        combined.append(&indexed);
+    }
+    let split = split_at_intersections(&combined, &|triangle0, triangle1| labels[triangle0] != labels[triangle1])?;
+    let model_ray_caster = RayCaster::new(model);
+    let source_ray_casters: Vec<RayCaster> = sources.iter().map(|(source, _)| RayCaster::new(source)).collect();
+
+    for (index, (source, _)) in sources.iter().enumerate() {
+        let intersects_model = split.intersecting_pairs.iter().any(|(triangle0, triangle1)| {
+            let pair = (labels[*triangle0], labels[*triangle1]);
+            pair == (Origin::Model, Origin::Source(index)) || pair == (Origin::Source(index), Origin::Model)
+        });
+        if !intersects_model
+        {
+            return Err(non_intersecting_error(model, source, &model_ray_caster, &source_ray_casters[index], index));
+        }
+    }
+
+    let mesh = split.mesh;
+    let origins: Vec<Origin> = split.parents.iter().map(|parent| labels[*parent]).collect();
+    let parts = mesh.components_where(&|triangle0, triangle1| origins[triangle0] == origins[triangle1]);
+    let center = |triangle: usize| {
+        let (p0, p1, p2) = mesh.triangle_positions(triangle);
+        (p0 + p1 + p2) / 3.0
+    };
+
+    let epsilon = model_ray_caster.epsilon();
+
+    // Unite the sources, ie. remove the parts of each source which are inside another source
+    let parts: Vec<Vec<usize>> = parts.into_iter().filter(|part| match origins[part[0]] {
+        Origin::Source(index) => !source_ray_casters.iter().enumerate().any(|(other, ray_caster)| other != index && ray_caster.is_inside(&center(part[0]))),
+        Origin::Model => true
+    }).collect();
+    let union_ray_caster = RayCaster::from_triangles(parts.iter().filter(|part| origins[part[0]] != Origin::Model)
+        .flat_map(|part| part.iter().map(|triangle| {
+            let (p0, p1, p2) = mesh.triangle_positions(*triangle);
+            (*triangle, p0, p1, p2)
+        })).collect());
+
+    let mut triangles = Vec::new();
+    let mut triangle_origins = Vec::new();
+    for part in parts {
+        let is_visible = match origins[part[0]] {
+            Origin::Source(index) => part.iter().any(|triangle| !model_ray_caster.blocks_view(&center(*triangle), &sources[index].1, epsilon)),
+            Origin::Model => part.iter().any(|triangle| sources.iter().any(|(_, source_center)| !union_ray_caster.blocks_view(&center(*triangle), source_center, epsilon)))
+        };
+        if is_visible
+        {
+            triangles.extend(part.iter().map(|triangle| mesh.triangles[*triangle]));
+            triangle_origins.extend(part.iter().map(|triangle| origins[*triangle]));
+        }
+    }
+    if triangles.len() == 0
+    {
+        return Err(Error::NoMeshesToMerge {message: format!("The sources and the model intersect, but no parts are visible from the source centers!")});
+    }
+
+    let (mesh, faces) = IndexedMesh {positions: mesh.positions, triangles}.to_mesh_with_triangles()?;
+    let origins = faces.iter().map(|(face_id, triangle)| (*face_id, triangle_origins[*triangle])).collect();
+    Ok(Stitched {mesh, origins})
+}
+
+///
+/// The placement of a spherical source on the surface of a model, see [place_source](fn.place_source.html).
+///
+#[derive(Debug, Clone)]
+pub struct SourcePlacement {
+    /// The center of the source.
+    pub center: Vec3,
+    /// The normal of the model face the source is placed on, pointing towards the center.
+    pub normal: Vec3,
+    /// The model face the source is placed on.
+    pub face_id: FaceID,
+    /// The point on the model face nearest to the requested position.
+    pub surface_point: Vec3
+}
+
+///
+/// Places a spherical source with the given radius on the surface of the model, so that it intersects the surface by the given penetration depth.
+/// The requested position is projected onto the nearest face of the model and the center is moved away from the surface along the actual
+/// normal of that face, ie. the center is `surface_point + normal * (radius - penetration_depth)`.
+/// The approximate normal is only used to choose the side of the surface, so the face normal is flipped if it points away from it.
+/// This makes the placement robust to positions and normals which are slightly off, for example when read from a file.
+///
+/// # Errors
+///
+/// Returns [EmptyModel](enum.Error.html#variant.EmptyModel) if the model has no faces and
+/// [InvalidPenetrationDepth](enum.Error.html#variant.InvalidPenetrationDepth) if the penetration depth is not strictly between 0 and the diameter,
+/// in which case the source would not intersect the surface.
+///
+pub fn place_source(model: &Mesh, position: &Vec3, approximate_normal: &Vec3, radius: f64, penetration_depth: f64) -> Result<SourcePlacement, Error>
+{
+    if penetration_depth <= 0.0 || penetration_depth >= 2.0 * radius
+    {
+        return Err(Error::InvalidPenetrationDepth {message: format!("The penetration depth {} must be between 0 and the diameter {} of the source!", penetration_depth, 2.0 * radius)});
+    }
+    let (face_id, surface_point) = RayCaster::new(model).nearest_face(position)
+        .ok_or_else(|| Error::EmptyModel {message: format!("Cannot place a source on a model without faces!")})?;
+    let mut normal = model.face_normal(face_id);
+    if normal.dot(*approximate_normal) < 0.0
+    {
+        normal = -normal;
+    }
+    let center = surface_point + normal * (radius - penetration_depth);
+    Ok(SourcePlacement {center, normal, face_id, surface_point})
+}
+
+fn non_intersecting_error(model: &Mesh, source: &Mesh, model_ray_caster: &RayCaster, source_ray_caster: &RayCaster, index: usize) -> Error
+{
+    if is_mostly_inside(source, model_ray_caster)
+    {
+        Error::SourceInsideModel {message: format!("The source {} does not intersect the model, it is completely enclosed by the model!", index)}
+    }
+    else if is_mostly_inside(model, source_ray_caster)
+    {
+        Error::ModelInsideSource {message: format!("The source {} does not intersect the model, the model is completely enclosed by the source!", index)}
+    }
+    else {
+        Error::SourceOutsideModel {message: format!("The source {} does not intersect the model, it is completely outside the model!", index)}
+    }
+}
+
+///
+/// Returns whether the majority of a few vertices spread over the mesh are inside the closed mesh of the ray caster.
+/// A single vertex is not enough, since a vertex touching the other mesh is neither inside nor outside.
+///
+fn is_mostly_inside(mesh: &Mesh, ray_caster: &RayCaster) -> bool
+{
+    let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+    let step = (vertex_ids.len() / NO_INSIDE_SAMPLES).max(1);
+    let samples: Vec<Vec3> = vertex_ids.iter().step_by(step).take(NO_INSIDE_SAMPLES).map(|vertex_id| mesh.vertex_position(*vertex_id)).collect();
+    2 * samples.iter().filter(|position| ray_caster.is_inside(position)).count() > samples.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_casting::closest_point_on_triangle;
+    use crate::test_utils::*;
+
+    /// Returns whether each edge of the mesh has exactly two neighbouring faces.
+    fn is_closed(mesh: &Mesh) -> bool
+    {
+        IndexedMesh::new(mesh).edge_triangles().values().all(|triangles| triangles.len() == 2)
+    }
+
+    #[test]
+    fn test_stitch_labels_faces()
+    {
+        // A room with a fire on the floor, penetrating the floor
+        let room = cube(3);
+        let center = vec3(0.53, 0.46, 0.25);
+        let fire = translated(&sphere(0.37, 8), center);
+        let stitched = stitch(&room, &fire, &center).unwrap();
+
+        assert!(is_closed(&stitched.mesh));
+        let model_faces = stitched.faces_from(Origin::Model);
+        let fire_faces = stitched.faces_from(Origin::Source(0));
+        assert!(model_faces.len() > 0 && fire_faces.len() > 0);
+        assert_eq!(model_faces.len() + fire_faces.len(), stitched.mesh.no_faces());
+        for face_id in model_faces {
+            let (p0, p1, p2) = stitched.mesh.face_positions(face_id);
+            assert!(p0.z.abs() < 1.0e-9 && p1.z.abs() < 1.0e-9 && p2.z.abs() < 1.0e-9);
+            assert!(stitched.mesh.face_center(face_id).distance(center) < 0.37);
+        }
+        for face_id in fire_faces {
+            let (p0, p1, p2) = stitched.mesh.face_positions(face_id);
+            assert!(p0.z > -1.0e-9 && p1.z > -1.0e-9 && p2.z > -1.0e-9);
+            assert!(stitched.mesh.face_center(face_id).distance(center) > 0.3);
+        }
+
+        // The volume of the spherical cap above the floor, the faceted sphere is slightly smaller than the sphere
+        let height = 0.37 + 0.25;
+        let cap_volume = std::f64::consts::PI * height * height * (3.0 * 0.37 - height) / 3.0;
+        assert!((volume(&stitched.mesh) - cap_volume).abs() < 0.1 * cap_volume);
+
+        let attributes = stitched.attributes();
+        let tags = attributes.face_layer::<i32>("origin").unwrap();
+        assert!(stitched.mesh.face_iter().all(|face_id| Origin::from_tag(tags[&face_id]) == stitched.origin(face_id)));
+    }
+
+    #[test]
+    fn test_origin_names_and_tags()
+    {
+        assert_eq!(Origin::Model.name(), "model");
+        assert_eq!(Origin::Source(2).name(), "source2");
+        for origin in [Origin::Model, Origin::Source(0), Origin::Source(5)].iter() {
+            assert_eq!(Origin::from_tag(origin.tag()), *origin);
+        }
+    }
+
+    #[test]
+    fn test_stitch_non_intersecting()
+    {
+        let room = translated(&cube(2), vec3(-0.5, -0.5, -0.5));
+
+        let inside = sphere(0.2, 6);
+        match stitch(&room, &inside, &vec3(0.0, 0.0, 0.0)) {
+            Err(Error::SourceInsideModel {..}) => {},
+            result => panic!("Expected SourceInsideModel, got {:?}", result.map(|stitched| stitched.mesh.no_faces()))
+        }
+
+        let outside = translated(&sphere(0.2, 6), vec3(2.0, 0.0, 0.0));
+        match stitch(&room, &outside, &vec3(2.0, 0.0, 0.0)) {
+            Err(Error::SourceOutsideModel {..}) => {},
+            result => panic!("Expected SourceOutsideModel, got {:?}", result.map(|stitched| stitched.mesh.no_faces()))
+        }
+
+        let enclosing = sphere(2.0, 6);
+        match stitch(&room, &enclosing, &vec3(0.0, 0.0, 0.0)) {
+            Err(Error::ModelInsideSource {..}) => {},
+            result => panic!("Expected ModelInsideSource, got {:?}", result.map(|stitched| stitched.mesh.no_faces()))
+        }
+
+        // The first vertex of the sphere (the north pole) touches the ceiling, which must not decide the result
+        let touching_inside = translated(&sphere(0.2, 6), vec3(0.0, 0.0, 0.3));
+        match stitch(&room, &touching_inside, &vec3(0.0, 0.0, 0.3)) {
+            Err(Error::SourceInsideModel {..}) => {},
+            result => panic!("Expected SourceInsideModel, got {:?}", result.map(|stitched| stitched.mesh.no_faces()))
+        }
+
+        // A box touching the side of the room from outside shares the face, but does not intersect it
+        let touching = translated(&cube(1), vec3(0.5, -0.25, -0.25));
+        match stitch(&room, &touching, &vec3(1.0, 0.25, 0.25)) {
+            Err(Error::SourceOutsideModel {..}) => {},
+            result => panic!("Expected SourceOutsideModel, got {:?}", result.map(|stitched| stitched.mesh.no_faces()))
+        }
+    }
+
+    #[test]
+    fn test_stitch_overlapping_sources()
+    {
+        let room = cube(3);
+        let center0 = vec3(0.4, 0.5, 0.2);
+        let center1 = vec3(0.63, 0.52, 0.18);
+        let fire0 = translated(&sphere(0.3, 8), center0);
+        let fire1 = translated(&sphere(0.28, 8), center1);
+        let stitched = stitch_sources(&room, &[(&fire0, center0), (&fire1, center1)]).unwrap();
+
+        assert!(is_closed(&stitched.mesh));
+        let faces0 = stitched.faces_from(Origin::Source(0));
+        let faces1 = stitched.faces_from(Origin::Source(1));
+        assert!(faces0.len() > 0 && faces1.len() > 0);
+        let (ray_caster0, ray_caster1) = (RayCaster::new(&fire0), RayCaster::new(&fire1));
+        assert!(faces0.iter().all(|face_id| !ray_caster1.is_inside(&stitched.mesh.face_center(*face_id))));
+        assert!(faces1.iter().all(|face_id| !ray_caster0.is_inside(&stitched.mesh.face_center(*face_id))));
+        assert_eq!(stitched.faces_from(Origin::Model).len() + faces0.len() + faces1.len(), stitched.mesh.no_faces());
+    }
+
+    #[test]
+    fn test_stitch_source_not_touching_model()
+    {
+        // The second fire intersects the first fire, but not the model
+        let room = cube(3);
+        let center0 = vec3(0.5, 0.5, 0.2);
+        let center1 = vec3(0.5, 0.5, 0.6);
+        let fire0 = translated(&sphere(0.3, 8), center0);
+        let fire1 = translated(&sphere(0.3, 8), center1);
+        match stitch_sources(&room, &[(&fire0, center0), (&fire1, center1)]) {
+            Err(Error::SourceInsideModel {message}) => assert!(message.contains("source 1")),
+            result => panic!("Expected SourceInsideModel, got {:?}", result.map(|stitched| stitched.mesh.no_faces()))
+        }
+    }
+
+    #[test]
+    fn test_place_source()
+    {
+        // A position below the floor of the room is projected onto the floor and the source is placed inside the room
+        let room = cube(3);
+        let (radius, penetration_depth) = (0.3, 0.1);
+        for approximate_normal in [vec3(0.0, 0.0, 1.0), vec3(0.6, -0.3, 0.5), vec3(-0.9, 0.0, 0.1)].iter() {
+            let placement = place_source(&room, &vec3(0.4, 0.6, -0.2), approximate_normal, radius, penetration_depth).unwrap();
+            assert!(placement.surface_point.distance(vec3(0.4, 0.6, 0.0)) < 1.0e-9);
+            assert!(placement.normal.distance(vec3(0.0, 0.0, 1.0)) < 1.0e-9);
+            assert!(placement.center.distance(vec3(0.4, 0.6, radius - penetration_depth)) < 1.0e-9);
+            assert!(room.face_normal(placement.face_id).distance(vec3(0.0, 0.0, -1.0)) < 1.0e-9);
+        }
+
+        // A reversed normal only chooses the other side of the floor
+        let placement = place_source(&room, &vec3(0.4, 0.6, 0.05), &vec3(0.0, 0.0, -1.0), radius, penetration_depth).unwrap();
+        assert!(placement.normal.distance(vec3(0.0, 0.0, -1.0)) < 1.0e-9);
+        assert!(placement.center.distance(vec3(0.4, 0.6, penetration_depth - radius)) < 1.0e-9);
+    }
+
+    #[test]
+    fn test_place_source_on_curved_surface()
+    {
+        let model = sphere(1.0, 8);
+        let position = vec3(0.3, 0.2, 1.5);
+        let placement = place_source(&model, &position, &vec3(0.0, 0.0, 1.0), 0.2, 0.05).unwrap();
+
+        let distance = |face_id: FaceID| closest_point_on_triangle(&position, &model.face_positions(face_id)).distance(position);
+        let nearest = model.face_iter().map(|face_id| distance(face_id)).fold(std::f64::MAX, f64::min);
+        assert!((distance(placement.face_id) - nearest).abs() < 1.0e-12);
+        assert!((placement.surface_point.distance(position) - nearest).abs() < 1.0e-12);
+
+        // The center is on the actual face normal, not on the approximate normal or the direction to the position
+        let face_normal = model.face_normal(placement.face_id);
+        assert!(placement.normal.distance(face_normal) < 1.0e-9);
+        assert!(placement.center.distance(placement.surface_point + face_normal * 0.15) < 1.0e-9);
+    }
+
+    #[test]
+    fn test_place_source_invalid()
+    {
+        let room = cube(3);
+        let (position, normal) = (vec3(0.5, 0.5, 0.0), vec3(0.0, 0.0, 1.0));
+        for penetration_depth in [0.0, -0.1, 0.6, 1.0].iter() {
+            match place_source(&room, &position, &normal, 0.3, *penetration_depth) {
+                Err(Error::InvalidPenetrationDepth {..}) => {},
+                result => panic!("Expected InvalidPenetrationDepth, got {:?}", result)
+            }
+        }
+
+        let mut empty = cube(3);
+        for face_id in empty.face_iter() {
+            empty.remove_face(face_id);
+        }
+        match place_source(&empty, &position, &normal, 0.3, 0.1) {
+            Err(Error::EmptyModel {..}) => {},
+            result => panic!("Expected EmptyModel, got {:?}", result)
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Subdivision of a mesh, ie. refinement of the faces where the new vertices are placed so the mesh converges to a smooth surface.
+//!
+//! Crease edges are refined as curves, so the surface stays sharp along them. Boundary and non-manifold edges are always treated as creases.
+//! A vertex at the end of a single crease edge (a dart) is smoothed like any other vertex, while a vertex with three or more crease edges (a corner) is kept in place.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::{IndexedMesh, edge_indices};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// A subdivision scheme.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubdivisionScheme {
+    /// Each face is split into four and all vertices are smoothed (Loop).
+    Loop,
+    /// A vertex is inserted at the center of each face and the old edges are flipped, so the number of faces triples (Kobbelt).
+    /// Crease edges are split into three every second iteration.
+    Sqrt3,
+    /// Each face is split into four at the midpoints of the edges without moving any vertices.
+    Midpoint
+}
+
+///
+/// Options for the subdivision of a mesh, see [subdivide](fn.subdivide.html).
+///
+#[derive(Debug, Clone)]
+pub struct SubdivisionOptions {
+    pub scheme: SubdivisionScheme,
+    pub no_iterations: usize,
+    /// Edges, given by their end vertices, where the surface stays sharp, for example the sharp edges found by [feature_edges](../features/fn.feature_edges.html).
+    pub crease_edges: Vec<(VertexID, VertexID)>
+}
+
+impl Default for SubdivisionOptions {
+    fn default() -> Self {
+        SubdivisionOptions {scheme: SubdivisionScheme::Loop, no_iterations: 1, crease_edges: Vec::new()}
+    }
+}
+
+///
+/// Subdivides the mesh using the scheme given in the options.
+/// The mesh is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn subdivide(mesh: &mut Mesh, options: &SubdivisionOptions) -> Result<(), Error>
+{
+    let mut indexed = IndexedMesh::new(mesh);
+    let mut creases = edge_indices(mesh, &options.crease_edges);
+    for iteration in 0..options.no_iterations {
+        let edges = indexed.edge_triangles();
+        creases.retain(|edge| edges.contains_key(edge));
+        for (edge, triangles) in edges.iter() {
+            if triangles.len() != 2 || triangles[0].1 == triangles[1].1 {
+                creases.insert(*edge);
+            }
+        }
+        creases = match options.scheme {
+            SubdivisionScheme::Loop => split_triangles(&mut indexed, &edges, &creases, true),
+            SubdivisionScheme::Midpoint => split_triangles(&mut indexed, &edges, &creases, false),
+            SubdivisionScheme::Sqrt3 => sqrt3_step(&mut indexed, &edges, &creases, iteration % 2 == 1)
+        };
+    }
+    *mesh = indexed.to_mesh()?;
+    Ok(())
+}
+
+/// Splits each triangle into four at new vertices on the edges. If smooth, the vertices are placed using the Loop masks, otherwise at the midpoints.
+/// Returns the crease edges of the subdivided mesh.
+fn split_triangles(mesh: &mut IndexedMesh, edges: &HashMap<(usize, usize), Vec<(usize, bool)>>, creases: &HashSet<(usize, usize)>, smooth: bool)
+    -> HashSet<(usize, usize)>
+{
+    let old = mesh.positions.clone();
+    let mut edge_points = HashMap::new();
+    for ((i0, i1), triangles) in edges.iter() {
+        let mut position = 0.5 * (old[*i0] + old[*i1]);
+        if smooth && !creases.contains(&(*i0, *i1)) {
+            let opposite: Vec3 = triangles.iter().map(|(triangle, _)| old[opposite_vertex(&mesh.triangles[*triangle], *i0, *i1)]).sum();
+            position = 0.375 * (old[*i0] + old[*i1]) + 0.125 * opposite;
+        }
+        edge_points.insert((*i0, *i1), mesh.positions.len());
+        mesh.positions.push(position);
+    }
+
+    if smooth {
+        let neighbours = vertex_neighbours(old.len(), edges);
+        let crease_neighbours = vertex_neighbours(old.len(), creases.iter().map(|edge| (edge, ())));
+        for vertex in 0..old.len() {
+            mesh.positions[vertex] = match crease_neighbours[vertex].len() {
+                0 | 1 if neighbours[vertex].len() > 0 => {
+                    let n = neighbours[vertex].len() as f64;
+                    let c = 0.375 + 0.25 * (2.0 * std::f64::consts::PI / n).cos();
+                    let beta = (0.625 - c * c) / n;
+                    let sum: Vec3 = neighbours[vertex].iter().map(|neighbour| old[*neighbour]).sum();
+                    (1.0 - n * beta) * old[vertex] + beta * sum
+                },
+                2 => 0.75 * old[vertex] + 0.125 * (old[crease_neighbours[vertex][0]] + old[crease_neighbours[vertex][1]]),
+                _ => old[vertex]
+            };
+        }
+    }
+
+    let edge_point = |a: usize, b: usize| edge_points[&(a.min(b), a.max(b))];
+    mesh.triangles = mesh.triangles.iter().flat_map(|[a, b, c]| {
+        let (ab, bc, ca) = (edge_point(*a, *b), edge_point(*b, *c), edge_point(*c, *a));
+        vec![[*a, ab, ca], [*b, bc, ab], [*c, ca, bc], [ab, bc, ca]]
+    }).collect();
+
+    let mut new_creases = HashSet::new();
+    for (i0, i1) in creases.iter() {
+        let point = edge_point(*i0, *i1);
+        new_creases.insert((*i0, point));
+        new_creases.insert((*i1, point));
+    }
+    new_creases
+}
+
+/// Inserts a vertex at the center of each triangle, flips the old edges which are not creases and smooths the old vertices.
+/// If the creases are trisected, the triangles next to a crease are not given a center, instead the crease edges are split into three.
+/// Returns the crease edges of the subdivided mesh.
+fn sqrt3_step(mesh: &mut IndexedMesh, edges: &HashMap<(usize, usize), Vec<(usize, bool)>>, creases: &HashSet<(usize, usize)>, trisect_creases: bool)
+    -> HashSet<(usize, usize)>
+{
+    let old = mesh.positions.clone();
+    let neighbours = vertex_neighbours(old.len(), edges);
+    let crease_neighbours = vertex_neighbours(old.len(), creases.iter().map(|edge| (edge, ())));
+    let is_crease = |a: usize, b: usize| creases.contains(&(a.min(b), a.max(b)));
+    let no_crease_edges: Vec<usize> = mesh.triangles.iter()
+        .map(|[a, b, c]| if trisect_creases { [(*a, *b), (*b, *c), (*c, *a)].iter().filter(|(x, y)| is_crease(*x, *y)).count() } else { 0 })
+        .collect();
+
+    // The trisection points of the crease edges, using the ternary cubic B-spline masks, the point nearest the smallest index first
+    let mut trisections = HashMap::new();
+    if trisect_creases {
+        let beyond = |vertex: usize, other: usize| {
+            if crease_neighbours[vertex].len() == 2 {
+                old[*crease_neighbours[vertex].iter().find(|neighbour| **neighbour != other).unwrap()]
+            }
+            else {
+                2.0 * old[vertex] - old[other]
+            }
+        };
+        for (i0, i1) in creases.iter() {
+            let q0 = (16.0 * old[*i0] + 10.0 * old[*i1] + beyond(*i0, *i1)) / 27.0;
+            let q1 = (16.0 * old[*i1] + 10.0 * old[*i0] + beyond(*i1, *i0)) / 27.0;
+            trisections.insert((*i0, *i1), (mesh.positions.len(), mesh.positions.len() + 1));
+            mesh.positions.push(q0);
+            mesh.positions.push(q1);
+        }
+    }
+
+    let positions = &mut mesh.positions;
+    let centers: Vec<Option<usize>> = mesh.triangles.iter().zip(no_crease_edges.iter()).map(|([a, b, c], no_crease_edges)| {
+        if *no_crease_edges == 1 {
+            None
+        }
+        else {
+            positions.push((old[*a] + old[*b] + old[*c]) / 3.0);
+            Some(positions.len() - 1)
+        }
+    }).collect();
+
+    for vertex in 0..old.len() {
+        mesh.positions[vertex] = match crease_neighbours[vertex].len() {
+            0 | 1 if neighbours[vertex].len() > 0 => {
+                let n = neighbours[vertex].len() as f64;
+                let alpha = (4.0 - 2.0 * (2.0 * std::f64::consts::PI / n).cos()) / 9.0;
+                let sum: Vec3 = neighbours[vertex].iter().map(|neighbour| old[*neighbour]).sum();
+                (1.0 - alpha) * old[vertex] + alpha / n * sum
+            },
+            2 if trisect_creases => (19.0 * old[vertex] + 4.0 * (old[crease_neighbours[vertex][0]] + old[crease_neighbours[vertex][1]])) / 27.0,
+            _ => old[vertex]
+        };
+    }
+
+    let mut triangles = Vec::new();
+    for ((i0, i1), edge_triangles) in edges.iter() {
+        let is_flippable = edge_triangles.len() == 2 && !creases.contains(&(*i0, *i1)) && edge_triangles[0].1 != edge_triangles[1].1
+            && edge_triangles.iter().all(|(triangle, _)| no_crease_edges[*triangle] == 0);
+        if is_flippable {
+            // The first triangle traverses the edge from i0 to i1
+            let (t0, t1) = if edge_triangles[0].1 { (edge_triangles[0].0, edge_triangles[1].0) } else { (edge_triangles[1].0, edge_triangles[0].0) };
+            let (m0, m1) = (centers[t0].unwrap(), centers[t1].unwrap());
+            triangles.push([*i0, m1, m0]);
+            triangles.push([m1, *i1, m0]);
+        }
+        else {
+            for (triangle, forward) in edge_triangles.iter().filter(|(triangle, _)| no_crease_edges[*triangle] == 0) {
+                let (a, b) = if *forward { (*i0, *i1) } else { (*i1, *i0) };
+                triangles.push([a, b, centers[*triangle].unwrap()]);
+            }
+        }
+    }
+
+    // The triangles next to trisected creases are fanned from the opposite vertex or, if more than one edge is a crease, from the center
+    for (triangle, indices) in mesh.triangles.iter().enumerate().filter(|(triangle, _)| no_crease_edges[*triangle] > 0) {
+        let mut polygon = Vec::new();
+        let mut start = 0;
+        for k in 0..3 {
+            let (a, b) = (indices[k], indices[(k + 1) % 3]);
+            polygon.push(a);
+            if let Some((q0, q1)) = trisections.get(&(a.min(b), a.max(b))) {
+                if a < b { polygon.extend(&[*q0, *q1]); } else { polygon.extend(&[*q1, *q0]); }
+                start = (k + 2) % 3;
+            }
+        }
+        match centers[triangle] {
+            Some(center) => {
+                for k in 0..polygon.len() {
+                    triangles.push([polygon[k], polygon[(k + 1) % polygon.len()], center]);
+                }
+            },
+            None => {
+                let offset = polygon.iter().position(|index| *index == indices[start]).unwrap();
+                polygon.rotate_left(offset);
+                for k in 1..polygon.len() - 1 {
+                    triangles.push([polygon[0], polygon[k], polygon[k + 1]]);
+                }
+            }
+        }
+    }
+    mesh.triangles = triangles;
+
+    let mut new_creases = HashSet::new();
+    for (i0, i1) in creases.iter() {
+        match trisections.get(&(*i0, *i1)) {
+            Some((q0, q1)) => {
+                new_creases.insert(((*i0).min(*q0), (*i0).max(*q0)));
+                new_creases.insert(((*q0).min(*q1), (*q0).max(*q1)));
+                new_creases.insert(((*q1).min(*i1), (*q1).max(*i1)));
+            },
+            None => { new_creases.insert((*i0, *i1)); }
+        }
+    }
+    new_creases
+}
+
+fn opposite_vertex(triangle: &[usize; 3], i0: usize, i1: usize) -> usize
+{
+    *triangle.iter().find(|index| **index != i0 && **index != i1).unwrap()
+}
+
+/// Returns the neighbours of each vertex along the given edges.
+fn vertex_neighbours<'a, T>(no_vertices: usize, edges: impl IntoIterator<Item = (&'a (usize, usize), T)>) -> Vec<Vec<usize>>
+{
+    let mut neighbours = vec![Vec::new(); no_vertices];
+    for ((i0, i1), _) in edges {
+        neighbours[*i0].push(*i1);
+        neighbours[*i1].push(*i0);
+    }
+    neighbours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn tetrahedron() -> Mesh
+    {
+        mesh(vec![1.0, 1.0, 1.0,  1.0, -1.0, -1.0,  -1.0, 1.0, -1.0,  -1.0, -1.0, 1.0], vec![0, 1, 2,  0, 3, 1,  0, 2, 3,  1, 3, 2])
+    }
+
+    /// Returns the largest angle between the normals of two neighbouring faces.
+    fn max_normal_angle(mesh: &Mesh) -> f64
+    {
+        let indexed = IndexedMesh::new(mesh);
+        let normal = |triangle: usize| {
+            let (p0, p1, p2) = indexed.triangle_positions(triangle);
+            (p1 - p0).cross(p2 - p0).normalize()
+        };
+        indexed.edge_triangles().values().map(|triangles| normal(triangles[0].0).dot(normal(triangles[1].0)).min(1.0).acos()).fold(0.0, f64::max)
+    }
+
+    fn crease(mesh: &Mesh, p0: Vec3, p1: Vec3) -> (VertexID, VertexID)
+    {
+        let vertex_id = |p: Vec3| mesh.vertex_iter().find(|vertex_id| mesh.vertex_position(*vertex_id).distance(p) < 1.0e-9).unwrap();
+        (vertex_id(p0), vertex_id(p1))
+    }
+
+    #[test]
+    fn test_loop_converges_to_smooth_surface()
+    {
+        let mut angles = vec![max_normal_angle(&tetrahedron())];
+        for no_iterations in 1..4 {
+            let mut mesh = tetrahedron();
+            subdivide(&mut mesh, &SubdivisionOptions {no_iterations, ..Default::default()}).unwrap();
+            assert_eq!(mesh.no_faces(), 4 * 4usize.pow(no_iterations as u32));
+            assert!(crate::validation::validate(&mesh).is_valid());
+            angles.push(max_normal_angle(&mesh));
+        }
+        for k in 1..angles.len() {
+            assert!(angles[k] < angles[k - 1]);
+        }
+        assert!(angles[angles.len() - 1] < 0.3 * angles[0]);
+    }
+
+    #[test]
+    fn test_sqrt3_converges_to_smooth_surface()
+    {
+        let mut angles = vec![max_normal_angle(&tetrahedron())];
+        for no_iterations in [2, 4].iter() {
+            let mut mesh = tetrahedron();
+            subdivide(&mut mesh, &SubdivisionOptions {scheme: SubdivisionScheme::Sqrt3, no_iterations: *no_iterations, ..Default::default()}).unwrap();
+            assert_eq!(mesh.no_faces(), 4 * 3usize.pow(*no_iterations as u32));
+            assert!(crate::validation::validate(&mesh).is_valid());
+            angles.push(max_normal_angle(&mesh));
+        }
+        for k in 1..angles.len() {
+            assert!(angles[k] < angles[k - 1]);
+        }
+        assert!(angles[angles.len() - 1] < 0.3 * angles[0]);
+    }
+
+    #[test]
+    fn test_creases_stay_sharp()
+    {
+        // All the edges of the cube are creases and all its vertices are corners, so the sides stay flat
+        let cube = cube(1);
+        let corners: Vec<Vec3> = cube.vertex_iter().map(|vertex_id| cube.vertex_position(vertex_id)).collect();
+        let mut crease_edges = Vec::new();
+        for (i, p0) in corners.iter().enumerate() {
+            for p1 in corners[i + 1..].iter().filter(|p1| (**p1 - *p0).magnitude2() == 1.0) {
+                crease_edges.push(crease(&cube, *p0, *p1));
+            }
+        }
+        assert_eq!(crease_edges.len(), 12);
+
+        for scheme in [SubdivisionScheme::Loop, SubdivisionScheme::Sqrt3].iter() {
+            let mut mesh = cube.clone();
+            subdivide(&mut mesh, &SubdivisionOptions {scheme: *scheme, no_iterations: 2, crease_edges: crease_edges.clone()}).unwrap();
+            assert!(crate::validation::validate(&mesh).is_valid());
+            assert!((volume(&mesh) - 1.0).abs() < 1.0e-9);
+            for vertex_id in mesh.vertex_iter() {
+                let p = mesh.vertex_position(vertex_id);
+                assert!([p.x, p.y, p.z].iter().any(|coordinate| coordinate.abs() < 1.0e-9 || (coordinate - 1.0).abs() < 1.0e-9));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dart()
+    {
+        // The ends of a single crease edge get the smooth Loop mask, which moves the corners of the regular tetrahedron to a quarter of
+        // the distance from the center, while the crease edge is split at its midpoint
+        let tetrahedron = tetrahedron();
+        let (p0, p1) = (vec3(1.0, 1.0, 1.0), vec3(1.0, -1.0, -1.0));
+        let mut mesh = tetrahedron.clone();
+        subdivide(&mut mesh, &SubdivisionOptions {crease_edges: vec![crease(&tetrahedron, p0, p1)], ..Default::default()}).unwrap();
+
+        let positions: Vec<Vec3> = mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)).collect();
+        for p in [0.25 * p0, 0.25 * p1, 0.5 * (p0 + p1)].iter() {
+            assert!(positions.iter().any(|q| q.distance(*p) < 1.0e-9));
+        }
+        assert!(positions.iter().all(|q| q.distance(p0) > 0.1 && q.distance(p1) > 0.1));
+    }
+}
@@ -0,0 +1,113 @@
+//! Meshes and helpers shared by the unit tests.
+
+use tri_mesh::prelude::*;
+use tri_mesh::MeshBuilder;
+use std::collections::HashMap;
+
+pub fn mesh(positions: Vec<f64>, indices: Vec<u32>) -> Mesh
+{
+    MeshBuilder::new().with_positions(positions).with_indices(indices).build().unwrap()
+}
+
+///
+/// Returns the unit cube `[0, 1]^3` where each side is divided into `n` by `n` squares, with the normals pointing outwards.
+///
+pub fn cube(n: usize) -> Mesh
+{
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    let mut vertices: HashMap<(usize, usize, usize), u32> = HashMap::new();
+    for axis in 0..3 {
+        for side in [0, n].iter() {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let mut vertex = |i: usize, j: usize| {
+                let mut key = [0; 3];
+                key[axis] = *side;
+                key[u] = i;
+                key[v] = j;
+                *vertices.entry((key[0], key[1], key[2])).or_insert_with(|| {
+                    positions.extend(key.iter().map(|k| *k as f64 / n as f64));
+                    (positions.len() / 3 - 1) as u32
+                })
+            };
+            for i in 0..n {
+                for j in 0..n {
+                    let c = [vertex(i, j), vertex(i + 1, j), vertex(i + 1, j + 1), vertex(i, j + 1)];
+                    if *side == n { indices.extend(&[c[0], c[1], c[2], c[0], c[2], c[3]]); }
+                    else { indices.extend(&[c[0], c[2], c[1], c[0], c[3], c[2]]); }
+                }
+            }
+        }
+    }
+    mesh(positions, indices)
+}
+
+///
+/// Returns a UV sphere with the given radius centered at the origin with `n` rings and `2n` segments, with the normals pointing outwards.
+///
+pub fn sphere(radius: f64, n: usize) -> Mesh
+{
+    let pi = std::f64::consts::PI;
+    let mut positions = vec![0.0, 0.0, radius];
+    for i in 1..n {
+        for j in 0..2 * n {
+            let (theta, phi) = (pi * i as f64 / n as f64, pi * j as f64 / n as f64);
+            positions.extend(&[radius * theta.sin() * phi.cos(), radius * theta.sin() * phi.sin(), radius * theta.cos()]);
+        }
+    }
+    positions.extend(&[0.0, 0.0, -radius]);
+
+    let segments = 2 * n as u32;
+    let ring = |i: u32, j: u32| 1 + (i - 1) * segments + j % segments;
+    let last = (positions.len() / 3 - 1) as u32;
+    let mut indices = Vec::new();
+    for j in 0..segments {
+        indices.extend(&[0, ring(1, j), ring(1, j + 1)]);
+        for i in 1..n as u32 - 1 {
+            indices.extend(&[ring(i, j), ring(i + 1, j), ring(i + 1, j + 1), ring(i, j), ring(i + 1, j + 1), ring(i, j + 1)]);
+        }
+        indices.extend(&[last, ring(n as u32 - 1, j + 1), ring(n as u32 - 1, j)]);
+    }
+    mesh(positions, indices)
+}
+
+///
+/// Returns the signed volume enclosed by the mesh, which is positive if the mesh is closed and the normals point outwards.
+///
+pub fn volume(mesh: &Mesh) -> f64
+{
+    mesh.face_iter().map(|face_id| {
+        let (p0, p1, p2) = mesh.face_positions(face_id);
+        p0.dot(p1.cross(p2)) / 6.0
+    }).sum()
+}
+
+///
+/// Returns a copy of the mesh moved by the given offset.
+///
+pub fn translated(mesh: &Mesh, offset: Vec3) -> Mesh
+{
+    let positions = mesh.positions_buffer().chunks(3).flat_map(|p| vec![p[0] + offset.x, p[1] + offset.y, p[2] + offset.z]).collect();
+    self::mesh(positions, mesh.indices_buffer())
+}
+
+///
+/// Returns the corners of the faces, each rotated so the smallest corner is first, and sorted,
+/// so meshes with the same faces compare equal independently of the vertex and face order.
+///
+pub fn sorted_triangles(mesh: &Mesh) -> Vec<[f64; 9]>
+{
+    let mut triangles: Vec<[f64; 9]> = mesh.face_iter().map(|face_id| {
+        let (p0, p1, p2) = mesh.face_positions(face_id);
+        let mut corners = [[p0.x, p0.y, p0.z], [p1.x, p1.y, p1.z], [p2.x, p2.y, p2.z]];
+        let first = (0..3).min_by(|a, b| corners[*a].partial_cmp(&corners[*b]).unwrap()).unwrap();
+        corners.rotate_left(first);
+        let mut triangle = [0.0; 9];
+        for (i, value) in corners.iter().flat_map(|corner| corner.iter()).enumerate() {
+            triangle[i] = *value;
+        }
+        triangle
+    }).collect();
+    triangles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    triangles
+}
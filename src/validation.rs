@@ -0,0 +1,229 @@
+//! Validation of a mesh, ie. detection of the defects which makes a mesh unsuitable for further processing, for example stitching.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::IndexedMesh;
+use crate::ray_casting::RayCaster;
+use std::collections::{HashMap, HashSet};
+
+///
+/// The faces with an area below this tolerance times the squared diagonal of the bounding box of the mesh are degenerate.
+///
+pub(crate) const DEGENERATE_AREA_TOLERANCE: f64 = 1.0e-12;
+
+///
+/// The defects found in a mesh by [validate](fn.validate.html), each given by the IDs of the offending primitives.
+/// An edge is given by its two end vertices, the vertex which comes first in [vertex_iter](../prelude/struct.Mesh.html#method.vertex_iter) first,
+/// and the defects are listed in the order of the vertices and faces in the mesh.
+///
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    /// Edges shared by more than two faces.
+    pub non_manifold_edges: Vec<(VertexID, VertexID)>,
+    /// Vertices where the faces around the vertex do not form one fan, for example where two surfaces touch at the vertex.
+    pub non_manifold_vertices: Vec<VertexID>,
+    /// Edges shared by two faces which traverse the edge in the same direction, ie. the faces are oriented inconsistently.
+    pub inconsistently_oriented_edges: Vec<(VertexID, VertexID)>,
+    /// Faces with two identical vertices or (almost) zero area.
+    pub degenerate_faces: Vec<FaceID>,
+    /// Faces with the same vertices as another face, given as the pair of the first face and the duplicate.
+    pub duplicate_faces: Vec<(FaceID, FaceID)>,
+    /// Vertices which are not part of any face.
+    pub isolated_vertices: Vec<VertexID>,
+    /// Edges with only one neighbouring face, ie. edges on an open boundary.
+    pub boundary_edges: Vec<(VertexID, VertexID)>,
+    /// Pairs of faces which intersect each other away from the vertices and edges they share, including coplanar faces which overlap.
+    pub self_intersections: Vec<(FaceID, FaceID)>
+}
+
+impl ValidationReport {
+    ///
+    /// Returns whether no defects are found, ie. whether the mesh is a closed, consistently oriented two-manifold without self-intersections.
+    ///
+    pub fn is_valid(&self) -> bool
+    {
+        self.no_defects() == 0
+    }
+
+    ///
+    /// Returns whether the mesh has no open boundaries.
+    ///
+    pub fn is_closed(&self) -> bool
+    {
+        self.boundary_edges.len() == 0
+    }
+
+    ///
+    /// Returns the total number of defects.
+    ///
+    pub fn no_defects(&self) -> usize
+    {
+        self.non_manifold_edges.len() + self.non_manifold_vertices.len() + self.inconsistently_oriented_edges.len()
+            + self.degenerate_faces.len() + self.duplicate_faces.len() + self.isolated_vertices.len()
+            + self.boundary_edges.len() + self.self_intersections.len()
+    }
+}
+
+///
+/// Validates the mesh and returns a report of all the defects found.
+///
+pub fn validate(mesh: &Mesh) -> ValidationReport
+{
+    let mut report = ValidationReport::default();
+    let indexed = IndexedMesh::new(mesh);
+    let vertex_ids: Vec<VertexID> = mesh.vertex_iter().collect();
+    let face_ids: Vec<FaceID> = mesh.face_iter().collect();
+    let edges = indexed.edge_triangles();
+
+    let mut edge_list: Vec<(&(usize, usize), &Vec<(usize, bool)>)> = edges.iter().collect();
+    edge_list.sort_by_key(|(edge, _)| **edge);
+    for ((i0, i1), triangles) in edge_list {
+        let edge = (vertex_ids[*i0], vertex_ids[*i1]);
+        if triangles.len() == 1 {
+            report.boundary_edges.push(edge);
+        }
+        else if triangles.len() == 2 {
+            if triangles[0].1 == triangles[1].1 {
+                report.inconsistently_oriented_edges.push(edge);
+            }
+        }
+        else {
+            report.non_manifold_edges.push(edge);
+        }
+    }
+
+    let mut triangle_keys: HashMap<[usize; 3], usize> = HashMap::new();
+    let area_tolerance = DEGENERATE_AREA_TOLERANCE * indexed.diagonal().powi(2);
+    for (triangle, indices) in indexed.triangles.iter().enumerate() {
+        let [i0, i1, i2] = *indices;
+        if i0 == i1 || i1 == i2 || i2 == i0 || mesh.face_area(face_ids[triangle]) <= area_tolerance {
+            report.degenerate_faces.push(face_ids[triangle]);
+        }
+
+        let mut key = *indices;
+        key.sort();
+        match triangle_keys.get(&key) {
+            Some(first) => report.duplicate_faces.push((face_ids[*first], face_ids[triangle])),
+            None => { triangle_keys.insert(key, triangle); }
+        }
+    }
+
+    for (vertex, triangles) in indexed.vertex_triangles().iter().enumerate() {
+        if triangles.len() == 0 {
+            report.isolated_vertices.push(vertex_ids[vertex]);
+        }
+        else if no_fans(&indexed, vertex, triangles, &edges) > 1 {
+            report.non_manifold_vertices.push(vertex_ids[vertex]);
+        }
+    }
+
+    report.self_intersections = RayCaster::new(mesh).intersecting_faces();
+    report
+}
+
+/// Returns the number of fans the triangles around the vertex form, ie. the number of groups of triangles connected through the edges at the vertex.
+fn no_fans(mesh: &IndexedMesh, vertex: usize, triangles: &[usize], edges: &HashMap<(usize, usize), Vec<(usize, bool)>>) -> usize
+{
+    let mut parent: HashMap<usize, usize> = triangles.iter().map(|triangle| (*triangle, *triangle)).collect();
+    fn find(parent: &mut HashMap<usize, usize>, triangle: usize) -> usize
+    {
+        let mut root = triangle;
+        while parent[&root] != root {
+            root = parent[&root];
+        }
+        parent.insert(triangle, root);
+        root
+    }
+
+    for triangle in triangles.iter() {
+        for other in mesh.triangles[*triangle].iter().filter(|index| **index != vertex) {
+            if let Some(neighbours) = edges.get(&(vertex.min(*other), vertex.max(*other))) {
+                for (neighbour, _) in neighbours.iter() {
+                    let root0 = find(&mut parent, *triangle);
+                    let root1 = find(&mut parent, *neighbour);
+                    parent.insert(root0, root1);
+                }
+            }
+        }
+    }
+    let roots: HashSet<usize> = triangles.iter().map(|triangle| find(&mut parent, *triangle)).collect();
+    roots.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_valid_mesh()
+    {
+        let report = validate(&cube(2));
+        assert!(report.is_valid(), "{:?}", report);
+        assert!(report.is_closed());
+    }
+
+    #[test]
+    fn test_open_mesh()
+    {
+        let closed = cube(2);
+        let indices = closed.indices_buffer();
+        let open = mesh(closed.positions_buffer(), indices[3..].to_vec());
+        let report = validate(&open);
+        assert!(!report.is_closed());
+        assert_eq!(report.boundary_edges.len(), 3);
+        assert_eq!(report.no_defects(), 3);
+    }
+
+    #[test]
+    fn test_self_intersection_at_shared_vertex()
+    {
+        // The second triangle shares a vertex with the first and passes through it
+        let positions = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.3, 0.3, -1.0, 0.3, 0.3, 1.0];
+        let report = validate(&mesh(positions, vec![0, 1, 2, 0, 3, 4]));
+        assert_eq!(report.self_intersections.len(), 1);
+    }
+
+    #[test]
+    fn test_no_self_intersection_between_neighbours()
+    {
+        // A folded and a flat pair of triangles sharing an edge and a fan of triangles sharing a vertex
+        let folded = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0];
+        assert_eq!(validate(&mesh(folded, vec![0, 1, 2, 2, 1, 3])).self_intersections.len(), 0);
+        let flat = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        assert_eq!(validate(&mesh(flat, vec![0, 1, 2, 0, 2, 3])).self_intersections.len(), 0);
+        let fan = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, -1.0, 0.0];
+        assert_eq!(validate(&mesh(fan, vec![0, 1, 2, 0, 3, 4])).self_intersections.len(), 0);
+    }
+
+    #[test]
+    fn test_coplanar_overlap()
+    {
+        // The second triangle shares an edge with the first and is folded onto it
+        let positions = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.8, 0.8, 0.0];
+        let report = validate(&mesh(positions.clone(), vec![0, 1, 2, 1, 0, 3]));
+        assert_eq!(report.self_intersections.len(), 1);
+        assert_eq!(report.inconsistently_oriented_edges.len(), 0);
+
+        // A triangle inside another triangle in the same plane without shared corners
+        let mut positions = positions;
+        positions.extend(&[0.1, 0.1, 0.0, 0.3, 0.1, 0.0, 0.1, 0.3, 0.0]);
+        let report = validate(&mesh(positions, vec![0, 1, 2, 4, 5, 6]));
+        assert_eq!(report.self_intersections.len(), 1);
+    }
+
+    #[test]
+    fn test_overlapping_cubes()
+    {
+        let first = cube(1);
+        let second = translated(&cube(1), vec3(0.5, 0.4, 0.3));
+        let mut positions = first.positions_buffer();
+        let offset = (positions.len() / 3) as u32;
+        positions.extend(second.positions_buffer());
+        let mut indices = first.indices_buffer();
+        indices.extend(second.indices_buffer().iter().map(|index| index + offset));
+        let report = validate(&mesh(positions, indices));
+        assert!(report.self_intersections.len() > 0);
+        assert!(report.is_closed());
+        assert_eq!(report.no_defects(), report.self_intersections.len());
+    }
+}
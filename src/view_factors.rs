@@ -0,0 +1,217 @@
+//! Radiative view factors from a source, for example a fire, to the faces of a mesh.
+//!
+//! The view factor from a face to the source is the fraction of the radiation leaving the face which reaches the source.
+//! By reciprocity, the radiative heat flux received by the face from a black body source with temperature `T` is `F σ T⁴`.
+
+use tri_mesh::prelude::*;
+use crate::ray_casting::{RayCaster, RELATIVE_EPSILON, diagonal};
+use std::collections::HashMap;
+
+///
+/// A source of radiation.
+///
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// A sphere which emits radiation from its entire surface.
+    Sphere {center: Vec3, radius: f64},
+    /// A planar disc which emits radiation from the side its normal points to.
+    Disc {center: Vec3, normal: Vec3, radius: f64}
+}
+
+///
+/// Options for the computation of view factors, see [view_factors](fn.view_factors.html).
+///
+#[derive(Debug, Clone)]
+pub struct ViewFactorOptions {
+    /// Each face is divided into `no_face_subdivisions²` sub-triangles and the view factor is averaged over their centers.
+    pub no_face_subdivisions: usize,
+    /// The number of points on the surface of the source used to integrate the view factor.
+    pub no_source_samples: usize,
+    /// Whether the mesh itself blocks the radiation.
+    pub occlusion: bool,
+    /// Whether the faces receive radiation on both sides or only on the side the face normal points to.
+    pub two_sided: bool
+}
+
+impl Default for ViewFactorOptions {
+    fn default() -> Self {
+        ViewFactorOptions {no_face_subdivisions: 1, no_source_samples: 256, occlusion: true, two_sided: false}
+    }
+}
+
+///
+/// Returns the view factor from each face of the mesh to the source, including occlusion by the mesh itself if enabled in the options.
+/// The view factors can be exported as face data, for example by inserting them in a face layer of [Attributes](../attributes/struct.Attributes.html).
+///
+pub fn view_factors(mesh: &Mesh, source: &Source, options: &ViewFactorOptions) -> HashMap<FaceID, f64>
+{
+    let source_samples = source_samples(source, options.no_source_samples.max(1));
+    let ray_caster = if options.occlusion { Some(RayCaster::new(mesh)) } else { None };
+    let epsilon = (RELATIVE_EPSILON * diagonal(mesh.vertex_iter().map(|vertex_id| mesh.vertex_position(vertex_id)))).max(std::f64::MIN_POSITIVE);
+    let face_samples = face_sample_coordinates(options.no_face_subdivisions.max(1));
+
+    let mut result = HashMap::new();
+    for face_id in mesh.face_iter() {
+        let (p0, p1, p2) = mesh.face_positions(face_id);
+        let normal = mesh.face_normal(face_id);
+        let mut sum = 0.0;
+        for (u, v, w) in face_samples.iter() {
+            let point = p0 * *u + p1 * *v + p2 * *w;
+            let mut view_factor = 0.0;
+            for (source_point, source_normal, area) in source_samples.iter() {
+                let difference = source_point - point;
+                let sqr_distance = difference.magnitude2();
+                if sqr_distance < epsilon * epsilon { continue; }
+                let direction = difference / sqr_distance.sqrt();
+                let mut cos_receiver = normal.dot(direction);
+                if options.two_sided { cos_receiver = cos_receiver.abs(); }
+                let cos_source = -source_normal.dot(direction);
+                if cos_receiver <= 0.0 || cos_source <= 0.0 { continue; }
+
+                if let Some(ref ray_caster) = ray_caster {
+                    let offset = normal * epsilon * normal.dot(direction).signum();
+                    if ray_caster.blocks_view(&(point + offset), source_point, epsilon) { continue; }
+                }
+                view_factor += cos_receiver * cos_source * area / (std::f64::consts::PI * sqr_distance);
+            }
+            sum += view_factor.min(1.0);
+        }
+        result.insert(face_id, sum / face_samples.len() as f64);
+    }
+    result
+}
+
+/// Returns evenly distributed points on the surface of the source with their normal and the area they represent.
+fn source_samples(source: &Source, no_samples: usize) -> Vec<(Vec3, Vec3, f64)>
+{
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    match source {
+        Source::Sphere {center, radius} => {
+            let area = 4.0 * std::f64::consts::PI * radius * radius / no_samples as f64;
+            (0..no_samples).map(|i| {
+                let z = 1.0 - (2.0 * i as f64 + 1.0) / no_samples as f64;
+                let r = (1.0 - z * z).sqrt();
+                let angle = i as f64 * golden_angle;
+                let normal = vec3(r * angle.cos(), r * angle.sin(), z);
+                (center + normal * *radius, normal, area)
+            }).collect()
+        },
+        Source::Disc {center, normal, radius} => {
+            let normal = normal.normalize();
+            let tangent = if normal.x.abs() < 0.9 { normal.cross(vec3(1.0, 0.0, 0.0)) } else { normal.cross(vec3(0.0, 1.0, 0.0)) }.normalize();
+            let bitangent = normal.cross(tangent);
+            let area = std::f64::consts::PI * radius * radius / no_samples as f64;
+            (0..no_samples).map(|i| {
+                let r = radius * ((i as f64 + 0.5) / no_samples as f64).sqrt();
+                let angle = i as f64 * golden_angle;
+                (center + tangent * (r * angle.cos()) + bitangent * (r * angle.sin()), normal, area)
+            }).collect()
+        }
+    }
+}
+
+/// Returns the barycentric coordinates of the centers of the sub-triangles when dividing a triangle into `n²` equally sized sub-triangles.
+pub(crate) fn face_sample_coordinates(n: usize) -> Vec<(f64, f64, f64)>
+{
+    let mut coordinates = Vec::new();
+    let size = n as f64;
+    for i in 0..n {
+        for j in 0..n-i {
+            let (v, w) = ((i as f64 + 1.0/3.0) / size, (j as f64 + 1.0/3.0) / size);
+            coordinates.push((1.0 - v - w, v, w));
+            if j + 1 < n - i {
+                let (v, w) = ((i as f64 + 2.0/3.0) / size, (j as f64 + 2.0/3.0) / size);
+                coordinates.push((1.0 - v - w, v, w));
+            }
+        }
+    }
+    coordinates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Returns the positions and indices of a square with the given half size centered at `(0, 0, z)` with the normal pointing in the positive z direction.
+    fn square(half_size: f64, z: f64) -> (Vec<f64>, Vec<u32>)
+    {
+        let s = half_size;
+        (vec![-s, -s, z, s, -s, z, s, s, z, -s, s, z], vec![0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn test_parallel_disc()
+    {
+        // The view factor from a small element to a coaxial parallel disc with radius r at distance h is r² / (h² + r²)
+        let (positions, indices) = square(0.01, 0.0);
+        let receiver = mesh(positions, indices);
+        let options = ViewFactorOptions {no_source_samples: 4096, ..Default::default()};
+        for (radius, height) in [(1.0, 1.0), (0.5, 2.0), (2.0, 0.5)].iter() {
+            let source = Source::Disc {center: vec3(0.0, 0.0, *height), normal: vec3(0.0, 0.0, -1.0), radius: *radius};
+            let expected = radius * radius / (height * height + radius * radius);
+            for view_factor in view_factors(&receiver, &source, &options).values() {
+                assert!((view_factor - expected).abs() < 0.01 * expected, "{} != {}", view_factor, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sphere()
+    {
+        // The view factor from a small element to a sphere with radius r whose center is at distance h along the normal is r² / h²
+        let (positions, indices) = square(0.01, 0.0);
+        let receiver = mesh(positions, indices);
+        let options = ViewFactorOptions {no_source_samples: 4096, ..Default::default()};
+        for (radius, height) in [(1.0, 2.0), (0.5, 2.0), (1.0, 1.5)].iter() {
+            let source = Source::Sphere {center: vec3(0.0, 0.0, *height), radius: *radius};
+            let expected = radius * radius / (height * height);
+            for view_factor in view_factors(&receiver, &source, &options).values() {
+                assert!((view_factor - expected).abs() < 0.01 * expected, "{} != {}", view_factor, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sphere_occlusion()
+    {
+        // A half plane halfway between the receiver and the sphere, with its edge above the receiver, blocks half of the view
+        let (mut positions, mut indices) = square(0.01, 0.0);
+        positions.extend(&[0.0, -4.0, 1.0, 4.0, -4.0, 1.0, 4.0, 4.0, 1.0, 0.0, 4.0, 1.0]);
+        indices.extend(&[4, 5, 6, 4, 6, 7]);
+        let mesh = mesh(positions, indices);
+        let source = Source::Sphere {center: vec3(0.0, 0.0, 2.0), radius: 1.0};
+        let options = ViewFactorOptions {no_source_samples: 4096, ..Default::default()};
+
+        let occluded = view_factors(&mesh, &source, &options);
+        let unoccluded = view_factors(&mesh, &source, &ViewFactorOptions {occlusion: false, ..options.clone()});
+        for face_id in mesh.face_iter().filter(|face_id| mesh.face_center(*face_id).z < 0.5) {
+            assert!((unoccluded[&face_id] - 0.25).abs() < 0.0025, "{} != 0.25", unoccluded[&face_id]);
+            assert!((occluded[&face_id] - 0.125).abs() < 0.01, "{} != 0.125", occluded[&face_id]);
+        }
+    }
+
+    #[test]
+    fn test_occlusion()
+    {
+        // A larger square between the receiver and the disc blocks the view
+        let (mut positions, mut indices) = square(0.01, 0.0);
+        let (blocker_positions, blocker_indices) = square(2.0, 0.5);
+        indices.extend(blocker_indices.iter().map(|index| index + 4));
+        positions.extend(blocker_positions);
+        let mesh = mesh(positions, indices);
+        let source = Source::Disc {center: vec3(0.0, 0.0, 1.0), normal: vec3(0.0, 0.0, -1.0), radius: 1.0};
+
+        let view_factors = view_factors(&mesh, &source, &ViewFactorOptions::default());
+        for face_id in mesh.face_iter() {
+            if mesh.face_center(face_id).z < 0.25 {
+                assert_eq!(view_factors[&face_id], 0.0);
+            }
+            else {
+                assert!(view_factors[&face_id] > 0.1);
+            }
+        }
+        let unoccluded = super::view_factors(&mesh, &source, &ViewFactorOptions {occlusion: false, ..Default::default()});
+        assert!(mesh.face_iter().all(|face_id| unoccluded[&face_id] > 0.1));
+    }
+}
@@ -0,0 +1,123 @@
+//! Visibility of the faces of a mesh from a point, for example the center of a fire.
+
+use tri_mesh::prelude::*;
+use crate::ray_casting::RayCaster;
+use std::collections::HashMap;
+
+///
+/// The classification of a face based on the fraction of its sample points which are visible from a point.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    Visible,
+    PartiallyVisible,
+    Occluded
+}
+
+impl Visibility {
+    ///
+    /// Returns `Visible` if the fraction is 1, `Occluded` if it is 0 and otherwise `PartiallyVisible`.
+    ///
+    pub fn from_fraction(fraction: f64) -> Visibility
+    {
+        if fraction >= 1.0 { Visibility::Visible }
+        else if fraction <= 0.0 { Visibility::Occluded }
+        else { Visibility::PartiallyVisible }
+    }
+}
+
+///
+/// Returns the fraction of each face of the mesh which is visible from the given point, ie. not occluded by the mesh itself.
+/// Each face is divided into `no_face_subdivisions²` sub-triangles and the fraction is the number of visible sub-triangle centers
+/// divided by the number of sub-triangles, so `no_face_subdivisions = 1` only tests the face center.
+/// Use [Visibility::from_fraction](enum.Visibility.html#method.from_fraction) to classify the faces.
+/// Note that only the line of sight is tested, so a face is visible from behind.
+///
+pub fn visibility_from_point(mesh: &Mesh, point: &Vec3, no_face_subdivisions: usize) -> HashMap<FaceID, f64>
+{
+    visibility_from_point_with_occluder(mesh, &RayCaster::new(mesh), point, no_face_subdivisions)
+}
+
+///
+/// Returns the fraction of each face of the mesh which is visible from the given point when the view is occluded
+/// by the mesh of the given ray caster, which can be another mesh than the mesh the faces are in.
+/// See [visibility_from_point](fn.visibility_from_point.html).
+///
+pub fn visibility_from_point_with_occluder(mesh: &Mesh, occluder: &RayCaster, point: &Vec3, no_face_subdivisions: usize) -> HashMap<FaceID, f64>
+{
+    let samples = crate::view_factors::face_sample_coordinates(no_face_subdivisions.max(1));
+    let epsilon = occluder.epsilon();
+    mesh.face_iter().map(|face_id| {
+        let (p0, p1, p2) = mesh.face_positions(face_id);
+        let no_visible = samples.iter().filter(|(u, v, w)| {
+            let sample = p0 * *u + p1 * *v + p2 * *w;
+            !occluder.blocks_view(&sample, point, epsilon)
+        }).count();
+        (face_id, no_visible as f64 / samples.len() as f64)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_visibility_inside_cube()
+    {
+        // All faces of a room are visible from a point inside it
+        let room = cube(2);
+        let visibility = visibility_from_point(&room, &vec3(0.3, 0.6, 0.4), 3);
+        assert_eq!(visibility.len(), room.no_faces());
+        assert!(visibility.values().all(|fraction| Visibility::from_fraction(*fraction) == Visibility::Visible));
+    }
+
+    #[test]
+    fn test_visibility_outside_cube()
+    {
+        // From outside, in front of the side x = 1, the faces on the side x = 0 are occluded by the side x = 1
+        // and the faces on the other sides are partially visible or occluded
+        let room = cube(2);
+        let visibility = visibility_from_point(&room, &vec3(3.0, 0.5, 0.5), 4);
+        for face_id in room.face_iter() {
+            let center = room.face_center(face_id);
+            let classification = Visibility::from_fraction(visibility[&face_id]);
+            if center.x > 1.0 - 1.0e-9 {
+                assert_eq!(classification, Visibility::Visible);
+            }
+            else if center.x < 1.0e-9 {
+                assert_eq!(classification, Visibility::Occluded);
+            }
+            else {
+                assert_ne!(classification, Visibility::Visible);
+            }
+        }
+    }
+
+    #[test]
+    fn test_visibility_with_occluder()
+    {
+        // A box above the half x > 0 of a floor, with a side in the plane x = 0 through the point, casts a shadow on that half
+        let floor = translated(&cube(4), vec3(-0.5, -0.5, -1.0));
+        let occluder = translated(&cube(1), vec3(0.0, -0.5, 1.0));
+        let ray_caster = RayCaster::new(&occluder);
+        let point = vec3(0.0, 0.0, 3.0);
+        let visibility = visibility_from_point_with_occluder(&floor, &ray_caster, &point, 1);
+        let own = visibility_from_point(&floor, &point, 1);
+        for face_id in floor.face_iter() {
+            let center = floor.face_center(face_id);
+            if center.z > -1.0e-9 {
+                assert_eq!(visibility[&face_id], if center.x > 0.0 { 0.0 } else { 1.0 });
+                assert_eq!(own[&face_id], 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_fraction()
+    {
+        assert_eq!(Visibility::from_fraction(1.0), Visibility::Visible);
+        assert_eq!(Visibility::from_fraction(0.5), Visibility::PartiallyVisible);
+        assert_eq!(Visibility::from_fraction(0.0), Visibility::Occluded);
+    }
+}
@@ -0,0 +1,134 @@
+//! Welding of vertices, ie. merging vertices which are closer than a tolerance, for example to connect the faces of a triangle soup from a CAD export.
+
+use tri_mesh::prelude::*;
+use crate::indexed_mesh::IndexedMesh;
+
+#[derive(Debug)]
+pub enum Error {
+    MeshBuilder(tri_mesh::mesh_builder::Error)
+}
+
+impl From<tri_mesh::mesh_builder::Error> for Error {
+    fn from(other: tri_mesh::mesh_builder::Error) -> Self {
+        Error::MeshBuilder(other)
+    }
+}
+
+///
+/// A distance tolerance.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    /// An absolute distance.
+    Absolute(f64),
+    /// A distance relative to the length of the diagonal of the axis aligned bounding box of the mesh.
+    Relative(f64)
+}
+
+impl Tolerance {
+    ///
+    /// Returns the absolute distance given the length of the diagonal of the bounding box of the mesh.
+    ///
+    pub fn distance(&self, diagonal: f64) -> f64
+    {
+        match self {
+            Tolerance::Absolute(distance) => *distance,
+            Tolerance::Relative(fraction) => *fraction * diagonal
+        }
+    }
+}
+
+///
+/// The changes made to a mesh by [weld](fn.weld.html).
+///
+#[derive(Debug, Clone, Default)]
+pub struct WeldReport {
+    /// The number of vertices which are removed because they are not part of any face.
+    pub no_removed_vertices: usize,
+    pub no_merged_vertices: usize,
+    /// The number of faces which are removed because two of their vertices are merged.
+    pub no_removed_faces: usize
+}
+
+///
+/// Merges the vertices of the mesh which are closer than the tolerance. Each vertex is merged into the first vertex, in the order of the vertex iterator,
+/// within the tolerance, which is found using a spatial hash, so the running time is linear in the number of vertices unless the tolerance is large
+/// compared to the size of the faces. Faces which collapse because two of their vertices are merged are removed, and so are vertices which are not part of any face.
+/// If no vertices are removed or merged, the mesh is not changed, otherwise it is rebuilt, so the vertex and face IDs are not preserved.
+///
+pub fn weld(mesh: &mut Mesh, tolerance: Tolerance) -> Result<WeldReport, Error>
+{
+    let mut indexed = IndexedMesh::new(mesh);
+    let mut report = WeldReport::default();
+    report.no_removed_vertices = indexed.remove_unused_vertices();
+    report.no_merged_vertices = indexed.merge_close_vertices(tolerance.distance(indexed.diagonal()));
+    if report.no_merged_vertices > 0 {
+        let no_triangles = indexed.triangles.len();
+        indexed.triangles.retain(|[i0, i1, i2]| i0 != i1 && i1 != i2 && i2 != i0);
+        report.no_removed_faces = no_triangles - indexed.triangles.len();
+    }
+    if report.no_removed_vertices > 0 || report.no_merged_vertices > 0 {
+        *mesh = indexed.to_mesh()?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_weld_triangle_soup()
+    {
+        // Each face of the cube has its own vertices
+        let cube = cube(1);
+        let positions = cube.positions_buffer();
+        let indices = cube.indices_buffer();
+        let soup_positions: Vec<f64> = indices.iter().flat_map(|index| positions[3 * *index as usize..3 * *index as usize + 3].to_vec()).collect();
+        let mut mesh = self::mesh(soup_positions, (0..indices.len() as u32).collect());
+        assert_eq!(mesh.no_vertices(), 36);
+
+        let report = weld(&mut mesh, Tolerance::Relative(1.0e-6)).unwrap();
+        assert_eq!(report.no_removed_vertices, 0);
+        assert_eq!(report.no_merged_vertices, 28);
+        assert_eq!(report.no_removed_faces, 0);
+        assert_eq!(mesh.no_vertices(), 8);
+        assert!(crate::validation::validate(&mesh).is_valid());
+        assert_eq!(sorted_triangles(&mesh), sorted_triangles(&cube));
+    }
+
+    #[test]
+    fn test_weld_collapsed_face()
+    {
+        // The last face is a sliver whose two first vertices are within the tolerance
+        let mut mesh = self::mesh(vec![0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  0.0, 1.0, 0.0,  1.0, 1.0, 0.0,  1.0, 1.0e-4, 0.0],
+                                  vec![0, 1, 2,  1, 3, 2,  4, 1, 0]);
+        let report = weld(&mut mesh, Tolerance::Absolute(1.0e-3)).unwrap();
+        assert_eq!(report.no_merged_vertices, 1);
+        assert_eq!(report.no_removed_faces, 1);
+        assert_eq!(mesh.no_vertices(), 4);
+        assert_eq!(mesh.no_faces(), 2);
+    }
+
+    #[test]
+    fn test_weld_isolated_vertices()
+    {
+        let mut mesh = self::mesh(vec![0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  0.0, 1.0, 0.0,  5.0, 5.0, 5.0], vec![0, 1, 2]);
+        let report = weld(&mut mesh, Tolerance::Relative(1.0e-6)).unwrap();
+        assert_eq!(report.no_removed_vertices, 1);
+        assert_eq!(report.no_merged_vertices, 0);
+        assert_eq!(mesh.no_vertices(), 3);
+        assert_eq!(mesh.no_faces(), 1);
+    }
+
+    #[test]
+    fn test_weld_unchanged()
+    {
+        let mut mesh = cube(2);
+        let triangles = sorted_triangles(&mesh);
+        let report = weld(&mut mesh, Tolerance::Relative(1.0e-6)).unwrap();
+        assert_eq!((report.no_removed_vertices, report.no_merged_vertices, report.no_removed_faces), (0, 0, 0));
+        assert_eq!(sorted_triangles(&mesh), triangles);
+    }
+}